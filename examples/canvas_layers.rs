@@ -0,0 +1,114 @@
+//! Renders a scene into three independent canvas layers
+//! (background, entities and a light overlay) and composites
+//! them on the screen, each with its own blend mode.
+
+extern crate cgmath;
+extern crate good_web_game as ggez;
+
+use ggez::event;
+use ggez::graphics::{self, BlendMode, Canvas, Color, DrawMode, DrawParam, Drawable, Mesh, Rect};
+use ggez::timer;
+use ggez::{Context, GameResult};
+
+type Point2 = cgmath::Point2<f32>;
+
+struct MainState {
+    background: Canvas,
+    entities: Canvas,
+    lights: Canvas,
+    stripe: Mesh,
+    ball: Mesh,
+    glow: Mesh,
+}
+
+impl MainState {
+    fn new(ctx: &mut Context) -> GameResult<MainState> {
+        let background = Canvas::with_window_size(ctx)?;
+        // drawn with `BlendMode::Premultiplied`, the default for canvases
+        let entities = Canvas::with_window_size(ctx)?;
+        let mut lights = Canvas::with_window_size(ctx)?;
+        lights.set_blend_mode(Some(BlendMode::Add));
+
+        let stripe = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0., 0., 40., 2000.),
+            Color::new(0.2, 0.2, 0.3, 1.0),
+        )?;
+        let ball = Mesh::new_circle(
+            ctx,
+            DrawMode::fill(),
+            Point2::new(0., 0.),
+            30.,
+            0.5,
+            Color::new(0.9, 0.4, 0.1, 1.0),
+        )?;
+        let glow = Mesh::new_circle(
+            ctx,
+            DrawMode::fill(),
+            Point2::new(0., 0.),
+            120.,
+            1.0,
+            Color::new(0.3, 0.3, 0.1, 1.0),
+        )?;
+
+        Ok(MainState {
+            background,
+            entities,
+            lights,
+            stripe,
+            ball,
+            glow,
+        })
+    }
+}
+
+impl event::EventHandler for MainState {
+    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let t = timer::time_since_start_f64(ctx) as f32;
+        let (w, h) = graphics::size(ctx);
+
+        graphics::set_canvas(ctx, Some(&self.background));
+        graphics::clear(ctx, Color::new(0.1, 0.1, 0.15, 1.0));
+        let mut x = 0.;
+        while x < w {
+            graphics::draw(ctx, &self.stripe, (Point2::new(x, 0.),))?;
+            x += 80.;
+        }
+
+        graphics::set_canvas(ctx, Some(&self.entities));
+        graphics::clear(ctx, Color::new(0., 0., 0., 0.));
+        for i in 0..5 {
+            let phase = t + i as f32 * 1.2;
+            let pos = Point2::new(
+                w / 2. + phase.cos() * w / 3.,
+                h / 2. + (phase * 1.7).sin() * h / 3.,
+            );
+            graphics::draw(ctx, &self.ball, (pos,))?;
+        }
+
+        graphics::set_canvas(ctx, Some(&self.lights));
+        graphics::clear(ctx, Color::new(0., 0., 0., 0.));
+        let light = Point2::new(w / 2. + (t * 0.5).sin() * w / 4., h / 2.);
+        graphics::draw(ctx, &self.glow, (light,))?;
+
+        graphics::set_canvas(ctx, None);
+        graphics::clear(ctx, graphics::BLACK);
+        for layer in &[&self.background, &self.entities, &self.lights] {
+            graphics::draw(ctx, *layer, DrawParam::new())?;
+        }
+
+        graphics::present(ctx)?;
+        Ok(())
+    }
+}
+
+pub fn main() -> GameResult {
+    ggez::start(ggez::conf::Conf::default(), |mut context| {
+        Box::new(MainState::new(&mut context).unwrap())
+    })
+}
@@ -2,54 +2,232 @@ use crate::{error::GameError, filesystem, Context, GameResult};
 
 use quad_snd::{
     decoder,
-    mixer::{Sound, SoundMixer},
+    mixer::{PlaybackStyle, Sound, SoundId, SoundMixer, Volume},
 };
 
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::Duration;
 
 pub struct AudioContext {
     pub(crate) mixer: Rc<RefCell<Option<SoundMixer>>>,
+    levels: Rc<RefCell<Levels>>,
+    /// Volume ramps started by `crossfade`, advanced every update.
+    fades: Vec<Fade>,
 }
 
 impl AudioContext {
     pub fn new() -> AudioContext {
         AudioContext {
             mixer: Rc::new(RefCell::new(None)),
+            levels: Rc::new(RefCell::new(Levels {
+                master: 1.0,
+                groups: [1.0; 2],
+                voices: vec![],
+            })),
+            fades: vec![],
         }
     }
 }
+
+/// Volume groups, each with its own volume on top of the master volume,
+/// like the sliders of a settings menu.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Group {
+    /// The group of `Music` tracks.
+    Music,
+    /// The group of `Source` sounds.
+    Sfx,
+}
+
+/// Master and group volumes, and the playing sounds they apply to.
+struct Levels {
+    master: f32,
+    groups: [f32; 2],
+    voices: Vec<Voice>,
+}
+
+struct Voice {
+    id: SoundId,
+    group: Group,
+    volume: f32,
+    /// When a sound played once is over, from `timer::time()`.
+    ends_at: Option<f64>,
+}
+
+impl Levels {
+    fn apply(&self, mixer: &mut SoundMixer, voice: &Voice) {
+        let volume = voice.volume * self.groups[voice.group as usize] * self.master;
+        mixer.set_volume(voice.id, Volume(volume));
+    }
+
+    /// Starts tracking a sound, or updates its volume if it already is,
+    /// and forgets the sounds that are over.
+    fn set(&mut self, mixer: &mut SoundMixer, voice: Voice) {
+        let now = crate::timer::time();
+        self.voices
+            .retain(|v| v.id != voice.id && v.ends_at.map_or(true, |end| end > now));
+        self.apply(mixer, &voice);
+        self.voices.push(voice);
+    }
+
+    fn remove(&mut self, id: SoundId) {
+        self.voices.retain(|v| v.id != id);
+    }
+
+    /// Applies the current levels to every sound still playing.
+    fn apply_all(&mut self, mixer: &mut SoundMixer) {
+        let now = crate::timer::time();
+        self.voices
+            .retain(|voice| voice.ends_at.map_or(true, |end| end > now));
+        for voice in &self.voices {
+            self.apply(mixer, voice);
+        }
+    }
+}
+
+/// Sets the volume every sound is multiplied by, clamped to `[0, 1]`.
+/// Applies to the sounds already playing too. Defaults to `1.0`.
+pub fn set_master_volume(ctx: &mut Context, volume: f32) {
+    ctx.audio_context.levels.borrow_mut().master = volume.max(0.0).min(1.0);
+    apply_levels(ctx);
+}
+
+pub fn master_volume(ctx: &Context) -> f32 {
+    ctx.audio_context.levels.borrow().master
+}
+
+/// Sets the volume of a group, clamped to `[0, 1]`. A sound plays at its own
+/// volume times the volume of its group times the master volume.
+/// Applies to the sounds already playing too. Defaults to `1.0`.
+pub fn set_group_volume(ctx: &mut Context, group: Group, volume: f32) {
+    ctx.audio_context.levels.borrow_mut().groups[group as usize] = volume.max(0.0).min(1.0);
+    apply_levels(ctx);
+}
+
+pub fn group_volume(ctx: &Context, group: Group) -> f32 {
+    ctx.audio_context.levels.borrow().groups[group as usize]
+}
+
+fn apply_levels(ctx: &mut Context) {
+    if let Some(ref mut mixer) = &mut *ctx.audio_context.mixer.borrow_mut() {
+        ctx.audio_context.levels.borrow_mut().apply_all(mixer);
+    }
+}
+
+fn load_sound(ctx: &mut Context, path: &str) -> GameResult<Sound> {
+    use std::io::Read;
+
+    let mut file = filesystem::open(ctx, path)?;
+
+    let mut bytes = vec![];
+    file.bytes.read_to_end(&mut bytes)?;
+
+    if path.ends_with(".ogg") {
+        decoder::read_ogg(&bytes).map_err(|_| GameError::SoundError)
+    } else if path.ends_with(".wav") {
+        decoder::read_wav(&bytes).map_err(|_| GameError::SoundError)
+    } else {
+        panic!("Unsupported format. NOTE: gwg determines file format by path string, path should and with .wav or .ogg");
+    }
+}
+
 pub struct Source {
     sound: Sound,
     mixer: Rc<RefCell<Option<SoundMixer>>>,
+    levels: Rc<RefCell<Levels>>,
+    group: Group,
+    volume: f32,
+    /// Set with `set_max_distance`, used by `play_at`.
+    max_distance: Option<f32>,
 }
 
 impl Source {
     pub fn new(ctx: &mut Context, path: &str) -> GameResult<Source> {
-        use std::io::Read;
+        Ok(Source {
+            sound: load_sound(ctx, path)?,
+            mixer: ctx.audio_context.mixer.clone(),
+            levels: ctx.audio_context.levels.clone(),
+            group: Group::Sfx,
+            volume: 1.0,
+            max_distance: None,
+        })
+    }
 
-        let mut file = filesystem::open(ctx, path)?;
+    pub fn play(&mut self) -> GameResult<()> {
+        self.play_sound(self.sound.clone(), 1.0)
+    }
 
-        let mut bytes = vec![];
-        file.bytes.read_to_end(&mut bytes)?;
+    /// Plays the sound as if it came from `world_pos`, in the coordinates
+    /// things are drawn with: panned left or right by its horizontal offset
+    /// from the center of the [screen coordinates](../graphics/fn.screen_coordinates.html),
+    /// and quieter the further away it is.
+    ///
+    /// The volume falls linearly from the center to silence at the
+    /// distance set with [`set_max_distance()`](#method.set_max_distance).
+    /// Transforms pushed on the graphics context are not taken into account.
+    ///
+    /// The mixer has no per-sound panning, so a panned copy of the samples
+    /// is made on every call: fine for short effects, not for long tracks.
+    /// Sounds with more than two channels are only attenuated.
+    pub fn play_at<P>(&mut self, ctx: &Context, world_pos: P) -> GameResult<()>
+    where
+        P: Into<mint::Point2<f32>>,
+    {
+        let pos: mint::Point2<f32> = world_pos.into();
+        let camera = crate::graphics::screen_coordinates(ctx);
+        let (half_w, half_h) = (camera.w.abs() / 2., camera.h.abs() / 2.);
+        let (dx, dy) = (
+            pos.x - (camera.x + camera.w / 2.),
+            pos.y - (camera.y + camera.h / 2.),
+        );
 
-        let sound;
-        if path.ends_with(".ogg") {
-            sound = decoder::read_ogg(&bytes).map_err(|_| GameError::SoundError)?;
-        } else if path.ends_with(".wav") {
-            sound = decoder::read_wav(&bytes).map_err(|_| GameError::SoundError)?;
+        let max_distance = self
+            .max_distance
+            .unwrap_or_else(|| 2. * (half_w * half_w + half_h * half_h).sqrt());
+        let distance = (dx * dx + dy * dy).sqrt();
+        let attenuation = if max_distance > 0. {
+            (1. - distance / max_distance).max(0.)
         } else {
-            panic!("Unsupported format. NOTE: gwg determines file format by path string, path should and with .wav or .ogg");
+            1.
         };
-        Ok(Source {
-            sound,
-            mixer: ctx.audio_context.mixer.clone(),
-        })
+        // screen coordinates may be flipped, the right of the screen is still the right
+        let pan = if half_w > 0. {
+            (dx * camera.w.signum() / half_w).max(-1.).min(1.)
+        } else {
+            0.
+        };
+
+        self.play_sound(panned(&self.sound, pan), attenuation)
     }
 
-    pub fn play(&mut self) -> GameResult<()> {
+    /// Sets the distance, in screen coordinates, at which `play_at` is silent.
+    ///
+    /// `None`, the default, is the diagonal of the screen coordinates at the
+    /// time of playing: a sound in a corner of the screen plays at half volume.
+    pub fn set_max_distance(&mut self, distance: Option<f32>) {
+        self.max_distance = distance;
+    }
+
+    pub fn max_distance(&self) -> Option<f32> {
+        self.max_distance
+    }
+
+    fn play_sound(&mut self, sound: Sound, attenuation: f32) -> GameResult<()> {
         if let Some(ref mut mixer) = &mut *self.mixer.borrow_mut() {
-            mixer.play(self.sound.clone());
+            let length = sound.samples.len() as f64
+                / sound.channels.max(1) as f64
+                / sound.sample_rate as f64;
+            let id = mixer.play(sound);
+            self.levels.borrow_mut().set(
+                mixer,
+                Voice {
+                    id,
+                    group: self.group,
+                    volume: self.volume * attenuation,
+                    ends_at: Some(crate::timer::time() + length),
+                },
+            );
 
             Ok(())
         } else {
@@ -57,6 +235,24 @@ impl Source {
         }
     }
 
+    /// Sets the volume of the next plays, clamped to `[0, 1]`.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.max(0.0).min(1.0);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the group of the next plays, `Group::Sfx` by default.
+    pub fn set_group(&mut self, group: Group) {
+        self.group = group;
+    }
+
+    pub fn group(&self) -> Group {
+        self.group
+    }
+
     pub fn playing(&self) -> bool {
         false
     }
@@ -68,8 +264,125 @@ impl Source {
     /// Play source "in the background"; cannot be stopped
     /// anything blocking is impossible on wasm, so will display a warning and just play in background
     pub fn play_detached(&mut self) -> GameResult<()> {
+        self.play()
+    }
+
+    pub fn play_later(&mut self) {}
+
+    pub fn set_fade_in(&mut self, _dur: std::time::Duration) {}
+
+    pub fn set_pitch(&mut self, ratio: f32) {}
+}
+
+/// A stereo copy of `sound` balanced by `pan`, from `-1.0` (left only)
+/// through `0.0` (unchanged) to `1.0` (right only).
+fn panned(sound: &Sound, pan: f32) -> Sound {
+    let (left, right) = ((1. - pan).min(1.), (1. + pan).min(1.));
+    let mut panned = sound.clone();
+    match sound.channels {
+        1 => {
+            panned.channels = 2;
+            panned.samples = Vec::with_capacity(sound.samples.len() * 2);
+            for &sample in &sound.samples {
+                panned.samples.push(sample * left);
+                panned.samples.push(sample * right);
+            }
+        }
+        2 => {
+            for frame in panned.samples.chunks_mut(2) {
+                frame[0] *= left;
+                if let Some(sample) = frame.get_mut(1) {
+                    *sample *= right;
+                }
+            }
+        }
+        _ => {}
+    }
+    panned
+}
+
+/// A looping background track.
+///
+/// Unlike a `Source`, a playing `Music` can be stopped, its volume changed,
+/// and faded into another track with [`crossfade()`](fn.crossfade.html).
+/// Dropping a `Music` stops it.
+///
+/// ```rust,ignore
+/// let mut forest = audio::Music::new(ctx, "/forest.ogg")?;
+/// forest.play_looping()?;
+/// // later, entering the cave
+/// audio::crossfade(ctx, &mut forest, &mut cave, Duration::from_secs(2))?;
+/// ```
+pub struct Music {
+    sound: Sound,
+    mixer: Rc<RefCell<Option<SoundMixer>>>,
+    levels: Rc<RefCell<Levels>>,
+    state: Rc<RefCell<MusicState>>,
+}
+
+struct MusicState {
+    /// The playing sound, if any.
+    id: Option<SoundId>,
+    /// Set with `Music::set_volume`.
+    volume: f32,
+    /// Current level of the fade in or out, multiplies `volume`.
+    fade: f32,
+    group: Group,
+}
+
+impl MusicState {
+    fn apply_volume(&self, mixer: &mut SoundMixer, levels: &mut Levels) {
+        if let Some(id) = self.id {
+            let voice = Voice {
+                id,
+                group: self.group,
+                volume: self.volume * self.fade,
+                ends_at: None,
+            };
+            levels.set(mixer, voice);
+        }
+    }
+
+    fn stop(&mut self, mixer: &mut SoundMixer, levels: &mut Levels) {
+        if let Some(id) = self.id.take() {
+            mixer.stop(id);
+            levels.remove(id);
+        }
+    }
+}
+
+impl Music {
+    pub fn new(ctx: &mut Context, path: &str) -> GameResult<Music> {
+        let mut sound = load_sound(ctx, path)?;
+        sound.playback_style = PlaybackStyle::Looped;
+
+        Ok(Music {
+            sound,
+            mixer: ctx.audio_context.mixer.clone(),
+            levels: ctx.audio_context.levels.clone(),
+            state: Rc::new(RefCell::new(MusicState {
+                id: None,
+                volume: 1.0,
+                fade: 1.0,
+                group: Group::Music,
+            })),
+        })
+    }
+
+    /// Plays the track from the beginning, over and over until stopped.
+    /// Restarts it if it is already playing.
+    pub fn play_looping(&mut self) -> GameResult<()> {
+        self.start(1.0)
+    }
+
+    fn start(&mut self, fade: f32) -> GameResult<()> {
         if let Some(ref mut mixer) = &mut *self.mixer.borrow_mut() {
-            mixer.play(self.sound.clone());
+            let mut state = self.state.borrow_mut();
+            let mut levels = self.levels.borrow_mut();
+            state.stop(mixer, &mut levels);
+            state.id = Some(mixer.play(self.sound.clone()));
+            state.fade = fade;
+            state.apply_volume(mixer, &mut levels);
 
             Ok(())
         } else {
@@ -77,11 +390,137 @@ impl Source {
         }
     }
 
-    pub fn play_later(&mut self) {}
+    /// Stops the track, cancelling any fade it is part of.
+    pub fn stop(&mut self) {
+        if let Some(ref mut mixer) = &mut *self.mixer.borrow_mut() {
+            self.state
+                .borrow_mut()
+                .stop(mixer, &mut self.levels.borrow_mut());
+        }
+    }
 
-    pub fn set_fade_in(&mut self, _dur: std::time::Duration) {}
+    /// Returns true if the track was started and not stopped since,
+    /// including while it is fading out.
+    pub fn playing(&self) -> bool {
+        self.state.borrow().id.is_some()
+    }
 
-    pub fn set_pitch(&mut self, ratio: f32) {}
+    /// Sets the volume, clamped to `[0, 1]`; fades ramp between silence
+    /// and this volume.
+    pub fn set_volume(&mut self, volume: f32) {
+        let mut state = self.state.borrow_mut();
+        state.volume = volume.max(0.0).min(1.0);
+        if let Some(ref mut mixer) = &mut *self.mixer.borrow_mut() {
+            state.apply_volume(mixer, &mut self.levels.borrow_mut());
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.state.borrow().volume
+    }
+
+    /// Moves the track to another group, `Group::Music` by default.
+    pub fn set_group(&mut self, group: Group) {
+        let mut state = self.state.borrow_mut();
+        state.group = group;
+        if let Some(ref mut mixer) = &mut *self.mixer.borrow_mut() {
+            state.apply_volume(mixer, &mut self.levels.borrow_mut());
+        }
+    }
+
+    pub fn group(&self) -> Group {
+        self.state.borrow().group
+    }
+}
+
+impl Drop for Music {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Fade {
+    state: Rc<RefCell<MusicState>>,
+    from: f32,
+    to: f32,
+    started: Duration,
+    duration: Duration,
+}
+
+/// Fades `from` out and `to` in at the same time, over `duration`.
+///
+/// `to` is started from the beginning if it is not playing, `from` is
+/// stopped once silent. The volumes are ramped every
+/// [`update()`](../event/trait.EventHandler.html#tymethod.update), so
+/// the ramp is as smooth as the frame rate.
+pub fn crossfade(
+    ctx: &mut Context,
+    from: &mut Music,
+    to: &mut Music,
+    duration: Duration,
+) -> GameResult<()> {
+    if !to.playing() {
+        to.start(0.0)?;
+    }
+
+    let started = crate::timer::time_since_start(ctx);
+    let fades = &mut ctx.audio_context.fades;
+    // a track being faded again, like switching back midway, starts from where it is
+    fades.retain(|fade| {
+        !Rc::ptr_eq(&fade.state, &from.state) && !Rc::ptr_eq(&fade.state, &to.state)
+    });
+    for (music, target) in &[(&*from, 0.0), (&*to, 1.0)] {
+        fades.push(Fade {
+            state: music.state.clone(),
+            from: music.state.borrow().fade,
+            to: *target,
+            started,
+            duration,
+        });
+    }
+    update_fades(ctx);
+
+    Ok(())
+}
+
+/// Advances the fades started by `crossfade`, called every update.
+pub(crate) fn update_fades(ctx: &mut Context) {
+    if ctx.audio_context.fades.is_empty() {
+        return;
+    }
+
+    let now = crate::timer::time_since_start(ctx);
+    let (mixer, levels) = (
+        ctx.audio_context.mixer.clone(),
+        ctx.audio_context.levels.clone(),
+    );
+    let (mut mixer, mut levels) = (mixer.borrow_mut(), levels.borrow_mut());
+    let mixer = match &mut *mixer {
+        Some(mixer) => mixer,
+        None => return,
+    };
+
+    ctx.audio_context.fades.retain(|fade| {
+        let mut state = fade.state.borrow_mut();
+        let elapsed = now.checked_sub(fade.started).unwrap_or_default();
+        let t = if fade.duration == Duration::default() {
+            1.0
+        } else {
+            (crate::timer::duration_to_f64(elapsed) / crate::timer::duration_to_f64(fade.duration))
+                .min(1.0) as f32
+        };
+
+        state.fade = fade.from + (fade.to - fade.from) * t;
+        state.apply_volume(mixer, &mut levels);
+        if t < 1.0 && state.id.is_some() {
+            return true;
+        }
+
+        if state.fade <= 0.0 {
+            state.stop(mixer, &mut levels);
+        }
+        false
+    });
 }
 
 /// This function should be called in one of the interaction event callbacks before any usages of audio
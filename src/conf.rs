@@ -21,14 +21,105 @@ pub enum Loading {
     Embedded,
 }
 
+/// Options controlling how the window (or the canvas, on web) is created.
+///
+/// There is no `min_size`, `max_size` or `graphics::set_resizable()`: the
+/// miniquad version this is built on has no such options, on any platform,
+/// so the window is always resizable to any size, and on web the size of the
+/// canvas element is up to the page.
+/// Layouts that break below some size should handle
+/// [`resize_event()`](../event/trait.EventHandler.html#method.resize_event),
+/// for example by scaling or letterboxing the screen coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WindowMode {
+    /// Request a full-resolution framebuffer on high-DPI displays.
+    ///
+    /// When enabled, `graphics::drawable_size` reports physical pixels while
+    /// `graphics::size`, the default screen coordinates and all mouse/touch
+    /// positions are in logical units, `graphics::dpi_scale` times smaller.
+    /// When disabled, the platform upscales a low-resolution framebuffer and
+    /// all of these agree.
+    pub high_dpi: bool,
+    /// Treat the framebuffer as linear and convert it to sRGB when presenting,
+    /// so blending happens in linear space.
+    ///
+    /// miniquad can't request an sRGB swapchain, so the frame is rendered to an
+    /// offscreen canvas and drawn to the screen with a gamma correcting shader,
+    /// which costs one extra full-screen draw per frame. Colors and image
+    /// texels are then taken as linear values: blends of midtones come out
+    /// brighter and more even, while unconverted sRGB colors and images look
+    /// washed out.
+    pub srgb: bool,
+}
+
+impl Default for WindowMode {
+    fn default() -> WindowMode {
+        WindowMode {
+            high_dpi: false,
+            srgb: false,
+        }
+    }
+}
+
+impl WindowMode {
+    /// Set whether the window should use a high-DPI framebuffer.
+    pub fn high_dpi(mut self, high_dpi: bool) -> Self {
+        self.high_dpi = high_dpi;
+        self
+    }
+
+    /// Set whether the frame should be gamma corrected to sRGB when presented.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+}
+
+/// The graphics API to ask for, see `Conf::preferred_backend`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Whatever miniquad creates.
+    Auto,
+    /// OpenGL ES 2, WebGL 1 on web.
+    Gles2,
+    /// OpenGL ES 3, WebGL 2 on web.
+    Gles3,
+}
+
+/// How the game starts: window, resources, fonts.
+///
+/// There is no option to create the WebGL context with
+/// `preserveDrawingBuffer`: miniquad's `gl.js` creates it before any Rust
+/// code runs, without such a setting. See the README for patching `gl.js`.
 #[derive(Debug)]
 pub struct Conf {
     pub cache: Cache,
     pub loading: Loading,
+    pub window_mode: WindowMode,
     /// `Filesystem::open` will try to read from this dir if there's no such file in the cache.
     ///
     /// Note that this won't work on platforms where `std::fs` is unavailable, like WASM.
     pub physical_root_dir: Option<PathBuf>,
+    /// Bake the built-in DejaVuSerif font atlas at startup, making it available as `Font::default()`.
+    ///
+    /// Games that only use their own fonts, or draw no text at all, may turn this off to skip
+    /// rasterizing and uploading the atlas. Drawing text with the default font then fails
+    /// with `GameError::FontError`.
+    pub load_default_font: bool,
+    /// Pixel size the default font's glyphs are rasterized at.
+    ///
+    /// Text drawn at roughly this size looks the sharpest. Larger values keep big text crisp
+    /// but grow the atlas quadratically (each glyph takes about `size * size` texels);
+    /// smaller values save memory and startup time but make large text blurry.
+    pub default_font_size: u32,
+    /// The graphics API to use, to debug rendering differences between them.
+    ///
+    /// This is only diagnostic: the miniquad version this is built on can't
+    /// be asked for a given API, so the game always runs on the one it
+    /// created. With the `log-impl` feature, a warning is logged if that's
+    /// not this one. See
+    /// [`graphics::backend_info()`](../graphics/fn.backend_info.html).
+    pub preferred_backend: Backend,
 }
 
 impl Default for Conf {
@@ -36,7 +127,11 @@ impl Default for Conf {
         Conf {
             cache: Cache::No,
             loading: Loading::No,
+            window_mode: WindowMode::default(),
             physical_root_dir: None,
+            load_default_font: true,
+            default_font_size: 70,
+            preferred_backend: Backend::Auto,
         }
     }
 }
@@ -6,7 +6,10 @@ use crate::{
     conf::Conf,
     filesystem::Filesystem,
     graphics,
-    input::{input_handler::InputHandler, KeyboardContext, MouseContext},
+    input::{
+        input_handler::InputHandler, Bindings, EventPolicy, InputRecorder, KeyboardContext,
+        MouseContext,
+    },
     timer::TimeContext,
 };
 
@@ -18,6 +21,13 @@ pub struct Context {
     pub keyboard_context: KeyboardContext,
     pub timer_context: TimeContext,
     pub quad_ctx: miniquad::Context,
+    pub(crate) input_recorder: InputRecorder,
+    /// Set with `input::set_bindings`.
+    pub(crate) input_bindings: Bindings,
+    /// Set with `input::set_event_policy`.
+    pub(crate) event_policy: EventPolicy,
+    /// Set by `event::quit`, handled by the event loop.
+    pub(crate) quit_requested: bool,
 }
 
 impl Context {
@@ -26,19 +36,24 @@ impl Context {
 
         Context {
             filesystem: Filesystem::new(&conf),
-            gfx_context: graphics::GraphicsContext::new(&mut quad_ctx),
+            gfx_context: graphics::GraphicsContext::new(&mut quad_ctx, &conf),
             audio_context: audio::AudioContext::new(),
             mouse_context: MouseContext::new(input_handler.clone()),
             keyboard_context: KeyboardContext::new(input_handler.clone()),
             timer_context: TimeContext::new(),
             quad_ctx,
+            input_recorder: InputRecorder::default(),
+            input_bindings: Bindings::new(),
+            event_policy: EventPolicy::default(),
+            quit_requested: false,
         }
     }
 
     pub(crate) fn framebuffer(&mut self) -> Option<miniquad::RenderPass> {
-        self.gfx_context
-            .canvas
+        let gfx = &self.gfx_context;
+        gfx.canvas
             .as_ref()
+            .or_else(|| gfx.frame.as_ref().map(|frame| &frame.canvas))
             .map(|canvas| canvas.offscreen_pass.clone())
     }
 }
@@ -15,6 +15,14 @@ pub enum GameError {
     MixerNotCreated,
     SoundError,
     TTFError(miniquad_text_rusttype::Error),
+    /// Something went wrong with a font that is not a TTF parsing error,
+    /// like using a font that was never loaded
+    FontError(String),
+    /// Something went wrong while loading a resource, like an image
+    /// with invalid data
+    ResourceLoadError(String),
+    /// Something went wrong while drawing, like a batch over its fixed capacity
+    RenderError(String),
     UnknownError(&'static str),
 }
 
@@ -47,4 +47,44 @@ pub trait EventHandler {
     }
 
     fn key_up_event(&mut self, _ctx: &mut Context, _keycode: KeyCode, _keymods: KeyMods) {}
+
+    /// Called when the window is hidden (`gained == false`), like when the browser
+    /// tab is switched, and when it is shown again (`gained == true`).
+    ///
+    /// No frames are run while hidden. The first `timer::delta()` after it
+    /// is shown again is clamped, so the time spent hidden does not come
+    /// back as one huge frame.
+    fn focus_event(&mut self, _ctx: &mut Context, _gained: bool) {}
+
+    /// Called once per frame, after `draw()` returned and the frame was
+    /// presented, for frame-boundary bookkeeping like pushing metrics.
+    ///
+    /// Every frame runs `update()`, then `draw()`, then this. The timer
+    /// advances at the end of `update()`, so `timer::delta()` here is the
+    /// same as in the `draw()` that just finished.
+    fn after_present(&mut self, _ctx: &mut Context) {}
+
+    /// Called when quitting was asked for with [`quit()`](fn.quit.html),
+    /// to save the game or ask to save changes first.
+    ///
+    /// Return `true` to cancel quitting, for example to show a "save changes?"
+    /// dialog, and call `quit()` again once answered. The default lets the
+    /// game quit.
+    ///
+    /// It can't be wired to the window close button or the browser's
+    /// `beforeunload`: the miniquad version in use has no event for either,
+    /// and no way to cancel them. Closing the window or the tab quits
+    /// without calling this.
+    fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+        false
+    }
+}
+
+/// Asks the game to quit at the end of the current frame, unless
+/// [`EventHandler::quit_event()`](trait.EventHandler.html#method.quit_event)
+/// cancels it.
+///
+/// On the web there is no quitting a page: the game keeps running.
+pub fn quit(ctx: &mut Context) {
+    ctx.quit_requested = true;
 }
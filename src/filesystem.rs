@@ -20,7 +20,12 @@ impl io::Read for File {
 #[derive(Debug)]
 pub struct Filesystem {
     root: Option<path::PathBuf>,
+    /// Set with `set_root`, prepended to every path.
+    asset_root: path::PathBuf,
     files: HashMap<path::PathBuf, File>,
+    /// Mounted with `mount_zip`, the last one first.
+    #[cfg(feature = "zip")]
+    zips: Vec<zip::ZipArchive<io::Cursor<Vec<u8>>>>,
 }
 
 impl Filesystem {
@@ -50,18 +55,33 @@ impl Filesystem {
         }
 
         let root = conf.physical_root_dir.clone();
-        Filesystem { files, root }
+        Filesystem {
+            files,
+            root,
+            asset_root: path::PathBuf::new(),
+            #[cfg(feature = "zip")]
+            zips: vec![],
+        }
+    }
+
+    /// Where `path` is in the cache and below the physical root dir.
+    fn resolve(&self, path: &path::Path) -> path::PathBuf {
+        // workaround for ggez-style pathes: in ggez pathes starts with "/", while in the cache
+        // dictionary they are presented without "/"
+        let path = path.strip_prefix("/").unwrap_or(path);
+        self.asset_root.join(path)
     }
 
     /// Opens the given `path` and returns the resulting `File`
     /// in read-only mode.
     pub fn open<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult<File> {
-        let mut path = path::PathBuf::from(path.as_ref());
+        let path = self.resolve(path.as_ref());
 
-        // workaround for ggez-style pathes: in ggez pathes starts with "/", while in the cache
-        // dictionary they are presented without "/"
-        if let Ok(stripped) = path.strip_prefix("/") {
-            path = path::PathBuf::from(stripped);
+        #[cfg(feature = "zip")]
+        {
+            if let Some(file) = self.open_zipped(&path)? {
+                return Ok(file);
+            }
         }
 
         #[cfg(not(target_arch = "wasm32"))]
@@ -79,8 +99,73 @@ impl Filesystem {
         }
         Ok(self.files[&path].clone())
     }
+
+    /// Decompresses `path` from the most recently mounted archive that has it.
+    #[cfg(feature = "zip")]
+    fn open_zipped(&mut self, path: &path::Path) -> GameResult<Option<File>> {
+        use std::io::Read;
+
+        // zip entries are always separated by "/"
+        let name = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        for archive in self.zips.iter_mut().rev() {
+            let mut entry = match archive.by_name(&name) {
+                Ok(entry) => entry,
+                Err(zip::result::ZipError::FileNotFound) => continue,
+                Err(err) => return Err(crate::GameError::ResourceLoadError(err.to_string())),
+            };
+            let mut buf = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut buf)?;
+            return Ok(Some(File {
+                bytes: io::Cursor::new(buf),
+            }));
+        }
+        Ok(None)
+    }
 }
 
 pub fn open<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<File> {
     ctx.filesystem.open(path)
 }
+
+/// Mounts a zip archive: from then on, `open` looks for files in it first,
+/// then in the archives mounted before it, then in the usual places.
+///
+/// A single archive is one download on web instead of one per file.
+/// Files are decompressed when opened, the archive stays compressed in memory.
+/// Paths in the archive are matched against the opened paths with the root
+/// of [`set_root()`](fn.set_root.html) prepended, without a leading `/`.
+///
+/// Only stored and deflated files are supported. Needs the `zip` feature.
+#[cfg(feature = "zip")]
+pub fn mount_zip(ctx: &mut Context, bytes: Vec<u8>) -> GameResult {
+    let archive = zip::ZipArchive::new(io::Cursor::new(bytes))
+        .map_err(|err| crate::GameError::ResourceLoadError(err.to_string()))?;
+    ctx.filesystem.zips.push(archive);
+    Ok(())
+}
+
+/// Sets a directory every path is relative to, so assets can be moved
+/// without changing every path in the game: with `set_root(ctx, "resources")`,
+/// `open(ctx, "/player.png")` opens `resources/player.png`.
+///
+/// The root applies the same way everywhere: on desktop below
+/// [`Conf::physical_root_dir`](../conf/struct.Conf.html#structfield.physical_root_dir),
+/// and in the preloaded cache, like on web, where it is a prefix of the
+/// paths in the archive. Files are never fetched by URL, so the root is not
+/// a base URL: it is joined like a path, with or without a trailing slash,
+/// and its leading `/` is ignored, like the one of the opened paths.
+/// An empty root, the default, leaves paths untouched.
+pub fn set_root<P: AsRef<path::Path>>(ctx: &mut Context, root: P) {
+    let root = root.as_ref();
+    ctx.filesystem.asset_root = root.strip_prefix("/").unwrap_or(root).to_path_buf();
+}
+
+/// The directory set with [`set_root()`](fn.set_root.html).
+pub fn root(ctx: &Context) -> &path::Path {
+    &ctx.filesystem.asset_root
+}
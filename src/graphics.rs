@@ -1,25 +1,56 @@
+mod animation;
+mod atlas;
 mod canvas;
+mod color_matrix;
 mod context;
+mod debug;
+pub(crate) mod dirty;
+mod draw_queue;
 mod drawparam;
+pub(crate) mod frame;
+mod gl;
+mod gpu_timer;
 mod image;
+mod layers;
 #[cfg(feature = "mesh")]
 mod mesh;
+mod nine_slice;
+mod palette;
+mod particles;
 mod shader;
+mod spritesheet;
 mod text;
+mod tiled;
+mod tween;
 mod types;
 
+pub mod effects;
 pub mod spritebatch;
 
-use crate::error::GameResult;
+use crate::error::{GameError, GameResult};
 use crate::Context;
 
 pub use self::{
-    canvas::{set_canvas, Canvas},
+    animation::{Animation, PlayMode},
+    atlas::TextureAtlas,
+    canvas::{set_canvas, Canvas, CanvasLoadAction},
+    color_matrix::ColorMatrix,
     context::GraphicsContext,
+    debug::{clear_debug, debug_draw_atlas, debug_line, debug_point, flush_debug, AtlasId},
+    dirty::{is_dirty_tracking, mark_all_dirty, mark_dirty, set_dirty_tracking, set_skip_if_clean},
+    draw_queue::{flush_queue, queue},
     drawparam::DrawParam,
+    gpu_timer::{begin_gpu_timer, end_gpu_timer},
     image::*,
+    layers::{draw_to_layer, layer_stack, layer_stack_mut, set_layer_stack, LayerId, LayerStack},
+    nine_slice::{draw_nine_slice, BorderInsets},
+    palette::PalettedImage,
+    particles::ParticleSystem,
     shader::*,
+    spritesheet::SpriteSheet,
     text::*,
+    tiled::{draw_tiled, draw_tilemap},
+    tween::{Easing, Tween, Tweenable},
     types::*,
 };
 
@@ -29,17 +60,295 @@ pub use self::mesh::*;
 use miniquad::PassAction;
 
 /// Clear the screen to the background color.
+///
+/// With [dirty tracking](fn.set_dirty_tracking.html), only the dirty
+/// regions of the screen are cleared.
 pub fn clear(ctx: &mut Context, color: Color) {
+    if dirty::clear(ctx, color) {
+        return;
+    }
+
     let action = PassAction::Clear {
         color: Some((color.r, color.g, color.b, color.a)),
-        depth: None,
+        depth: Some(1.),
         stencil: None,
     };
 
     let pass = ctx.framebuffer();
     ctx.quad_ctx.begin_pass(pass, action);
-    ctx.quad_ctx
-        .clear(Some((color.r, color.g, color.b, color.a)), None, None);
+    ctx.quad_ctx.end_render_pass();
+}
+
+/// Sets the color the screen is cleared to at the beginning of every frame,
+/// before [`draw()`](../event/trait.EventHandler.html#tymethod.draw) is called.
+///
+/// `None`, the default, disables the automatic clear: the screen is then
+/// left as it is, so call [`clear()`](fn.clear.html) yourself. Use a
+/// transparent color to clear to transparent black.
+pub fn set_clear_color(ctx: &mut Context, color: Option<Color>) {
+    ctx.gfx_context.clear_color = color;
+}
+
+/// Returns the color set with [`set_clear_color()`](fn.set_clear_color.html), if any.
+pub fn clear_color(ctx: &Context) -> Option<Color> {
+    ctx.gfx_context.clear_color
+}
+
+/// Draws a quad of the given color over the whole screen (or canvas),
+/// blended with what is already there.
+///
+/// Unlike [`clear()`](fn.clear.html), which replaces pixels, this respects
+/// the global blend mode, so a translucent color gives a fade:
+///
+/// ```rust,ignore
+/// graphics::draw_overlay(ctx, Color::new(0., 0., 0., self.fade));
+/// ```
+///
+/// The current transform and shader are ignored, the quad always covers
+/// [`screen_coordinates()`](fn.screen_coordinates.html).
+pub fn draw_overlay(ctx: &mut Context, color: Color) -> GameResult {
+    let white_image = ctx.gfx_context.white_image.clone();
+    let rect = ctx.gfx_context.screen_rect;
+    let shader = ctx.gfx_context.shader.take();
+    ctx.gfx_context.push_transform(&cgmath::One::one());
+
+    let result = white_image.draw(
+        ctx,
+        DrawParam::new()
+            .dest(Point2::new(rect.x, rect.y))
+            .scale(Vector2::new(rect.w, rect.h))
+            .color(color),
+    );
+
+    ctx.gfx_context.pop_transform();
+    ctx.gfx_context.shader = shader;
+    result
+}
+
+/// Returns a 1x1 white `Image`, handy to draw solid rectangles
+/// by setting `DrawParam::scale` and `DrawParam::color`.
+///
+/// It shares the texture the engine already uses internally,
+/// so nothing new is allocated on the GPU.
+///
+/// ```rust,ignore
+/// let pixel = graphics::white_image(ctx);
+/// graphics::draw(ctx, &pixel, DrawParam::new().dest(pos).scale(Vector2::new(w, h)).color(RED))?;
+/// ```
+pub fn white_image(ctx: &Context) -> Image {
+    ctx.gfx_context.white_image.clone()
+}
+
+/// Restricts all drawing to the given rectangle, in screen coordinates
+/// (see [`screen_coordinates()`](fn.screen_coordinates.html)), until it is
+/// reset with `None`. The current transform does not apply to it.
+///
+/// A [`DrawParam::clip`](struct.DrawParam.html#structfield.clip) is
+/// intersected with it.
+pub fn set_scissor(ctx: &mut Context, rect: Option<Rect>) {
+    ctx.gfx_context.scissor = rect;
+}
+
+/// Returns the scissor set with [`set_scissor()`](fn.set_scissor.html), if any.
+pub fn scissor(ctx: &Context) -> Option<Rect> {
+    ctx.gfx_context.scissor
+}
+
+/// A rectangle in framebuffer pixels, origin at the bottom-left like GL wants it.
+type PixelRect = (i32, i32, i32, i32);
+
+/// Begins the render pass of a single draw call on the current target,
+/// restricted to the global scissor and to `clip`.
+pub(crate) fn begin_draw_pass(ctx: &mut Context, clip: Option<Rect>) {
+    let pass = ctx.framebuffer();
+    ctx.quad_ctx.begin_pass(pass, PassAction::Nothing);
+
+    let global = ctx
+        .gfx_context
+        .scissor
+        .map(|rect| to_pixel_rect(ctx, ctx.gfx_context.projection, rect));
+    let local = clip.map(|rect| to_pixel_rect(ctx, ctx.gfx_context.transformed_projection(), rect));
+
+    let scissor = match (global, local) {
+        (Some(a), Some(b)) => Some(intersect_pixel_rects(a, b)),
+        (a, b) => a.or(b),
+    };
+    if let Some((x, y, w, h)) = scissor {
+        ctx.quad_ctx.apply_scissor_rect(x, y, w, h);
+    }
+}
+
+/// Size of the current render target in pixels.
+pub(crate) fn target_size(ctx: &Context) -> (f32, f32) {
+    match ctx.gfx_context.canvas {
+        Some(ref canvas) => {
+            let image = canvas.image();
+            (f32::from(image.width()), f32::from(image.height()))
+        }
+        None => {
+            let (w, h) = drawable_size(ctx);
+            (w as f32, h as f32)
+        }
+    }
+}
+
+/// Bounds of `rect` on the current render target after `projection`.
+fn to_pixel_rect(ctx: &Context, projection: cgmath::Matrix4<f32>, rect: Rect) -> PixelRect {
+    let (target_w, target_h) = target_size(ctx);
+
+    let corners = [
+        (rect.left(), rect.top()),
+        (rect.right(), rect.top()),
+        (rect.right(), rect.bottom()),
+        (rect.left(), rect.bottom()),
+    ];
+    let (mut x_min, mut y_min) = (std::f32::MAX, std::f32::MAX);
+    let (mut x_max, mut y_max) = (std::f32::MIN, std::f32::MIN);
+    for (x, y) in corners.iter() {
+        let ndc = projection * cgmath::Vector4::new(*x, *y, 0., 1.);
+        let px = (ndc.x / ndc.w + 1.) / 2. * target_w;
+        let py = (ndc.y / ndc.w + 1.) / 2. * target_h;
+        x_min = f32::min(x_min, px);
+        y_min = f32::min(y_min, py);
+        x_max = f32::max(x_max, px);
+        y_max = f32::max(y_max, py);
+    }
+
+    let (x, y) = (x_min.round() as i32, y_min.round() as i32);
+    (x, y, x_max.round() as i32 - x, y_max.round() as i32 - y)
+}
+
+fn intersect_pixel_rects(a: PixelRect, b: PixelRect) -> PixelRect {
+    let x = a.0.max(b.0);
+    let y = a.1.max(b.1);
+    let right = (a.0 + a.2).min(b.0 + b.2);
+    let top = (a.1 + a.3).min(b.1 + b.3);
+    (x, y, (right - x).max(0), (top - y).max(0))
+}
+
+/// Converts `rect`, in [screen coordinates](fn.set_screen_coordinates.html),
+/// to `(x, y, width, height)` pixels of the current render target, the way
+/// miniquad viewports and scissor rects want them.
+///
+/// The DPI scale is accounted for, and the origin is at the bottom-left,
+/// so the top of the screen has the largest `y`. The transform set with
+/// `push_transform` is ignored. Rotated screen coordinates give the
+/// bounds of the rotated rect.
+pub fn rect_to_viewport(ctx: &Context, rect: Rect) -> (i32, i32, i32, i32) {
+    to_pixel_rect(ctx, ctx.gfx_context.projection, rect)
+}
+
+/// The inverse of [`rect_to_viewport()`](fn.rect_to_viewport.html): converts
+/// `(x, y, width, height)` pixels of the current render target, origin at
+/// the bottom-left, to a rect in screen coordinates.
+pub fn viewport_to_rect(ctx: &Context, viewport: (i32, i32, i32, i32)) -> Rect {
+    use cgmath::SquareMatrix;
+
+    let (target_w, target_h) = target_size(ctx);
+    let inverse = ctx
+        .gfx_context
+        .projection
+        .invert()
+        .unwrap_or_else(cgmath::Matrix4::identity);
+
+    let (x, y, w, h) = viewport;
+    let to_screen = |px: i32, py: i32| {
+        let ndc = cgmath::Vector4::new(
+            px as f32 / target_w * 2. - 1.,
+            py as f32 / target_h * 2. - 1.,
+            0.,
+            1.,
+        );
+        let point = inverse * ndc;
+        (point.x / point.w, point.y / point.w)
+    };
+    let (x0, y0) = to_screen(x, y);
+    let (x1, y1) = to_screen(x + w, y + h);
+
+    let (left, top) = (x0.min(x1), y0.min(y1));
+    Rect::new(left, top, x0.max(x1) - left, y0.max(y1) - top)
+}
+
+/// Returns the largest width and height of a texture the GPU supports.
+///
+/// Creating a larger `Image` or `Canvas` fails; this may be as low as
+/// 4096 on mobile WebGL, so split big images into tiles.
+pub fn max_texture_size(ctx: &Context) -> u32 {
+    ctx.gfx_context.max_texture_size
+}
+
+/// The graphics API the game runs on, see
+/// [`backend_info()`](fn.backend_info.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendInfo {
+    /// `"OpenGL"`, `"OpenGL ES"` or `"WebGL"`.
+    pub name: String,
+    /// The version string reported by the driver, which starts with the API
+    /// version and often names the driver, like `"OpenGL ES 3.0 Mesa 20.0"`.
+    /// Empty where it can't be queried, like on macOS.
+    pub version: String,
+    /// See [`max_texture_size()`](fn.max_texture_size.html).
+    pub max_texture_size: u32,
+}
+
+impl BackendInfo {
+    /// True if this is the API `backend` asks for, always for `Backend::Auto`.
+    pub fn is(&self, backend: crate::conf::Backend) -> bool {
+        use crate::conf::Backend;
+
+        let major = self
+            .version
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .chars()
+            .next()
+            .and_then(|c| c.to_digit(10));
+        // WebGL 1 and 2 are OpenGL ES 2 and 3, desktop GL is neither
+        let es_major = match self.name.as_str() {
+            "WebGL" => major.map(|major| major + 1),
+            "OpenGL ES" => major,
+            _ => None,
+        };
+        match backend {
+            Backend::Auto => true,
+            Backend::Gles2 => es_major == Some(2),
+            Backend::Gles3 => es_major == Some(3),
+        }
+    }
+}
+
+/// Reports the graphics API the game runs on, to tell apart rendering
+/// differences between browsers, drivers and WebGL versions.
+///
+/// On web it is always WebGL 1, the only one miniquad creates there.
+pub fn backend_info(ctx: &Context) -> BackendInfo {
+    let version = self::gl::version();
+    let name = if version.starts_with("WebGL") {
+        "WebGL"
+    } else if version.starts_with("OpenGL ES") {
+        "OpenGL ES"
+    } else {
+        "OpenGL"
+    };
+
+    BackendInfo {
+        name: name.to_string(),
+        version,
+        max_texture_size: ctx.gfx_context.max_texture_size,
+    }
+}
+
+/// Sets the blend mode used by every `Drawable` that has no
+/// blend mode of its own (see [`Drawable::set_blend_mode()`](trait.Drawable.html#tymethod.set_blend_mode)).
+///
+/// Defaults to `BlendMode::Alpha`.
+pub fn set_blend_mode(ctx: &mut Context, mode: BlendMode) -> GameResult {
+    ctx.gfx_context.blend_mode = mode;
+    Ok(())
+}
+
+/// Returns the current global blend mode.
+pub fn blend_mode(ctx: &Context) -> BlendMode {
+    ctx.gfx_context.blend_mode
 }
 
 /// Draws the given `Drawable` object to the screen by calling its
@@ -53,6 +362,227 @@ where
     drawable.draw(ctx, params)
 }
 
+/// Like [`draw()`](fn.draw.html), but also returns the axis-aligned bounds
+/// of what was drawn, for debugging layouts or finding overlaps.
+///
+/// The bounds are in screen coordinates, with the current transform
+/// applied, so a box drawn around them should be drawn with the identity
+/// transform. They are computed from the drawable's
+/// [`dimensions()`](trait.Drawable.html#method.dimensions), scaled by
+/// `src`, `scale` and rotated like an image is, and are `None` for
+/// drawables of unknown size.
+pub fn draw_measured<D, T>(ctx: &mut Context, drawable: &D, params: T) -> GameResult<Option<Rect>>
+where
+    D: Drawable,
+    T: Into<DrawParam>,
+{
+    let params = params.into();
+    drawable.draw(ctx, params)?;
+
+    let local = match drawable.dimensions(ctx) {
+        Some(local) => local,
+        None => return Ok(None),
+    };
+    let mut quad = params;
+    quad.scale = mint::Vector2 {
+        x: params.scale.x * params.src.w * local.w,
+        y: params.scale.y * params.src.h * local.h,
+    };
+    let model = self::image::param_to_instance_transform(&quad);
+    // the rect origin, moved along with the rotation and scale
+    let (sin, cos) = params.rotation.sin_cos();
+    let (ox, oy) = (local.x * params.scale.x, local.y * params.scale.y);
+    let origin = (ox * cos - oy * sin, ox * sin + oy * cos);
+
+    let transform = ctx.gfx_context.transform();
+    let corners = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)]
+        .iter()
+        .map(|&(u, v)| {
+            let p = model * cgmath::Vector4::new(u, v, 0., 1.);
+            let p = transform * cgmath::Vector3::new(p.x + origin.0, p.y + origin.1, 1.);
+            (p.x / p.z, p.y / p.z)
+        });
+
+    let (mut min, mut max) = (
+        (std::f32::MAX, std::f32::MAX),
+        (std::f32::MIN, std::f32::MIN),
+    );
+    for (x, y) in corners {
+        min = (min.0.min(x), min.1.min(y));
+        max = (max.0.max(x), max.1.max(y));
+    }
+    Ok(Some(Rect::new(min.0, min.1, max.0 - min.0, max.1 - min.1)))
+}
+
+/// Like [`draw()`](fn.draw.html), but in window pixels, ignoring the screen
+/// coordinates and the transform: `(0, 0)` is the top-left corner and one
+/// unit is one logical pixel, like the default screen coordinates.
+///
+/// Handy for a HUD on top of a scrolled or zoomed world. The screen
+/// coordinates and the transform are restored afterwards, and the scissor
+/// rect, if any, still clips the same part of the window.
+pub fn draw_screen<D, T>(ctx: &mut Context, drawable: &D, params: T) -> GameResult
+where
+    D: Drawable,
+    T: Into<DrawParam>,
+{
+    let params = params.into();
+
+    let screen_rect = ctx.gfx_context.screen_rect;
+    let scissor = ctx.gfx_context.scissor;
+    let scissor_pixels = scissor.map(|rect| rect_to_viewport(ctx, rect));
+
+    let (w, h) = size(ctx);
+    ctx.gfx_context
+        .set_screen_coordinates(Rect::new(0., 0., w, h));
+    ctx.gfx_context.scissor = scissor_pixels.map(|pixels| viewport_to_rect(ctx, pixels));
+    ctx.gfx_context.push_transform(&cgmath::One::one());
+
+    let result = drawable.draw(ctx, params);
+
+    ctx.gfx_context.pop_transform();
+    ctx.gfx_context.scissor = scissor;
+    ctx.gfx_context.set_screen_coordinates(screen_rect);
+    result
+}
+
+/// Draws many instances of `image` in a single draw call, with
+/// already computed per-instance attributes.
+///
+/// This is what a [`SpriteBatch`](spritebatch/struct.SpriteBatch.html) does
+/// under the hood, minus the `DrawParam` to matrix conversion and without
+/// keeping anything around: handy when the instance data comes from
+/// elsewhere, like an ECS. The image blend mode and the current transform
+/// apply to all of them.
+///
+/// ```rust,ignore
+/// let instances: Vec<_> = particles
+///     .iter()
+///     .map(|p| InstanceAttributes::from_param(&image, &DrawParam::new().dest(p.pos)))
+///     .collect();
+/// graphics::draw_instanced(ctx, &image, &instances)?;
+/// ```
+pub fn draw_instanced(
+    ctx: &mut Context,
+    image: &Image,
+    instances: &[InstanceAttributes],
+) -> GameResult {
+    use crate::graphics::context::{batch_shader, PipelineKind};
+
+    if instances.is_empty() {
+        return Ok(());
+    }
+
+    image.apply_filter(ctx);
+    let instance_buffer = ctx
+        .gfx_context
+        .instance_buffer(&mut ctx.quad_ctx, instances.len());
+    instance_buffer.update(&mut ctx.quad_ctx, instances);
+
+    let bindings = miniquad::Bindings {
+        vertex_buffers: vec![image.bindings.vertex_buffers[0], instance_buffer],
+        index_buffer: image.bindings.index_buffer,
+        images: vec![image.texture],
+    };
+
+    begin_draw_pass(ctx, None);
+    let pipeline =
+        ctx.gfx_context
+            .pipeline(&mut ctx.quad_ctx, PipelineKind::Sprite, image.blend_mode());
+    ctx.quad_ctx.apply_pipeline(&pipeline);
+    ctx.quad_ctx.apply_bindings(&bindings);
+    ctx.quad_ctx.apply_uniforms(&batch_shader::Uniforms {
+        projection: ctx.gfx_context.transformed_projection(),
+        model: cgmath::One::one(),
+        color_add: Color::new(0., 0., 0., 0.).into(),
+        depth: 0.,
+    });
+    ctx.quad_ctx.draw(0, 6, instances.len() as i32);
+    ctx.quad_ctx.end_render_pass();
+
+    Ok(())
+}
+
+/// Draws once, with nothing visible, with each pipeline the built-in
+/// drawables use for the given blend modes, so they're ready before the
+/// game starts: a loading screen is a good place to call it.
+///
+/// The first draw with a pipeline is slow on some backends, browsers in
+/// particular, which may only finish compiling and linking its shaders then
+/// and stutter on the first frame that draws a new kind of thing. Pipelines
+/// are made of a blend mode and one of:
+///
+/// - the image shader, for `Image` and `Canvas`,
+/// - the image shader with a color matrix, see `DrawParam::color_matrix`,
+/// - the image shader with an alpha test, see `Image::set_alpha_threshold`,
+/// - the sprite shader, for sprite batches, particles and `draw_instanced`,
+/// - the sprite shader with a color per corner,
+/// - the mesh shader, also used by the debug shapes,
+/// - the palette shader of `PalettedImage`,
+/// - each custom shader, see [`precompile_shader()`](fn.precompile_shader.html).
+///
+/// `Text` is drawn by its own pipeline, which isn't covered.
+///
+/// Variants for the current render target are made: canvases with a depth
+/// buffer use other ones, and so do meshes with face culling. Only the image
+/// and sprite shaders are actually drawn with, the others have their
+/// pipelines created but may still stutter once.
+///
+/// ```rust,ignore
+/// graphics::precompile_pipelines(ctx, &[BlendMode::Alpha, BlendMode::Add])?;
+/// ```
+pub fn precompile_pipelines(ctx: &mut Context, blend_modes: &[BlendMode]) -> GameResult {
+    precompile(ctx, None, blend_modes)
+}
+
+/// Like [`precompile_pipelines()`](fn.precompile_pipelines.html), for drawing
+/// `Image`s and `Canvas`es with a custom shader.
+pub fn precompile_shader(
+    ctx: &mut Context,
+    shader: &Shader,
+    blend_modes: &[BlendMode],
+) -> GameResult {
+    precompile(ctx, Some(*shader), blend_modes)
+}
+
+fn precompile(ctx: &mut Context, shader: Option<Shader>, blend_modes: &[BlendMode]) -> GameResult {
+    use crate::graphics::context::PipelineKind;
+
+    let mut image = white_image(ctx);
+    // the shaders run on a quad of no size, which covers no pixel
+    let nothing = DrawParam::new().scale(Vector2::new(0., 0.));
+
+    let previous = ctx.gfx_context.shader;
+    ctx.gfx_context.shader = shader;
+    let result = blend_modes.iter().try_for_each(|&mode| {
+        image.set_blend_mode(Some(mode));
+        image.draw(ctx, nothing)?;
+        if shader.is_some() {
+            return Ok(());
+        }
+
+        image.draw(ctx, nothing.color_matrix(ColorMatrix::IDENTITY))?;
+        draw_instanced(
+            ctx,
+            &image,
+            &[InstanceAttributes::from_param(&image, &nothing)],
+        )?;
+        for &kind in &[
+            PipelineKind::AlphaTest,
+            PipelineKind::GradientSprite,
+            PipelineKind::Mesh,
+            PipelineKind::Palette,
+        ] {
+            ctx.gfx_context
+                .pipeline(&mut ctx.quad_ctx, kind, Some(mode));
+        }
+        Ok(())
+    });
+    ctx.gfx_context.shader = previous;
+
+    result
+}
+
 pub fn set_transform(context: &mut Context, transform: &cgmath::Matrix3<f32>) {
     let gfx = &mut context.gfx_context;
     gfx.set_transform(transform);
@@ -63,23 +593,171 @@ pub fn push_transform(context: &mut Context, transform: &cgmath::Matrix3<f32>) {
     gfx.push_transform(transform);
 }
 
-/// Returns the size of the window in pixels as (width, height),
-/// including borders, titlebar, etc.
-/// Returns zeros if the window doesn't exist.
-/// TODO: Rename, since get_drawable_size is usually what we
-/// actually want. Maybe get_entire_size or get_window_border_size?
+/// Pops the transform pushed by the last [`push_transform()`](fn.push_transform.html).
+/// Popping the last remaining transform does nothing.
+pub fn pop_transform(context: &mut Context) {
+    context.gfx_context.pop_transform();
+}
+
+/// Saves the render state, to be restored by [`pop_state()`](fn.pop_state.html).
+///
+/// The state is made of exactly:
+/// * the canvas, see [`set_canvas()`](fn.set_canvas.html)
+/// * the global blend mode, see [`set_blend_mode()`](fn.set_blend_mode.html)
+/// * the shader, see [`set_shader()`](fn.set_shader.html)
+/// * the scissor rect, see [`set_scissor()`](fn.set_scissor.html)
+///
+/// The transform has its own stack, see [`push_transform()`](fn.push_transform.html).
+/// Shader parameters, the clear color and the line width are not saved.
+///
+/// ```rust,ignore
+/// graphics::push_state(ctx);
+/// graphics::set_scissor(ctx, Some(widget.bounds));
+/// graphics::set_blend_mode(ctx, BlendMode::Add)?;
+/// widget.draw(ctx)?;
+/// graphics::pop_state(ctx);
+/// ```
+pub fn push_state(ctx: &mut Context) {
+    let gfx = &mut ctx.gfx_context;
+    let state = context::RenderState {
+        canvas: gfx.canvas.clone(),
+        blend_mode: gfx.blend_mode,
+        shader: gfx.shader,
+        scissor: gfx.scissor,
+    };
+    gfx.state_stack.push(state);
+}
+
+/// Restores the render state saved by the last [`push_state()`](fn.push_state.html).
+/// Does nothing if there is none.
+pub fn pop_state(ctx: &mut Context) {
+    let gfx = &mut ctx.gfx_context;
+    if let Some(state) = gfx.state_stack.pop() {
+        gfx.canvas = state.canvas;
+        gfx.blend_mode = state.blend_mode;
+        gfx.shader = state.shader;
+        gfx.scissor = state.scissor;
+    }
+}
+
+/// Returns the transform currently applied to everything drawn,
+/// in addition to the screen coordinates projection.
+pub fn transform(context: &Context) -> cgmath::Matrix3<f32> {
+    context.gfx_context.transform()
+}
+
+/// Returns the axis-aligned bounds of `rect` after applying the current transform,
+/// in the same units as [`screen_coordinates()`](fn.screen_coordinates.html).
+///
+/// Rotation makes the bounds larger than the rect itself. Handy for culling:
+///
+/// ```rust,ignore
+/// let visible = graphics::transformed_bounds(ctx, sprite_rect)
+///     .overlaps(&graphics::screen_coordinates(ctx));
+/// ```
+pub fn transformed_bounds(context: &Context, rect: Rect) -> Rect {
+    use cgmath::Transform;
+
+    let transform = context.gfx_context.transform();
+    let corners = [
+        Point2::new(rect.left(), rect.top()),
+        Point2::new(rect.right(), rect.top()),
+        Point2::new(rect.right(), rect.bottom()),
+        Point2::new(rect.left(), rect.bottom()),
+    ];
+
+    let p0 = transform.transform_point(corners[0]);
+    let (mut x_min, mut y_min, mut x_max, mut y_max) = (p0.x, p0.y, p0.x, p0.y);
+    for corner in &corners[1..] {
+        let p = transform.transform_point(*corner);
+        x_min = f32::min(x_min, p.x);
+        y_min = f32::min(y_min, p.y);
+        x_max = f32::max(x_max, p.x);
+        y_max = f32::max(y_max, p.y);
+    }
+
+    Rect::new(x_min, y_min, x_max - x_min, y_max - y_min)
+}
+
+/// Returns where `point`, in the coordinates things are drawn with, ends up
+/// in the window: the screen coordinates projection and the current
+/// transform are applied.
+///
+/// The result is in logical pixels from the top-left corner of the window,
+/// like mouse positions and CSS pixels on web, handy to overlay HTML
+/// elements on the canvas. Everything is drawn to the whole window, there
+/// is no viewport offset to account for.
+pub fn world_to_screen<P>(ctx: &Context, point: P) -> Point2
+where
+    P: Into<mint::Point2<f32>>,
+{
+    let point: mint::Point2<f32> = point.into();
+    let (w, h) = size(ctx);
+
+    let ndc =
+        ctx.gfx_context.transformed_projection() * cgmath::Vector4::new(point.x, point.y, 0., 1.);
+    Point2::new((ndc.x / ndc.w + 1.) / 2. * w, (1. - ndc.y / ndc.w) / 2. * h)
+}
+
+/// The inverse of [`world_to_screen()`](fn.world_to_screen.html): returns the
+/// point, in the coordinates things are drawn with, drawn at `point` in
+/// logical pixels from the top-left of the window, like a mouse position.
+pub fn screen_to_world<P>(ctx: &Context, point: P) -> Point2
+where
+    P: Into<mint::Point2<f32>>,
+{
+    use cgmath::SquareMatrix;
+
+    let point: mint::Point2<f32> = point.into();
+    let (w, h) = size(ctx);
+
+    let inverse = ctx
+        .gfx_context
+        .transformed_projection()
+        .invert()
+        .unwrap_or_else(cgmath::Matrix4::identity);
+    let ndc = cgmath::Vector4::new(point.x / w * 2. - 1., 1. - point.y / h * 2., 0., 1.);
+    let world = inverse * ndc;
+    Point2::new(world.x / world.w, world.y / world.w)
+}
+
+/// Returns the size of the window in logical pixels as (width, height).
+///
+/// This is the unit used by the default screen coordinates and by mouse
+/// and touch positions.
 pub fn size(ctx: &Context) -> (f32, f32) {
     let size = ctx.quad_ctx.screen_size();
-    (size.0, size.1)
+    let dpi_scale = dpi_scale(ctx);
+    (size.0 / dpi_scale, size.1 / dpi_scale)
 }
 
 /// Returns the size of the window's underlying drawable in pixels as (width, height).
-/// This may return a different value than `get_size()` when run on a platform with high-DPI support
+/// This may return a different value than `size()` when run on a platform with high-DPI support
+/// and [`WindowMode::high_dpi`](../conf/struct.WindowMode.html#structfield.high_dpi) enabled.
 pub fn drawable_size(ctx: &Context) -> (u32, u32) {
     let size = ctx.quad_ctx.screen_size();
     (size.0 as u32, size.1 as u32)
 }
 
+/// Returns the number of physical pixels per logical pixel.
+///
+/// Always `1.0` unless the window was created with
+/// [`WindowMode::high_dpi`](../conf/struct.WindowMode.html#structfield.high_dpi).
+pub fn dpi_scale(ctx: &Context) -> f32 {
+    ctx.quad_ctx.dpi_scale()
+}
+
+/// Tells the game it has the keyboard focus, see
+/// [`keyboard::has_focus()`](../input/keyboard/fn.has_focus.html), for
+/// example after closing an HTML overlay.
+///
+/// miniquad gives no way to focus the canvas element itself: if the browser
+/// sends key events elsewhere, the page has to call `canvas.focus()`, which
+/// needs a `tabindex` attribute on the canvas.
+pub fn request_focus(ctx: &mut Context) {
+    ctx.keyboard_context.focused = true;
+}
+
 /// Sets the bounds of the screen viewport.
 ///
 /// The default coordinate system has (0,0) at the top-left corner
@@ -90,11 +768,65 @@ pub fn drawable_size(ctx: &Context) -> (u32, u32) {
 ///
 /// The `Rect`'s x and y will define the top-left corner of the screen,
 /// and that plus its w and h will define the bottom-right corner.
+///
+/// With [`set_camera_snap()`](fn.set_camera_snap.html), the corner is
+/// moved to the nearest whole pixel of the render target.
 pub fn set_screen_coordinates(context: &mut Context, rect: Rect) -> GameResult {
+    let mut rect = rect;
+    if context.gfx_context.camera_snap {
+        let (width, height) = target_size(context);
+        // screen coordinate units per pixel, accounting for the zoom
+        let (px, py) = (rect.w / width, rect.h / height);
+        if px != 0. && py != 0. {
+            rect.x = (rect.x / px).round() * px;
+            rect.y = (rect.y / py).round() * py;
+        }
+    }
     context.gfx_context.set_screen_coordinates(rect);
     Ok(())
 }
 
+/// Rounds the corner of the screen coordinates to whole pixels of the render
+/// target in [`set_screen_coordinates()`](fn.set_screen_coordinates.html),
+/// the usual way to scroll a camera, so pixel art doesn't shimmer when
+/// scrolling by fractions of a pixel. The size, and so the zoom, is kept.
+///
+/// Unlike [`set_text_snap()`](fn.set_text_snap.html), this moves the whole
+/// projection: everything drawn moves by the same whole number of pixels.
+/// Smoothly scrolling games should leave it off, the default. Applies
+/// from the next `set_screen_coordinates()`.
+pub fn set_camera_snap(ctx: &mut Context, snap: bool) {
+    ctx.gfx_context.camera_snap = snap;
+}
+
+pub fn camera_snap(ctx: &Context) -> bool {
+    ctx.gfx_context.camera_snap
+}
+
+/// Sets screen coordinates spanning `size`, `(width, height)`, with the
+/// origin and Y direction of `origin`, without building a flipped `Rect`
+/// for [`set_screen_coordinates()`](fn.set_screen_coordinates.html) by hand.
+///
+/// With `Origin::Center` and `Origin::BottomLeft`, Y increases up. Images,
+/// texts and canvases are still drawn from their top-left corner at
+/// `dest` towards increasing Y, top row first, so they come out upside
+/// down, extending up from `dest`. Flip them back by drawing with
+/// `DrawParam::scale` `(1.0, -1.0)`, which then makes `dest` their
+/// top-left corner on the screen again. Meshes are drawn as their
+/// vertices say and need nothing.
+///
+/// [`screen_coordinates()`](fn.screen_coordinates.html) then returns the
+/// flipped rect, with a negative height.
+pub fn set_ortho(ctx: &mut Context, origin: Origin, size: (f32, f32)) -> GameResult {
+    let (w, h) = size;
+    let rect = match origin {
+        Origin::TopLeft => Rect::new(0., 0., w, h),
+        Origin::Center => Rect::new(-w / 2., h / 2., w, -h),
+        Origin::BottomLeft => Rect::new(0., h, w, -h),
+    };
+    set_screen_coordinates(ctx, rect)
+}
+
 /// Returns a rectangle defining the coordinate system of the screen.
 /// It will be `Rect { x: left, y: top, w: width, h: height }`
 ///
@@ -104,19 +836,148 @@ pub fn screen_coordinates(ctx: &Context) -> Rect {
     ctx.gfx_context.screen_rect
 }
 
+/// Returns the part of the screen not obscured by notches, rounded corners
+/// or system bars, in the same units as [`screen_coordinates()`](fn.screen_coordinates.html).
+/// Keep HUD elements inside it.
+///
+/// miniquad reports no insets on any platform, web included, so they are
+/// all zero and this is the whole screen, unless they were set with
+/// [`set_safe_area_insets()`](fn.set_safe_area_insets.html).
+/// It is also the whole screen while the window has no size.
+pub fn safe_area(ctx: &Context) -> Rect {
+    let screen = ctx.gfx_context.screen_rect;
+    let (width, height) = size(ctx);
+    if width <= 0. || height <= 0. {
+        return screen;
+    }
+    let (sx, sy) = (screen.w / width, screen.h / height);
+    let insets = ctx.gfx_context.safe_area_insets;
+
+    Rect::new(
+        screen.x + insets.left * sx,
+        screen.y + insets.top * sy,
+        screen.w - (insets.left + insets.right) * sx,
+        screen.h - (insets.top + insets.bottom) * sy,
+    )
+}
+
+/// Sets the screen insets used by [`safe_area()`](fn.safe_area.html),
+/// in logical pixels.
+///
+/// On the web they can be read from the CSS `env(safe-area-inset-*)` values,
+/// on a page with `viewport-fit=cover`, and passed in from JS glue of the
+/// game's own; good-web-game ships none.
+pub fn set_safe_area_insets(ctx: &mut Context, left: f32, top: f32, right: f32, bottom: f32) {
+    ctx.gfx_context.safe_area_insets = context::SafeAreaInsets {
+        left,
+        top,
+        right,
+        bottom,
+    };
+}
+
 /// Tells the graphics system to actually put everything on the screen.
 /// Call this at the end of your [`EventHandler`](../event/trait.EventHandler.html)'s
 /// [`draw()`](../event/trait.EventHandler.html#tymethod.draw) method.
 ///
 /// Unsets any active canvas.
-pub fn present(_: &mut Context) -> GameResult<()> {
+///
+/// Frames are atomic: every draw call goes to the back buffer, which is only
+/// shown once [`draw()`](../event/trait.EventHandler.html#tymethod.draw) returns,
+/// so a partially drawn frame is never visible. Drawing after `present()`
+/// in the same frame still ends up in that frame. If `draw()` does not call
+/// `present()`, the frame is presented anyway once it returns.
+pub fn present(ctx: &mut Context) -> GameResult<()> {
+    ctx.gfx_context.canvas = None;
+    ctx.gfx_context.delete_dropped_passes(&mut ctx.quad_ctx);
+    if !ctx.gfx_context.frame_presented {
+        layers::composite_layer_stack(ctx)?;
+        draw_queue::flush_queue(ctx)?;
+        debug::flush_debug(ctx);
+        ctx.gfx_context.frame_presented = true;
+        frame::draw_frame(ctx)?;
+        ctx.quad_ctx.commit_frame();
+    }
     Ok(())
 }
 
+/// Copies what was drawn so far on the current target, the screen or a
+/// canvas, into `image`, without allocating anything. For feedback effects
+/// like trails and motion blur, or to sample the last frame in a shader.
+///
+/// The copy is done on the GPU and `image` must be the size of the target
+/// in pixels, see [`drawable_size()`](fn.drawable_size.html), or this fails.
+/// It always fails on web, the JS GL bindings of miniquad can't copy.
+///
+/// Like the image of a `Canvas`, the copy is upside down: its first row is
+/// the bottom of the target. Shaders sampling it see the target as is,
+/// with `v` going up; to draw it the right way up, draw it with
+/// [`DrawParam::flip_y`](struct.DrawParam.html#method.flip_y).
+pub fn copy_framebuffer_to(ctx: &mut Context, image: &mut Image) -> GameResult {
+    let (width, height) = target_size(ctx);
+    if (f32::from(image.width()), f32::from(image.height())) != (width, height) {
+        return Err(GameError::RenderError(format!(
+            "Can't copy a {}x{} framebuffer into a {}x{} image",
+            width,
+            height,
+            image.width(),
+            image.height()
+        )));
+    }
+
+    // binds the framebuffer of the current target
+    let pass = ctx.framebuffer();
+    ctx.quad_ctx.begin_pass(pass, PassAction::Nothing);
+    let copied = gl::copy_framebuffer_to_texture(&image.texture, width as i32, height as i32);
+    ctx.quad_ctx.end_render_pass();
+    if !copied {
+        return Err(GameError::RenderError(
+            "Copying the framebuffer isn't supported on this platform".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Sets the default stroke width, used by
+/// [`DrawMode::default_stroke()`](enum.DrawMode.html#method.default_stroke).
+///
+/// The width is in world units, like the rest of the geometry: it is scaled
+/// along with the mesh by `DrawParam::scale`, transforms and screen
+/// coordinates, so lines get thicker when zooming in. Defaults to `1.0`.
+pub fn set_line_width(ctx: &mut Context, width: f32) {
+    ctx.gfx_context.line_width = width;
+}
+
+/// Returns the default stroke width set with [`set_line_width()`](fn.set_line_width.html).
+pub fn line_width(ctx: &Context) -> f32 {
+    ctx.gfx_context.line_width
+}
+
+/// Sets the size, in pixels, that fonts loaded from now on are rasterized at.
 pub fn set_font_size(ctx: &mut Context, font_size: u32) {
     ctx.gfx_context.font_size = font_size;
 }
 
+/// Sets whether `Text` is snapped to whole pixels.
+///
+/// When on, the origin of every text drawn is rounded to the nearest pixel
+/// of the render target, after the transform and screen coordinates. The
+/// glyphs then always land on the same pixel grid, which keeps small static
+/// UI text crisp and stops it from shimmering as a camera moves, at the
+/// cost of moving in one pixel steps.
+///
+/// Off by default: texts are drawn at sub-pixel positions, so they move
+/// smoothly but are resampled a little differently at each position.
+pub fn set_text_snap(ctx: &mut Context, snap: bool) {
+    ctx.gfx_context.text_snap = snap;
+}
+
+/// Returns whether `Text` is snapped to whole pixels, see
+/// [`set_text_snap()`](fn.set_text_snap.html).
+pub fn text_snap(ctx: &Context) -> bool {
+    ctx.gfx_context.text_snap
+}
+
 /// All types that can be drawn on the screen implement the `Drawable` trait.
 pub trait Drawable {
     /// Draws the drawable onto the rendering target.
@@ -133,9 +994,19 @@ pub trait Drawable {
     /// Gets the blend mode to be used when drawing this drawable.
     fn blend_mode(&self) -> Option<BlendMode>;
 
-    fn dimensions(&self, _: &mut Context) -> Option<Rect> {
+    /// Returns the bounding box of the drawable in its own coordinates,
+    /// before any `DrawParam` is applied, for the drawables that know it
+    /// without a `Context`. `Text` needs its font for that, use
+    /// [`dimensions()`](#method.dimensions) instead.
+    fn aabb(&self) -> Option<Rect> {
         None
     }
+
+    /// Returns the bounding box of the drawable in its own coordinates,
+    /// like [`aabb()`](#method.aabb), which it defaults to.
+    fn dimensions(&self, _: &mut Context) -> Option<Rect> {
+        self.aabb()
+    }
 }
 
 /// Applies `DrawParam` to `Rect`.
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use crate::graphics::{Rect, SpriteSheet};
+use crate::timer;
+
+/// What an [`Animation`](struct.Animation.html) does after its last frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayMode {
+    /// Start over from the first frame.
+    Loop,
+    /// Stay on the last frame and report the animation as finished.
+    Once,
+}
+
+/// Frame-based sprite animation.
+///
+/// Steps through a list of source rects at a fixed rate; feed
+/// [`current_frame()`](#method.current_frame) into `DrawParam::src`.
+/// Updating does not allocate.
+///
+/// ```rust,ignore
+/// // in update()
+/// self.walk.update(timer::delta(ctx));
+/// // in draw()
+/// graphics::draw(ctx, &self.image, DrawParam::new().src(self.walk.current_frame()))?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Animation {
+    frames: Vec<Rect>,
+    frame_duration: f64,
+    mode: PlayMode,
+    elapsed: f64,
+    current: usize,
+    finished: bool,
+}
+
+impl Animation {
+    /// Creates a looping animation playing `frames` at `fps` frames per second.
+    ///
+    /// Panics if `frames` is empty or `fps` is not positive.
+    pub fn new(frames: &[Rect], fps: f32) -> Animation {
+        assert!(!frames.is_empty(), "Animation needs at least one frame");
+        assert!(fps > 0.0, "Animation fps should be positive");
+
+        Animation {
+            frames: frames.to_vec(),
+            frame_duration: 1.0 / f64::from(fps),
+            mode: PlayMode::Loop,
+            elapsed: 0.0,
+            current: 0,
+            finished: false,
+        }
+    }
+
+    /// Creates an animation playing every frame of a `SpriteSheet`, in order.
+    pub fn from_sprite_sheet(sheet: &SpriteSheet, fps: f32) -> Animation {
+        Animation::new(sheet.frames(), fps)
+    }
+
+    /// Sets what happens after the last frame, builder-style.
+    pub fn with_mode(mut self, mode: PlayMode) -> Animation {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets what happens after the last frame.
+    pub fn set_mode(&mut self, mode: PlayMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    /// Advances the animation by `dt`, usually [`timer::delta()`](../timer/fn.delta.html).
+    pub fn update(&mut self, dt: Duration) {
+        if self.finished {
+            return;
+        }
+
+        self.elapsed += timer::duration_to_f64(dt);
+        while self.elapsed >= self.frame_duration {
+            self.elapsed -= self.frame_duration;
+
+            if self.current + 1 < self.frames.len() {
+                self.current += 1;
+            } else if self.mode == PlayMode::Loop {
+                self.current = 0;
+            } else {
+                self.finished = true;
+                self.elapsed = 0.0;
+                break;
+            }
+        }
+    }
+
+    /// The source rect of the frame that should be drawn right now.
+    pub fn current_frame(&self) -> Rect {
+        self.frames[self.current]
+    }
+
+    /// Index of the current frame.
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// Returns true once a `PlayMode::Once` animation has shown its last frame
+    /// for its full duration. Looping animations never finish.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Rewinds to the first frame.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.current = 0;
+        self.finished = false;
+    }
+}
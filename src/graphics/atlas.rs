@@ -0,0 +1,168 @@
+use crate::{
+    graphics::{Image, Rect},
+    Context, GameError, GameResult,
+};
+
+/// Empty pixels kept around every packed image, so linear filtering
+/// does not bleed neighbours into each other.
+const PADDING: u16 = 1;
+
+/// A row of the atlas, images are packed left to right in it.
+#[derive(Clone, Copy, Debug)]
+struct Shelf {
+    y: u16,
+    height: u16,
+    next_x: u16,
+}
+
+/// Many small images packed into one large texture.
+///
+/// Every image added gets a source rect inside the atlas `Image`, ready for
+/// [`DrawParam::src`](struct.DrawParam.html#structfield.src). Drawing them
+/// all then binds a single texture, and they can share one `SpriteBatch`.
+///
+/// Images are packed in shelves, rows as tall as their tallest image,
+/// which works well for images of similar heights. Nothing can be removed;
+/// build a new atlas instead.
+///
+/// The atlas keeps a CPU copy of its pixels, `width * height * 4` bytes,
+/// and re-uploads the whole texture on every addition.
+///
+/// ```rust,ignore
+/// let mut atlas = TextureAtlas::new(ctx, 1024, 1024)?;
+/// let coin = atlas.add_image(ctx, &Image::new_with_cpu_copy(ctx, "/coin.png")?)?;
+/// graphics::draw(ctx, atlas.image(), DrawParam::new().src(coin).dest(pos))?;
+/// ```
+#[derive(Debug)]
+pub struct TextureAtlas {
+    image: Image,
+    pixels: Vec<u8>,
+    shelves: Vec<Shelf>,
+    /// Source rects of everything packed, in order.
+    packed: Vec<Rect>,
+}
+
+impl TextureAtlas {
+    /// Creates an empty, transparent atlas of the given size in pixels.
+    pub fn new(ctx: &mut Context, width: u16, height: u16) -> GameResult<TextureAtlas> {
+        let pixels = vec![0; width as usize * height as usize * 4];
+        let image = Image::from_rgba8(ctx, width, height, &pixels)?;
+
+        Ok(TextureAtlas {
+            image,
+            pixels,
+            shelves: vec![],
+            packed: vec![],
+        })
+    }
+
+    /// The image holding every packed image.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Packs raw RGBA pixels, returns their source rect in the atlas.
+    ///
+    /// Fails if there is no room left.
+    pub fn add_rgba8(
+        &mut self,
+        ctx: &mut Context,
+        width: u16,
+        height: u16,
+        bytes: &[u8],
+    ) -> GameResult<Rect> {
+        if bytes.len() != width as usize * height as usize * 4 {
+            return Err(GameError::ResourceLoadError(format!(
+                "Image of {}x{} pixels needs {} bytes of RGBA data, got {}",
+                width,
+                height,
+                width as usize * height as usize * 4,
+                bytes.len()
+            )));
+        }
+
+        let (x, y) = self.allocate(width, height).ok_or_else(|| {
+            GameError::ResourceLoadError(format!(
+                "No room left in the texture atlas for an image of {}x{} pixels",
+                width, height
+            ))
+        })?;
+
+        let atlas_width = self.image.width() as usize;
+        let row_len = width as usize * 4;
+        for row in 0..height as usize {
+            let src = row * row_len;
+            let dst = ((y as usize + row) * atlas_width + x as usize) * 4;
+            self.pixels[dst..dst + row_len].copy_from_slice(&bytes[src..src + row_len]);
+        }
+        self.image.texture.update(&mut ctx.quad_ctx, &self.pixels);
+
+        let rect = Rect::fraction(
+            x as f32,
+            y as f32,
+            width as f32,
+            height as f32,
+            &self.image.dimensions(),
+        );
+        self.packed.push(rect);
+        Ok(rect)
+    }
+
+    /// The source rects of all the packed images, in the order they were added.
+    pub fn packed(&self) -> &[Rect] {
+        &self.packed
+    }
+
+    /// Packs an `Image`, returns its source rect in the atlas.
+    ///
+    /// The image needs a CPU copy of its pixels, see
+    /// [`Image::pixels()`](struct.Image.html#method.pixels).
+    /// Fails if it has none, or if there is no room left.
+    pub fn add_image(&mut self, ctx: &mut Context, image: &Image) -> GameResult<Rect> {
+        let pixels = image.pixels().ok_or_else(|| {
+            GameError::ResourceLoadError(
+                "Only images with a CPU copy of their pixels can be added to an atlas".to_string(),
+            )
+        })?;
+
+        self.add_rgba8(ctx, image.width(), image.height(), pixels)
+    }
+
+    /// Finds room for a `width` x `height` image, returns its top-left corner.
+    fn allocate(&mut self, width: u16, height: u16) -> Option<(u16, u16)> {
+        let atlas_width = u32::from(self.image.width());
+        let atlas_height = u32::from(self.image.height());
+        let padded_width = u32::from(width) + u32::from(PADDING);
+        let padded_height = u32::from(height) + u32::from(PADDING);
+
+        // the shortest shelf the image fits in wastes the least space
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| {
+                u32::from(shelf.height) >= padded_height
+                    && u32::from(shelf.next_x) + padded_width <= atlas_width
+            })
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = shelf {
+            let x = shelf.next_x;
+            shelf.next_x += padded_width as u16;
+            return Some((x, shelf.y));
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| u32::from(shelf.y) + u32::from(shelf.height));
+        if y + padded_height > atlas_height || padded_width > atlas_width {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y: y as u16,
+            height: padded_height as u16,
+            next_x: padded_width as u16,
+        });
+        Some((0, y as u16))
+    }
+}
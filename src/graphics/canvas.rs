@@ -1,15 +1,78 @@
 use crate::{
     conf::NumSamples,
-    graphics::{BlendMode, DrawParam, Drawable, FilterMode, Image, Rect},
-    Context, GameResult,
+    graphics::{BlendMode, Color, DrawParam, Drawable, FilterMode, Image, Rect},
+    Context, GameError, GameResult,
 };
 
 use miniquad::{RenderPass, Texture, TextureFormat, TextureParams};
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// What happens to the contents of a canvas when it becomes the render
+/// target, see [`Canvas::set_load_action()`](struct.Canvas.html#method.set_load_action).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CanvasLoadAction {
+    /// The previous contents are kept and drawn over, accumulating across
+    /// frames, like for trails or a paint program.
+    Keep,
+    /// The color is cleared to the given one, and the depth too,
+    /// like [`graphics::clear()`](fn.clear.html) would.
+    Clear(Color),
+}
+
+impl Default for CanvasLoadAction {
+    fn default() -> CanvasLoadAction {
+        CanvasLoadAction::Keep
+    }
+}
+
+/// A render target to draw into instead of the screen, see
+/// [`set_canvas()`](fn.set_canvas.html).
+///
+/// Cloning a `Canvas` is cheap, clones share the same texture. The GPU
+/// resources are freed on the next [`present()`](fn.present.html) once the
+/// last clone is dropped, and the last clone of its [`image()`](#method.image)
+/// too, so images taken out of a canvas stay valid on their own.
+/// Canvases used as the current target are kept alive until it changes.
+///
+/// What's drawn into a canvas with `BlendMode::Alpha` ends up with its colors
+/// multiplied by alpha, so canvases are drawn with `BlendMode::Premultiplied`
+/// by default, and stack without dark edges. Opt out by setting another blend
+/// mode, like `set_blend_mode(Some(BlendMode::Alpha))` for a canvas holding
+/// straight alpha written with `BlendMode::Replace`.
 #[derive(Clone, Debug)]
 pub struct Canvas {
     image: Image,
+    depth_texture: Option<Texture>,
+    /// The color targets after the first one, see `new_mrt`.
+    targets: Vec<Image>,
     pub(crate) offscreen_pass: RenderPass,
+    load_action: CanvasLoadAction,
+}
+
+/// The render pass of a canvas, shared by the image of the canvas and all
+/// its clones. miniquad deletes the color and depth textures along with the
+/// pass, so it's only queued for deletion once the last of them is dropped.
+#[derive(Debug)]
+pub(crate) struct CanvasPass {
+    pass: RenderPass,
+    dropped_passes: Rc<RefCell<Vec<RenderPass>>>,
+}
+
+impl CanvasPass {
+    fn new(ctx: &Context, pass: RenderPass) -> Rc<CanvasPass> {
+        Rc::new(CanvasPass {
+            pass,
+            dropped_passes: ctx.gfx_context.dropped_passes.clone(),
+        })
+    }
+}
+
+impl Drop for CanvasPass {
+    fn drop(&mut self) {
+        self.dropped_passes.borrow_mut().push(self.pass);
+    }
 }
 
 impl Canvas {
@@ -19,6 +82,7 @@ impl Canvas {
         height: u16,
         _samples: NumSamples,
     ) -> GameResult<Canvas> {
+        check_size(ctx, width, height)?;
         let texture = Texture::new_render_texture(
             &mut ctx.quad_ctx,
             TextureParams {
@@ -31,16 +95,161 @@ impl Canvas {
 
         // let framebuffer = Framebuffer::new(ctx, &texture)
         //     .ok_or_else(|| GameError::UnknownError("Couldn't create a Framebuffer"))?;
-        let image = Image::from_texture(&mut ctx.quad_ctx, texture)?;
+        let mut image = Image::from_texture(&mut ctx.quad_ctx, texture)?;
+        image.set_blend_mode(Some(BlendMode::Premultiplied));
 
         let offscreen_pass = RenderPass::new(&mut ctx.quad_ctx, texture, None);
+        image.canvas_pass = Some(CanvasPass::new(ctx, offscreen_pass));
+
+        Ok(Canvas {
+            image,
+            depth_texture: None,
+            targets: vec![],
+            offscreen_pass,
+            load_action: CanvasLoadAction::default(),
+        })
+    }
+
+    /// Create a new `Canvas` with a depth buffer attached.
+    ///
+    /// While this canvas is the render target, depth testing is enabled for
+    /// everything drawn: fragments farther than what is already there are
+    /// discarded. Drawables are drawn at the depth set with
+    /// [`DrawParam::depth()`](struct.DrawParam.html#method.depth), `0.0` by
+    /// default, so with the default depths the last drawn still wins.
+    /// [`graphics::clear()`](fn.clear.html) resets the depth too.
+    ///
+    /// On WebGL1 depth textures need the `WEBGL_depth_texture` extension,
+    /// which nearly all browsers have but is not guaranteed.
+    pub fn new_with_depth(
+        ctx: &mut Context,
+        width: u16,
+        height: u16,
+        filter: FilterMode,
+    ) -> GameResult<Canvas> {
+        check_size(ctx, width, height)?;
+        let color_texture = Texture::new_render_texture(
+            &mut ctx.quad_ctx,
+            TextureParams {
+                width: width as u32,
+                height: height as u32,
+                format: TextureFormat::RGBA8,
+                filter,
+                ..Default::default()
+            },
+        );
+        let depth_texture = Texture::new_render_texture(
+            &mut ctx.quad_ctx,
+            TextureParams {
+                width: width as u32,
+                height: height as u32,
+                format: TextureFormat::Depth,
+                ..Default::default()
+            },
+        );
+
+        let mut image = Image::from_texture(&mut ctx.quad_ctx, color_texture)?;
+        image.set_filter(filter);
+        image.set_blend_mode(Some(BlendMode::Premultiplied));
+
+        let offscreen_pass = RenderPass::new(&mut ctx.quad_ctx, color_texture, Some(depth_texture));
+        image.canvas_pass = Some(CanvasPass::new(ctx, offscreen_pass));
 
         Ok(Canvas {
             image,
+            depth_texture: Some(depth_texture),
+            targets: vec![],
             offscreen_pass,
+            load_action: CanvasLoadAction::default(),
         })
     }
 
+    /// Create a new `Canvas` with several color targets, one for each
+    /// `(width, height, filter)` of `targets`, that every draw writes to at
+    /// once, for effects like a glow mask drawn along with the picture.
+    ///
+    /// A custom [`Shader`](struct.Shader.html) writes each target with
+    /// `gl_FragData[n]`; the built-in shaders, and shaders writing
+    /// `gl_FragColor`, write the same color to all of them. Draw the canvas
+    /// itself to show the first target, [`target()`](#method.target) gives
+    /// the others. [`set_canvas()`](fn.set_canvas.html) binds them all,
+    /// and [`graphics::clear()`](fn.clear.html) clears them all.
+    ///
+    /// The targets must all have the same size. Fails if there are more
+    /// than the GPU can draw to at once: WebGL1 needs the `WEBGL_draw_buffers`
+    /// extension for more than one, which isn't available on web with this
+    /// version of miniquad.
+    pub fn new_mrt(ctx: &mut Context, targets: &[(u16, u16, FilterMode)]) -> GameResult<Canvas> {
+        let &(width, height, filter) = targets.first().ok_or_else(|| {
+            GameError::RenderError("A canvas needs at least one target".to_string())
+        })?;
+        if targets.iter().any(|&(w, h, _)| (w, h) != (width, height)) {
+            return Err(GameError::RenderError(
+                "All the targets of a canvas must have the same size".to_string(),
+            ));
+        }
+        let max_targets = super::gl::max_draw_buffers();
+        if targets.len() > max_targets {
+            return Err(GameError::RenderError(format!(
+                "Canvas of {} targets, this GPU can only draw to {} at once",
+                targets.len(),
+                max_targets
+            )));
+        }
+
+        let mut canvas = Canvas::new(ctx, width, height, NumSamples::One)?;
+        canvas.set_filter(filter);
+        for &(_, _, filter) in &targets[1..] {
+            let texture = Texture::new_render_texture(
+                &mut ctx.quad_ctx,
+                TextureParams {
+                    width: u32::from(width),
+                    height: u32::from(height),
+                    format: TextureFormat::RGBA8,
+                    filter,
+                    ..Default::default()
+                },
+            );
+            let mut image = Image::from_texture(&mut ctx.quad_ctx, texture)?;
+            image.set_filter(filter);
+            image.set_blend_mode(Some(BlendMode::Premultiplied));
+            canvas.targets.push(image);
+        }
+
+        if !canvas.targets.is_empty() {
+            let textures: Vec<Texture> = canvas.targets.iter().map(|image| image.texture).collect();
+            // the attachments are framebuffer state, set once while it's bound
+            ctx.quad_ctx
+                .begin_pass(canvas.offscreen_pass, miniquad::PassAction::Nothing);
+            super::gl::attach_color_targets(&textures);
+            ctx.quad_ctx.end_render_pass();
+        }
+
+        Ok(canvas)
+    }
+
+    /// Number of color targets, `1` unless made with
+    /// [`new_mrt()`](#method.new_mrt).
+    pub fn target_count(&self) -> usize {
+        1 + self.targets.len()
+    }
+
+    /// The image of the color target `n`, `0` being the one drawn when
+    /// drawing the canvas, like [`image()`](#method.image).
+    /// Like it, the image is upside down.
+    pub fn target(&self, n: usize) -> Option<&Image> {
+        match n {
+            0 => Some(&self.image),
+            n => self.targets.get(n - 1),
+        }
+    }
+
+    /// Returns true if the canvas has a depth buffer,
+    /// see [`new_with_depth()`](#method.new_with_depth).
+    pub fn has_depth(&self) -> bool {
+        self.depth_texture.is_some()
+    }
+
     /// Create a new `Canvas` with the current window dimensions.
     pub fn with_window_size(ctx: &mut Context) -> GameResult<Canvas> {
         use crate::graphics;
@@ -51,6 +260,20 @@ impl Canvas {
         Canvas::new(ctx, w as u16, h as u16, NumSamples::One)
     }
 
+    /// Sets what happens to the contents of the canvas every time it is made
+    /// the render target with [`set_canvas()`](fn.set_canvas.html).
+    ///
+    /// Defaults to `CanvasLoadAction::Keep`: nothing is cleared unless
+    /// [`graphics::clear()`](fn.clear.html) is called. Canvases have no
+    /// stencil buffer to clear.
+    pub fn set_load_action(&mut self, action: CanvasLoadAction) {
+        self.load_action = action;
+    }
+
+    pub fn load_action(&self) -> CanvasLoadAction {
+        self.load_action
+    }
+
     /// Gets the backend `Image` that is being rendered to.
     pub fn image(&self) -> &Image {
         &self.image
@@ -70,17 +293,29 @@ impl Canvas {
     pub fn into_inner(self) -> Image {
         // TODO: This texture is created with different settings
         // than the default; does that matter?
-        self.image
+        // the returned clone keeps the pass and its texture alive
+        self.image.clone()
     }
 }
 
+fn check_size(ctx: &Context, width: u16, height: u16) -> GameResult {
+    let max_size = crate::graphics::max_texture_size(ctx);
+    if u32::from(width) > max_size || u32::from(height) > max_size {
+        return Err(GameError::ResourceLoadError(format!(
+            "Canvas of {}x{} pixels is larger than the maximum texture size of this GPU, {}x{}",
+            width, height, max_size, max_size
+        )));
+    }
+    Ok(())
+}
+
 impl Drawable for Canvas {
     fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
-        // Gotta flip the image on the Y axis here
-        // to account for OpenGL's origin being at the bottom-left.
+        // OpenGL's origin is at the bottom-left, so the image is upside
+        // down: sample `src` from the matching rows, backwards
         let mut flipped_param = param;
-        flipped_param.scale.y *= -1.0;
-        flipped_param.dest.y += self.image.height() as f32 * param.scale.y;
+        flipped_param.src.y = 1.0 - param.src.y - param.src.h;
+        flipped_param.flip_y = !param.flip_y;
         self.image.draw(ctx, flipped_param)
     }
 
@@ -92,13 +327,24 @@ impl Drawable for Canvas {
         self.image.blend_mode()
     }
 
-    fn dimensions(&self, _: &mut Context) -> Option<Rect> {
+    fn aabb(&self) -> Option<Rect> {
         Some(self.image.dimensions())
     }
 }
 
 /// Set the `Canvas` to render to. Specifying `Option::None` will cause all
 /// rendering to be done directly to the screen.
+///
+/// Any number of canvases may exist at once; every draw call opens and closes
+/// its own render pass, so switching targets leaves no state behind. Each
+/// canvas keeps its own contents and its own blend mode, used when the canvas
+/// itself is drawn.
+///
+/// Whether the contents are kept or cleared depends on the
+/// [load action](struct.Canvas.html#method.set_load_action) of the canvas.
 pub fn set_canvas(ctx: &mut Context, target: Option<&Canvas>) {
     ctx.gfx_context.canvas = target.cloned();
+    if let Some(CanvasLoadAction::Clear(color)) = target.map(Canvas::load_action) {
+        crate::graphics::clear(ctx, color);
+    }
 }
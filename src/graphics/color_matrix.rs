@@ -0,0 +1,118 @@
+/// A linear transform of colors, see
+/// [`DrawParam::color_matrix()`](struct.DrawParam.html#method.color_matrix).
+///
+/// Each output channel is a row of `matrix` dotted with the input
+/// `(r, g, b, a)`, plus the matching channel of `offset`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorMatrix {
+    pub matrix: [[f32; 4]; 4],
+    pub offset: [f32; 4],
+}
+
+/// Luminance weights of the red, green and blue channels, from Rec. 709.
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+impl ColorMatrix {
+    /// Leaves colors untouched.
+    pub const IDENTITY: ColorMatrix = ColorMatrix {
+        matrix: [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ],
+        offset: [0., 0., 0., 0.],
+    };
+
+    pub fn new(matrix: [[f32; 4]; 4], offset: [f32; 4]) -> ColorMatrix {
+        ColorMatrix { matrix, offset }
+    }
+
+    /// Scales the saturation: `0.0` is grayscale, `1.0` changes nothing
+    /// and larger values make colors more vivid.
+    pub fn saturation(saturation: f32) -> ColorMatrix {
+        let mut matrix = ColorMatrix::IDENTITY.matrix;
+        for (row, channel) in matrix.iter_mut().take(3).enumerate() {
+            for col in 0..3 {
+                let identity = if row == col { 1. } else { 0. };
+                channel[col] = LUMINANCE[col] * (1. - saturation) + identity * saturation;
+            }
+        }
+        ColorMatrix::new(matrix, [0.; 4])
+    }
+
+    /// Multiplies red, green and blue by `brightness`, alpha is kept.
+    pub fn brightness(brightness: f32) -> ColorMatrix {
+        let b = brightness;
+        ColorMatrix::new(
+            [
+                [b, 0., 0., 0.],
+                [0., b, 0., 0.],
+                [0., 0., b, 0.],
+                [0., 0., 0., 1.],
+            ],
+            [0.; 4],
+        )
+    }
+
+    /// Rotates hues by `angle` radians around the color wheel,
+    /// roughly keeping the luminance.
+    pub fn hue_rotation(angle: f32) -> ColorMatrix {
+        let (sin, cos) = angle.sin_cos();
+        let [lr, lg, lb] = LUMINANCE;
+        ColorMatrix::new(
+            [
+                [
+                    lr + cos * (1. - lr) - sin * lr,
+                    lg - cos * lg - sin * lg,
+                    lb - cos * lb + sin * (1. - lb),
+                    0.,
+                ],
+                [
+                    lr - cos * lr + sin * 0.143,
+                    lg + cos * (1. - lg) + sin * 0.140,
+                    lb - cos * lb - sin * 0.283,
+                    0.,
+                ],
+                [
+                    lr - cos * lr - sin * (1. - lr),
+                    lg - cos * lg + sin * lg,
+                    lb + cos * (1. - lb) + sin * lb,
+                    0.,
+                ],
+                [0., 0., 0., 1.],
+            ],
+            [0.; 4],
+        )
+    }
+
+    /// The matrix applying `self`, then `next`.
+    pub fn then(&self, next: &ColorMatrix) -> ColorMatrix {
+        let mut result = ColorMatrix::new([[0.; 4]; 4], next.offset);
+        for row in 0..4 {
+            for col in 0..4 {
+                result.matrix[row][col] = (0..4)
+                    .map(|k| next.matrix[row][k] * self.matrix[k][col])
+                    .sum();
+            }
+            result.offset[row] += (0..4)
+                .map(|k| next.matrix[row][k] * self.offset[k])
+                .sum::<f32>();
+        }
+        result
+    }
+
+    /// The matrix as GLSL wants it, column by column.
+    pub(crate) fn to_matrix4(&self) -> cgmath::Matrix4<f32> {
+        let m = &self.matrix;
+        let column = |col: usize| cgmath::Vector4::new(m[0][col], m[1][col], m[2][col], m[3][col]);
+        cgmath::Matrix4::from_cols(column(0), column(1), column(2), column(3))
+    }
+}
+
+impl Default for ColorMatrix {
+    fn default() -> ColorMatrix {
+        ColorMatrix::IDENTITY
+    }
+}
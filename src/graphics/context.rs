@@ -1,11 +1,14 @@
 use crate::{
-    graphics::{types::Rect, Canvas},
+    conf::Conf,
+    graphics::{types::Rect, BlendMode, Canvas, CullMode, FrontFace, Image, Shader},
     GameResult,
 };
 use miniquad_text_rusttype::FontTexture;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
-use cgmath::{Matrix3, Matrix4};
+use cgmath::{Matrix3, Matrix4, SquareMatrix, Vector4};
 
 const DEFAULT_FONT_BYTES: &'static [u8] = include_bytes!(concat!(
     env!("CARGO_MANIFEST_DIR"),
@@ -15,25 +18,136 @@ const DEFAULT_FONT_BYTES: &'static [u8] = include_bytes!(concat!(
 pub struct GraphicsContext {
     pub(crate) screen_rect: Rect,
     pub(crate) projection: Matrix4<f32>,
+    pub(crate) transform_stack: Vec<Matrix3<f32>>,
     pub(crate) white_texture: miniquad::Texture,
+    /// A 1x1 white `Image` sharing `white_texture`, for drawing solid quads.
+    pub(crate) white_image: Image,
     //pub(crate) text_cache: HashMap<String, GpuText>,
     pub(crate) canvas: Option<Canvas>,
-    pub(crate) sprite_pipeline: miniquad::Pipeline,
-    pub(crate) mesh_pipeline: miniquad::Pipeline,
-    pub(crate) image_pipeline: miniquad::Pipeline,
+    pub(crate) blend_mode: BlendMode,
+    sprite_shader: miniquad::Shader,
+    gradient_sprite_shader: miniquad::Shader,
+    mesh_shader: miniquad::Shader,
+    image_shader: miniquad::Shader,
+    palette_shader: miniquad::Shader,
+    color_matrix_shader: miniquad::Shader,
+    alpha_test_shader: miniquad::Shader,
+    pipelines: HashMap<PipelineKey, miniquad::Pipeline>,
+    /// User fragment shaders, indexed by `Shader::id`.
+    custom_shaders: Vec<CustomShader>,
+    pub(crate) shader: Option<Shader>,
     pub(crate) text_system: miniquad_text_rusttype::TextSystem,
-    pub(crate) fonts_cache: Vec<Rc<miniquad_text_rusttype::FontTexture>>,
+    /// Loaded fonts, indexed by `FontId`.
+    /// Slot 0 is reserved for the default font and is empty if it was not loaded.
+    pub(crate) fonts_cache: Vec<Option<Rc<miniquad_text_rusttype::FontTexture>>>,
+    /// The size each font of `fonts_cache` was rasterized at.
+    pub(crate) font_sizes: Vec<u32>,
     pub(crate) font_size: u32,
+    /// Set with `graphics::set_text_snap`.
+    pub(crate) text_snap: bool,
+    /// Set with `graphics::set_camera_snap`.
+    pub(crate) camera_snap: bool,
+    pub(crate) line_width: f32,
+    /// Shared by all `graphics::draw_instanced` calls, grown as needed.
+    instance_buffer: Option<(miniquad::Buffer, usize)>,
+    /// Shared by all `graphics::draw_batched_meshes` calls and the debug
+    /// shapes, vertices then indices with their capacities in bytes, grown
+    /// as needed.
+    mesh_batch_buffers: Option<(miniquad::Buffer, usize, miniquad::Buffer, usize)>,
+    /// Largest texture width and height the GPU supports, queried once.
+    pub(crate) max_texture_size: u32,
+    /// Highest anisotropic filtering level, 1 if not supported.
+    pub(crate) max_anisotropy: u8,
+    pub(crate) safe_area_insets: SafeAreaInsets,
+    /// Set with `graphics::set_scissor`, in screen coordinates.
+    pub(crate) scissor: Option<Rect>,
+    /// Automatic clear at the beginning of every frame, see `graphics::set_clear_color`.
+    pub(crate) clear_color: Option<crate::graphics::Color>,
+    /// Offscreen target of the whole frame, if it needs post-processing when presented.
+    pub(crate) frame: Option<super::frame::FrameCanvas>,
+    /// Set by `graphics::set_dirty_tracking`.
+    pub(crate) dirty: Option<super::dirty::DirtyRegions>,
+    /// See `graphics::begin_gpu_timer`.
+    pub(crate) gpu_timer: super::gpu_timer::GpuTimer,
+    /// Set by `graphics::present`, reset once the frame is over.
+    pub(crate) frame_presented: bool,
+    /// Render passes of dropped canvases; deleting them needs the miniquad
+    /// context, so it happens on the next `graphics::present`.
+    pub(crate) dropped_passes: Rc<RefCell<Vec<miniquad::RenderPass>>>,
+    /// Filled by `graphics::queue_text`, emptied by `graphics::draw_queued_text`.
+    pub(crate) queued_text: Vec<super::text::QueuedText>,
+    /// Saved by `graphics::push_state`.
+    pub(crate) state_stack: Vec<RenderState>,
+    /// Filled by `graphics::debug_line` and `graphics::debug_point`.
+    pub(crate) debug_shapes: super::debug::DebugShapes,
+    /// Filled by `graphics::queue`, emptied by `graphics::flush_queue`.
+    pub(crate) draw_queue: Vec<super::draw_queue::QueuedDraw>,
+    /// Reused by `graphics::flush_queue` to batch images.
+    pub(crate) queue_batch: Option<super::spritebatch::SpriteBatch>,
+    /// Set by `graphics::set_layer_stack`, composited by `graphics::present`.
+    pub(crate) layer_stack: Option<super::layers::LayerStack>,
+}
+
+/// The render state saved by `graphics::push_state`.
+pub(crate) struct RenderState {
+    pub canvas: Option<Canvas>,
+    pub blend_mode: BlendMode,
+    pub shader: Option<Shader>,
+    pub scissor: Option<Rect>,
+}
+
+/// Obscured screen borders, in logical pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct SafeAreaInsets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// Which of the built-in shaders a pipeline runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum PipelineKind {
+    Sprite,
+    /// Sprites with a color per corner.
+    GradientSprite,
+    Image,
+    Mesh,
+    /// A user fragment shader on top of the image vertex shader.
+    Custom(usize),
+    /// Palette lookup on top of the image vertex shader.
+    Palette,
+    /// The image shader with a color matrix.
+    ColorMatrix,
+    /// The image shader discarding pixels below an alpha threshold.
+    AlphaTest,
+}
+
+struct CustomShader {
+    shader: miniquad::Shader,
+    params: Vector4<f32>,
+}
+
+/// Everything that makes two pipelines different.
+/// Pipelines are created lazily, once per distinct key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineKey {
+    pub kind: PipelineKind,
+    pub blend_mode: BlendMode,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub depth_test: bool,
 }
 
 impl GraphicsContext {
-    pub fn new(ctx: &mut miniquad::Context) -> GraphicsContext {
+    pub(crate) fn new(ctx: &mut miniquad::Context, conf: &Conf) -> GraphicsContext {
         use miniquad::*;
 
         let projection = cgmath::One::one();
         let screen_rect = Rect::new(-1., -1., 2., 2.);
 
         let white_texture = Texture::from_rgba8(ctx, 1, 1, &[255, 255, 255, 255]);
+        let white_image = Image::from_texture(ctx, white_texture).unwrap();
 
         let sprite_shader = Shader::new(
             ctx,
@@ -42,30 +156,11 @@ impl GraphicsContext {
             batch_shader::META,
         );
 
-        let sprite_pipeline = miniquad::Pipeline::with_params(
+        let gradient_sprite_shader = Shader::new(
             ctx,
-            &[
-                BufferLayout::default(),
-                BufferLayout {
-                    step_func: VertexStep::PerInstance,
-                    ..Default::default()
-                },
-            ],
-            &[
-                VertexAttribute::with_buffer("position", VertexFormat::Float2, 0),
-                VertexAttribute::with_buffer("Source", VertexFormat::Float4, 1),
-                VertexAttribute::with_buffer("Color", VertexFormat::Float4, 1),
-                VertexAttribute::with_buffer("InstanceModel", VertexFormat::Mat4, 1),
-            ],
-            sprite_shader,
-            PipelineParams {
-                color_blend: Some((
-                    Equation::Add,
-                    BlendFactor::Value(BlendValue::SourceAlpha),
-                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
-                )),
-                ..Default::default()
-            },
+            gradient_batch_shader::VERTEX,
+            batch_shader::FRAGMENT,
+            batch_shader::META,
         );
 
         let image_shader = Shader::new(
@@ -75,25 +170,6 @@ impl GraphicsContext {
             image_shader::META,
         );
 
-        let image_pipeline = miniquad::Pipeline::with_params(
-            ctx,
-            &[BufferLayout::default()],
-            &[VertexAttribute::with_buffer(
-                "position",
-                VertexFormat::Float2,
-                0,
-            )],
-            image_shader,
-            PipelineParams {
-                color_blend: Some((
-                    Equation::Add,
-                    BlendFactor::Value(BlendValue::SourceAlpha),
-                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
-                )),
-                ..Default::default()
-            },
-        );
-
         let mesh_shader = Shader::new(
             ctx,
             mesh_shader::VERTEX,
@@ -101,42 +177,228 @@ impl GraphicsContext {
             mesh_shader::META,
         );
 
-        let mesh_pipeline = Pipeline::with_params(
+        let palette_shader = Shader::new(
             ctx,
-            &[BufferLayout::default()],
-            &[
-                VertexAttribute::new("position", VertexFormat::Float2),
-                VertexAttribute::new("texcoord", VertexFormat::Float2),
-                VertexAttribute::new("color0", VertexFormat::Float4),
-            ],
-            mesh_shader,
-            PipelineParams {
-                color_blend: Some((
-                    Equation::Add,
-                    BlendFactor::Value(BlendValue::SourceAlpha),
-                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
-                )),
-                ..Default::default()
-            },
+            image_shader::VERTEX,
+            palette_shader::FRAGMENT,
+            palette_shader::META,
+        );
+
+        let color_matrix_shader = Shader::new(
+            ctx,
+            image_shader::VERTEX,
+            color_matrix_shader::FRAGMENT,
+            color_matrix_shader::META,
+        );
+
+        let alpha_test_shader = Shader::new(
+            ctx,
+            image_shader::VERTEX,
+            alpha_test_shader::FRAGMENT,
+            alpha_test_shader::META,
         );
 
         let text_system = miniquad_text_rusttype::TextSystem::new(ctx);
 
         // load default font, will be available by FontId::default()
-        let fonts_cache = vec![Rc::new(load_font(ctx, DEFAULT_FONT_BYTES, 70).unwrap())];
+        let default_font = if conf.load_default_font {
+            Some(Rc::new(
+                load_font(ctx, DEFAULT_FONT_BYTES, conf.default_font_size).unwrap(),
+            ))
+        } else {
+            None
+        };
+        let fonts_cache = vec![default_font];
+        let font_sizes = vec![conf.default_font_size];
 
         GraphicsContext {
             projection,
+            transform_stack: vec![Matrix3::identity()],
             screen_rect,
             white_texture,
+            white_image,
             //text_cache: HashMap::new(),
             canvas: None,
-            sprite_pipeline,
-            mesh_pipeline,
-            image_pipeline,
+            blend_mode: BlendMode::Alpha,
+            sprite_shader,
+            gradient_sprite_shader,
+            mesh_shader,
+            image_shader,
+            palette_shader,
+            color_matrix_shader,
+            alpha_test_shader,
+            pipelines: HashMap::new(),
+            custom_shaders: vec![],
+            shader: None,
             text_system,
             fonts_cache,
+            font_sizes,
             font_size: 50,
+            text_snap: false,
+            camera_snap: false,
+            line_width: 1.0,
+            instance_buffer: None,
+            mesh_batch_buffers: None,
+            max_texture_size: super::gl::max_texture_size(),
+            max_anisotropy: super::gl::max_anisotropy(),
+            safe_area_insets: SafeAreaInsets::default(),
+            scissor: None,
+            clear_color: None,
+            frame: None,
+            dirty: None,
+            gpu_timer: Default::default(),
+            frame_presented: false,
+            dropped_passes: Rc::new(RefCell::new(vec![])),
+            queued_text: vec![],
+            state_stack: vec![],
+            debug_shapes: Default::default(),
+            draw_queue: vec![],
+            queue_batch: None,
+            layer_stack: None,
+        }
+    }
+
+    /// Deletes the render passes of the canvases dropped since the last call.
+    pub(crate) fn delete_dropped_passes(&mut self, ctx: &mut miniquad::Context) {
+        for pass in self.dropped_passes.borrow_mut().drain(..) {
+            pass.delete(ctx);
+        }
+    }
+
+    /// Returns the pipeline for drawing with the given shader and blend mode,
+    /// creating it on first use.
+    ///
+    /// `None` means the global blend mode set with `graphics::set_blend_mode`.
+    pub(crate) fn pipeline(
+        &mut self,
+        ctx: &mut miniquad::Context,
+        kind: PipelineKind,
+        blend_mode: Option<BlendMode>,
+    ) -> miniquad::Pipeline {
+        let key = self.pipeline_key(kind, blend_mode);
+        self.pipeline_for_key(ctx, key)
+    }
+
+    /// The key of the pipeline `pipeline()` would return, for drawables that
+    /// need to tweak more pipeline state before calling `pipeline_for_key()`.
+    pub(crate) fn pipeline_key(
+        &self,
+        kind: PipelineKind,
+        blend_mode: Option<BlendMode>,
+    ) -> PipelineKey {
+        PipelineKey {
+            kind,
+            blend_mode: blend_mode.unwrap_or(self.blend_mode),
+            cull_mode: CullMode::default(),
+            front_face: FrontFace::default(),
+            depth_test: self.canvas.as_ref().map_or(false, Canvas::has_depth),
+        }
+    }
+
+    /// Returns the pipeline for the given key, creating it on first use.
+    pub(crate) fn pipeline_for_key(
+        &mut self,
+        ctx: &mut miniquad::Context,
+        key: PipelineKey,
+    ) -> miniquad::Pipeline {
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return *pipeline;
+        }
+
+        let pipeline = self.create_pipeline(ctx, key);
+        self.pipelines.insert(key, pipeline);
+        pipeline
+    }
+
+    fn create_pipeline(&self, ctx: &mut miniquad::Context, key: PipelineKey) -> miniquad::Pipeline {
+        use miniquad::*;
+
+        let (depth_test, depth_write) = if key.depth_test {
+            (Comparison::LessOrEqual, true)
+        } else {
+            (Comparison::Always, false)
+        };
+
+        let params = PipelineParams {
+            depth_test,
+            depth_write,
+            color_blend: Some(key.blend_mode.to_blend_state()),
+            alpha_blend: Some(key.blend_mode.to_alpha_blend_state()),
+            cull_face: key.cull_mode.to_quad(),
+            front_face_order: key.front_face.to_quad(),
+            ..Default::default()
+        };
+
+        match key.kind {
+            PipelineKind::Sprite => Pipeline::with_params(
+                ctx,
+                &[
+                    BufferLayout::default(),
+                    BufferLayout {
+                        step_func: VertexStep::PerInstance,
+                        ..Default::default()
+                    },
+                ],
+                &[
+                    VertexAttribute::with_buffer("position", VertexFormat::Float2, 0),
+                    VertexAttribute::with_buffer("Source", VertexFormat::Float4, 1),
+                    VertexAttribute::with_buffer("Color", VertexFormat::Float4, 1),
+                    VertexAttribute::with_buffer("InstanceModel", VertexFormat::Mat4, 1),
+                ],
+                self.sprite_shader,
+                params,
+            ),
+            PipelineKind::GradientSprite => Pipeline::with_params(
+                ctx,
+                &[
+                    BufferLayout::default(),
+                    BufferLayout {
+                        step_func: VertexStep::PerInstance,
+                        ..Default::default()
+                    },
+                ],
+                &[
+                    VertexAttribute::with_buffer("position", VertexFormat::Float2, 0),
+                    VertexAttribute::with_buffer("Source", VertexFormat::Float4, 1),
+                    VertexAttribute::with_buffer("CornersTop", VertexFormat::Float4, 1),
+                    VertexAttribute::with_buffer("CornersBottom", VertexFormat::Float4, 1),
+                    VertexAttribute::with_buffer("InstanceModel", VertexFormat::Mat4, 1),
+                ],
+                self.gradient_sprite_shader,
+                params,
+            ),
+            PipelineKind::Image
+            | PipelineKind::Custom(_)
+            | PipelineKind::Palette
+            | PipelineKind::ColorMatrix
+            | PipelineKind::AlphaTest => Pipeline::with_params(
+                ctx,
+                &[BufferLayout::default()],
+                &[VertexAttribute::with_buffer(
+                    "position",
+                    VertexFormat::Float2,
+                    0,
+                )],
+                match key.kind {
+                    PipelineKind::Custom(id) => self.custom_shaders[id].shader,
+                    PipelineKind::Palette => self.palette_shader,
+                    PipelineKind::ColorMatrix => self.color_matrix_shader,
+                    PipelineKind::AlphaTest => self.alpha_test_shader,
+                    _ => self.image_shader,
+                },
+                params,
+            ),
+            PipelineKind::Mesh => Pipeline::with_params(
+                ctx,
+                &[BufferLayout::default()],
+                &[
+                    VertexAttribute::new("position", VertexFormat::Float2),
+                    VertexAttribute::new("texcoord", VertexFormat::Float2),
+                    VertexAttribute::new("color0", VertexFormat::Float4),
+                ],
+                self.mesh_shader,
+                params,
+            ),
         }
     }
 }
@@ -150,21 +412,126 @@ impl GraphicsContext {
     ) -> GameResult<usize> {
         let font = load_font(ctx, &font_bytes, font_size)?;
 
-        self.fonts_cache.push(Rc::new(font));
+        self.fonts_cache.push(Some(Rc::new(font)));
+        self.font_sizes.push(font_size);
 
         Ok(self.fonts_cache.len() - 1)
     }
 
-    pub fn set_transform(&mut self, _transform: &Matrix3<f32>) {
-        unimplemented!();
+    /// Compiles a user fragment shader, returns its id.
+    pub(crate) fn add_shader(&mut self, ctx: &mut miniquad::Context, fragment: &str) -> usize {
+        let shader =
+            miniquad::Shader::new(ctx, image_shader::VERTEX, fragment, custom_shader::META);
+        self.custom_shaders.push(CustomShader {
+            shader,
+            params: Vector4::new(0., 0., 0., 0.),
+        });
+
+        self.custom_shaders.len() - 1
+    }
+
+    pub(crate) fn set_shader_params(&mut self, id: usize, params: Vector4<f32>) {
+        self.custom_shaders[id].params = params;
     }
 
-    pub fn push_transform(&mut self, _transform: &Matrix3<f32>) {
-        unimplemented!();
+    pub(crate) fn shader_params(&self, id: usize) -> Vector4<f32> {
+        self.custom_shaders[id].params
+    }
+
+    /// Returns a stream vertex buffer large enough for `instances` instances
+    /// of `InstanceAttributes`, reallocating it if it's too small.
+    pub(crate) fn instance_buffer(
+        &mut self,
+        ctx: &mut miniquad::Context,
+        instances: usize,
+    ) -> miniquad::Buffer {
+        if let Some((buffer, capacity)) = self.instance_buffer {
+            if capacity >= instances {
+                return buffer;
+            }
+            buffer.delete();
+        }
+
+        let capacity = instances.next_power_of_two();
+        let buffer = miniquad::Buffer::stream(
+            ctx,
+            miniquad::BufferType::VertexBuffer,
+            std::mem::size_of::<crate::graphics::InstanceAttributes>() * capacity,
+        );
+        self.instance_buffer = Some((buffer, capacity));
+        buffer
     }
 
+    /// Returns stream vertex and index buffers of at least `vertex_bytes`
+    /// and `index_bytes`, reallocating the ones that are too small.
+    pub(crate) fn mesh_batch_buffers(
+        &mut self,
+        ctx: &mut miniquad::Context,
+        vertex_bytes: usize,
+        index_bytes: usize,
+    ) -> (miniquad::Buffer, miniquad::Buffer) {
+        use miniquad::{Buffer, BufferType};
+
+        let grow = |ctx: &mut miniquad::Context,
+                    current: Option<(Buffer, usize)>,
+                    bytes: usize,
+                    kind: BufferType| match current {
+            Some((buffer, capacity)) if capacity >= bytes => (buffer, capacity),
+            current => {
+                if let Some((buffer, _)) = current {
+                    buffer.delete();
+                }
+                let capacity = bytes.next_power_of_two();
+                (Buffer::stream(ctx, kind, capacity), capacity)
+            }
+        };
+
+        let (vertices, indices) = match self.mesh_batch_buffers {
+            Some((vertices, vertex_capacity, indices, index_capacity)) => (
+                Some((vertices, vertex_capacity)),
+                Some((indices, index_capacity)),
+            ),
+            None => (None, None),
+        };
+        let (vertices, vertex_capacity) =
+            grow(ctx, vertices, vertex_bytes, BufferType::VertexBuffer);
+        let (indices, index_capacity) = grow(ctx, indices, index_bytes, BufferType::IndexBuffer);
+        self.mesh_batch_buffers = Some((vertices, vertex_capacity, indices, index_capacity));
+        (vertices, indices)
+    }
+
+    /// Replaces the transform on the top of the stack.
+    pub fn set_transform(&mut self, transform: &Matrix3<f32>) {
+        *self
+            .transform_stack
+            .last_mut()
+            .expect("Transform stack is never empty") = *transform;
+    }
+
+    /// Pushes a new transform on top of the stack, it will be used until popped.
+    pub fn push_transform(&mut self, transform: &Matrix3<f32>) {
+        self.transform_stack.push(*transform);
+    }
+
+    /// Pops the top transform. The bottom-most transform is never popped.
     pub fn pop_transform(&mut self) {
-        unimplemented!();
+        if self.transform_stack.len() > 1 {
+            self.transform_stack.pop();
+        }
+    }
+
+    /// The transform on the top of the stack.
+    pub fn transform(&self) -> Matrix3<f32> {
+        *self
+            .transform_stack
+            .last()
+            .expect("Transform stack is never empty")
+    }
+
+    /// Projection combined with the current transform, what the shaders
+    /// should use as their `Projection` uniform.
+    pub(crate) fn transformed_projection(&self) -> Matrix4<f32> {
+        self.projection * transform_to_matrix4(&self.transform())
     }
 
     pub fn set_screen_coordinates(&mut self, rect: crate::graphics::types::Rect) {
@@ -174,6 +541,18 @@ impl GraphicsContext {
     }
 }
 
+/// Lifts a 2D homogeneous transform into 3D, leaving Z untouched.
+fn transform_to_matrix4(m: &Matrix3<f32>) -> Matrix4<f32> {
+    #[rustfmt::skip]
+    let m = Matrix4::new(
+        m.x.x, m.x.y, 0.0, m.x.z,
+        m.y.x, m.y.y, 0.0, m.y.z,
+        0.0, 0.0, 1.0, 0.0,
+        m.z.x, m.z.y, 0.0, m.z.z,
+    );
+    m
+}
+
 fn load_font(
     ctx: &mut miniquad::Context,
     font_data: &[u8],
@@ -216,9 +595,10 @@ pub(crate) mod batch_shader {
     varying lowp vec2 uv;
 
     uniform sampler2D Texture;
+    uniform lowp vec4 ColorAdd;
 
     void main() {
-        gl_FragColor = texture2D(Texture, uv) * color;
+        gl_FragColor = texture2D(Texture, uv) * color + ColorAdd;
     }"#;
 
     pub const META: ShaderMeta = ShaderMeta {
@@ -227,6 +607,8 @@ pub(crate) mod batch_shader {
             uniforms: &[
                 ("Projection", UniformType::Mat4),
                 ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("depth", UniformType::Float1),
             ],
         },
     };
@@ -236,9 +618,49 @@ pub(crate) mod batch_shader {
     pub struct Uniforms {
         pub projection: cgmath::Matrix4<f32>,
         pub model: cgmath::Matrix4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub depth: f32,
     }
 }
 
+/// The batch shader with a color per corner instead of one per instance,
+/// sharing its fragment shader and uniforms.
+///
+/// The 4 colors would take 4 more attributes, over the 8 WebGL1 guarantees,
+/// so each one is packed in two floats, `r * 256 + g` and `b * 256 + a`
+/// with 8-bit channels, which floats hold exactly.
+pub(crate) mod gradient_batch_shader {
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 position;
+    attribute vec4 Source;
+    attribute vec4 CornersTop;
+    attribute vec4 CornersBottom;
+    attribute mat4 InstanceModel;
+
+    varying lowp vec4 color;
+    varying lowp vec2 uv;
+
+    uniform mat4 Projection;
+    uniform mat4 Model;
+
+    uniform float depth;
+
+    vec4 unpack(vec2 packed) {
+        vec2 high = floor(packed / 256.0);
+        vec2 low = packed - high * 256.0;
+        return vec4(high.x, low.x, high.y, low.y) / 255.0;
+    }
+
+    void main() {
+        gl_Position = Projection * Model * InstanceModel * vec4(position, 0, 1);
+        gl_Position.z = depth;
+        vec4 top = mix(unpack(CornersTop.xy), unpack(CornersTop.zw), position.x);
+        vec4 bottom = mix(unpack(CornersBottom.zw), unpack(CornersBottom.xy), position.x);
+        color = mix(top, bottom, position.y);
+        uv = position * Source.zw + Source.xy;
+    }"#;
+}
+
 pub(crate) mod image_shader {
     use miniquad::{ShaderMeta, UniformBlockLayout, UniformType};
 
@@ -267,9 +689,10 @@ pub(crate) mod image_shader {
     varying lowp vec2 uv;
 
     uniform sampler2D Texture;
+    uniform lowp vec4 ColorAdd;
 
     void main() {
-        gl_FragColor = texture2D(Texture, uv) * color;
+        gl_FragColor = texture2D(Texture, uv) * color + ColorAdd;
     }"#;
 
     pub const META: ShaderMeta = ShaderMeta {
@@ -280,6 +703,8 @@ pub(crate) mod image_shader {
                 ("Source", UniformType::Float4),
                 ("Color", UniformType::Float4),
                 ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("depth", UniformType::Float1),
             ],
         },
     };
@@ -291,6 +716,8 @@ pub(crate) mod image_shader {
         pub source: cgmath::Vector4<f32>,
         pub color: cgmath::Vector4<f32>,
         pub model: cgmath::Matrix4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub depth: f32,
     }
 }
 
@@ -323,9 +750,86 @@ pub(crate) mod mesh_shader {
     varying lowp vec2 uv;
 
     uniform sampler2D Texture;
+    uniform lowp vec4 ColorAdd;
+
+    void main() {
+        gl_FragColor = texture2D(Texture, uv) * color + ColorAdd;
+    }"#;
+
+    pub const META: ShaderMeta = ShaderMeta {
+        images: &["Texture"],
+        uniforms: UniformBlockLayout {
+            uniforms: &[
+                ("Projection", UniformType::Mat4),
+                ("Model", UniformType::Mat4),
+                ("Color", UniformType::Float4),
+                ("ColorAdd", UniformType::Float4),
+                ("depth", UniformType::Float1),
+            ],
+        },
+    };
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct Uniforms {
+        pub projection: cgmath::Matrix4<f32>,
+        pub model: cgmath::Matrix4<f32>,
+        pub color: cgmath::Vector4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub depth: f32,
+    }
+}
+
+/// Image shader uniforms, with the color looked up in a palette
+/// by the red channel of the texture.
+pub(crate) mod palette_shader {
+    use miniquad::{ShaderMeta, UniformBlockLayout, UniformType};
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying lowp vec2 uv;
+
+    uniform sampler2D Texture;
+    uniform sampler2D Palette;
+    uniform lowp vec4 ColorAdd;
 
     void main() {
-        gl_FragColor = texture2D(Texture, uv) * color;
+        mediump float index = texture2D(Texture, uv).r * 255.0;
+        mediump vec2 palette_uv = vec2((index + 0.5) / 256.0, 0.5);
+        gl_FragColor = texture2D(Palette, palette_uv) * color + ColorAdd;
+    }"#;
+
+    pub const META: ShaderMeta = ShaderMeta {
+        images: &["Texture", "Palette"],
+        uniforms: UniformBlockLayout {
+            uniforms: &[
+                ("Projection", UniformType::Mat4),
+                ("Source", UniformType::Float4),
+                ("Color", UniformType::Float4),
+                ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("depth", UniformType::Float1),
+            ],
+        },
+    };
+}
+
+/// Image shader uniforms plus a color matrix, applied between `Color` and `ColorAdd`.
+pub(crate) mod color_matrix_shader {
+    use miniquad::{ShaderMeta, UniformBlockLayout, UniformType};
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying lowp vec2 uv;
+
+    uniform sampler2D Texture;
+    uniform lowp vec4 ColorAdd;
+    uniform mediump mat4 ColorMatrix;
+    uniform mediump vec4 ColorOffset;
+
+    void main() {
+        mediump vec4 c = texture2D(Texture, uv) * color;
+        gl_FragColor = ColorMatrix * c + ColorOffset + ColorAdd;
     }"#;
 
     pub const META: ShaderMeta = ShaderMeta {
@@ -333,8 +837,61 @@ pub(crate) mod mesh_shader {
         uniforms: UniformBlockLayout {
             uniforms: &[
                 ("Projection", UniformType::Mat4),
+                ("Source", UniformType::Float4),
+                ("Color", UniformType::Float4),
                 ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("ColorMatrix", UniformType::Mat4),
+                ("ColorOffset", UniformType::Float4),
+                ("depth", UniformType::Float1),
+            ],
+        },
+    };
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct Uniforms {
+        pub projection: cgmath::Matrix4<f32>,
+        pub source: cgmath::Vector4<f32>,
+        pub color: cgmath::Vector4<f32>,
+        pub model: cgmath::Matrix4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub color_matrix: cgmath::Matrix4<f32>,
+        pub color_offset: cgmath::Vector4<f32>,
+        pub depth: f32,
+    }
+}
+
+pub(crate) mod alpha_test_shader {
+    use miniquad::{ShaderMeta, UniformBlockLayout, UniformType};
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying lowp vec2 uv;
+
+    uniform sampler2D Texture;
+    uniform lowp vec4 ColorAdd;
+    uniform lowp float AlphaThreshold;
+
+    void main() {
+        lowp vec4 c = texture2D(Texture, uv) * color;
+        if (c.a < AlphaThreshold) {
+            discard;
+        }
+        gl_FragColor = c + ColorAdd;
+    }"#;
+
+    pub const META: ShaderMeta = ShaderMeta {
+        images: &["Texture"],
+        uniforms: UniformBlockLayout {
+            uniforms: &[
+                ("Projection", UniformType::Mat4),
+                ("Source", UniformType::Float4),
                 ("Color", UniformType::Float4),
+                ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("AlphaThreshold", UniformType::Float1),
+                ("depth", UniformType::Float1),
             ],
         },
     };
@@ -343,7 +900,43 @@ pub(crate) mod mesh_shader {
     #[derive(Debug)]
     pub struct Uniforms {
         pub projection: cgmath::Matrix4<f32>,
+        pub source: cgmath::Vector4<f32>,
+        pub color: cgmath::Vector4<f32>,
         pub model: cgmath::Matrix4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub alpha_threshold: f32,
+        pub depth: f32,
+    }
+}
+
+/// Uniforms of user fragment shaders: the image shader ones plus `Params`.
+pub(crate) mod custom_shader {
+    use miniquad::{ShaderMeta, UniformBlockLayout, UniformType};
+
+    pub const META: ShaderMeta = ShaderMeta {
+        images: &["Texture"],
+        uniforms: UniformBlockLayout {
+            uniforms: &[
+                ("Projection", UniformType::Mat4),
+                ("Source", UniformType::Float4),
+                ("Color", UniformType::Float4),
+                ("Model", UniformType::Mat4),
+                ("ColorAdd", UniformType::Float4),
+                ("Params", UniformType::Float4),
+                ("depth", UniformType::Float1),
+            ],
+        },
+    };
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct Uniforms {
+        pub projection: cgmath::Matrix4<f32>,
+        pub source: cgmath::Vector4<f32>,
         pub color: cgmath::Vector4<f32>,
+        pub model: cgmath::Matrix4<f32>,
+        pub color_add: cgmath::Vector4<f32>,
+        pub params: cgmath::Vector4<f32>,
+        pub depth: f32,
     }
 }
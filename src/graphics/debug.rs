@@ -0,0 +1,238 @@
+use cgmath::{Matrix4, Vector3, Vector4};
+
+use crate::{
+    graphics::{
+        self,
+        context::{mesh_shader, PipelineKind},
+        BlendMode, Color, DrawParam, Image, Point2, Rect, TextureAtlas, Vector2,
+    },
+    Context, GameResult,
+};
+
+use miniquad::Bindings;
+
+/// Lines and points queued by `debug_line` and `debug_point`, in screen
+/// coordinates, the current transform already applied.
+#[derive(Debug, Default)]
+pub(crate) struct DebugShapes {
+    lines: Vec<(Point2, Point2, Color)>,
+    points: Vec<(Point2, Color)>,
+}
+
+#[repr(C)]
+struct DebugVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Queues a line from `a` to `b`, drawn one pixel wide at the end of the frame,
+/// see [`flush_debug()`](fn.flush_debug.html).
+///
+/// Meant for debugging physics or AI: there is nothing to create or keep
+/// around, and thousands of lines are drawn in a draw call or two. The
+/// current transform is applied right away, later changes don't move it.
+pub fn debug_line<P>(ctx: &mut Context, a: P, b: P, color: Color)
+where
+    P: Into<mint::Point2<f32>>,
+{
+    let (a, b) = (transformed(ctx, a), transformed(ctx, b));
+    ctx.gfx_context.debug_shapes.lines.push((a, b, color));
+}
+
+/// Queues a point, drawn as a 3 pixels wide square at the end of the frame,
+/// like [`debug_line()`](fn.debug_line.html).
+pub fn debug_point<P>(ctx: &mut Context, point: P, color: Color)
+where
+    P: Into<mint::Point2<f32>>,
+{
+    let point = transformed(ctx, point);
+    ctx.gfx_context.debug_shapes.points.push((point, color));
+}
+
+/// A texture [`debug_draw_atlas()`](fn.debug_draw_atlas.html) can show.
+///
+/// The glyph atlases of fonts are kept inside `miniquad_text_rusttype`,
+/// which doesn't give access to their textures, so they can't be shown.
+#[derive(Clone, Copy, Debug)]
+pub enum AtlasId<'a> {
+    /// A `TextureAtlas`, with an outline around every packed image.
+    Atlas(&'a TextureAtlas),
+    /// Any image, like the one written by a `Canvas`.
+    Image(&'a Image),
+}
+
+/// Draws a whole atlas texture in `dest`, keeping its aspect ratio, over a
+/// dark backdrop showing its transparent parts, to check how it's packed.
+///
+/// The outlines are queued like [`debug_line()`](fn.debug_line.html)s.
+/// `dest` is in the current coordinates, the transform applies.
+pub fn debug_draw_atlas(ctx: &mut Context, atlas: AtlasId, dest: Rect) -> GameResult {
+    let (image, packed) = match atlas {
+        AtlasId::Atlas(atlas) => (atlas.image(), atlas.packed()),
+        AtlasId::Image(image) => (image, &[][..]),
+    };
+
+    let (w, h) = (f32::from(image.width()), f32::from(image.height()));
+    let scale = (dest.w / w).min(dest.h / h);
+    let shown = Rect::new(dest.x, dest.y, w * scale, h * scale);
+
+    let white = graphics::white_image(ctx);
+    let backdrop = DrawParam::new()
+        .dest(Point2::new(shown.x, shown.y))
+        .scale(Vector2::new(shown.w, shown.h))
+        .color(Color::new(0.1, 0.1, 0.1, 1.0));
+    graphics::draw(ctx, &white, backdrop)?;
+    let param = DrawParam::new()
+        .dest(Point2::new(shown.x, shown.y))
+        .scale(Vector2::new(scale, scale));
+    graphics::draw(ctx, image, param)?;
+
+    let outline = |ctx: &mut Context, rect: Rect, color: Color| {
+        let corners = [
+            Point2::new(rect.left(), rect.top()),
+            Point2::new(rect.right(), rect.top()),
+            Point2::new(rect.right(), rect.bottom()),
+            Point2::new(rect.left(), rect.bottom()),
+        ];
+        for i in 0..4 {
+            debug_line(ctx, corners[i], corners[(i + 1) % 4], color);
+        }
+    };
+    for src in packed {
+        let rect = Rect::new(
+            shown.x + src.x * shown.w,
+            shown.y + src.y * shown.h,
+            src.w * shown.w,
+            src.h * shown.h,
+        );
+        outline(ctx, rect, Color::new(0.0, 1.0, 0.0, 1.0));
+    }
+    outline(ctx, shown, Color::new(1.0, 0.0, 1.0, 1.0));
+
+    Ok(())
+}
+
+fn transformed<P>(ctx: &Context, point: P) -> Point2
+where
+    P: Into<mint::Point2<f32>>,
+{
+    let point: mint::Point2<f32> = point.into();
+    let p = ctx.gfx_context.transform() * Vector3::new(point.x, point.y, 1.);
+    Point2::new(p.x / p.z, p.y / p.z)
+}
+
+/// Discards the queued debug lines and points without drawing them.
+pub fn clear_debug(ctx: &mut Context) {
+    let shapes = &mut ctx.gfx_context.debug_shapes;
+    shapes.lines.clear();
+    shapes.points.clear();
+}
+
+/// Draws the queued debug lines and points on the current target, on top of
+/// everything, and empties the queue.
+///
+/// [`present()`](fn.present.html) calls it, so there is usually no need to:
+/// call it to draw them on a canvas instead, or under something. They are
+/// drawn with the current screen coordinates and the alpha blend mode.
+///
+/// The pipelines of miniquad 0.2 only draw triangles, so lines are thin
+/// quads; their width is one pixel of the render target whatever the screen
+/// coordinates. The vertices go to buffers kept from one frame to the next.
+pub fn flush_debug(ctx: &mut Context) {
+    let shapes = std::mem::replace(&mut ctx.gfx_context.debug_shapes, DebugShapes::default());
+    if shapes.lines.is_empty() && shapes.points.is_empty() {
+        ctx.gfx_context.debug_shapes = shapes;
+        return;
+    }
+
+    // size of a pixel of the target, in screen coordinates
+    let screen = ctx.gfx_context.screen_rect;
+    let (target_w, target_h) = crate::graphics::target_size(ctx);
+    let pixel = (screen.w.abs() / target_w, screen.h.abs() / target_h);
+
+    let mut quads = Vec::with_capacity(shapes.lines.len() + shapes.points.len());
+    for &(a, b, color) in &shapes.lines {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let length = (dx * dx + dy * dy).sqrt().max(std::f32::EPSILON);
+        // half a pixel to each side of the line
+        let (nx, ny) = (-dy / length * pixel.0 / 2., dx / length * pixel.1 / 2.);
+        quads.push((
+            [
+                [a.x + nx, a.y + ny],
+                [b.x + nx, b.y + ny],
+                [b.x - nx, b.y - ny],
+                [a.x - nx, a.y - ny],
+            ],
+            color,
+        ));
+    }
+    for &(p, color) in &shapes.points {
+        let (hx, hy) = (pixel.0 * 1.5, pixel.1 * 1.5);
+        quads.push((
+            [
+                [p.x - hx, p.y - hy],
+                [p.x + hx, p.y - hy],
+                [p.x + hx, p.y + hy],
+                [p.x - hx, p.y + hy],
+            ],
+            color,
+        ));
+    }
+
+    // u16 indices, at most 16384 quads per draw call
+    for chunk in quads.chunks(std::u16::MAX as usize / 4 + 1) {
+        let mut vertices = Vec::with_capacity(chunk.len() * 4);
+        let mut indices = Vec::with_capacity(chunk.len() * 6);
+        for (corners, color) in chunk {
+            let first = vertices.len() as u16;
+            indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+            vertices.extend(corners.iter().map(|&pos| DebugVertex {
+                pos,
+                uv: [0., 0.],
+                color: [color.r, color.g, color.b, color.a],
+            }));
+        }
+        draw_chunk(ctx, &vertices, &indices);
+    }
+
+    // hand the allocations back for the next frame
+    let mut shapes = shapes;
+    shapes.lines.clear();
+    shapes.points.clear();
+    ctx.gfx_context.debug_shapes = shapes;
+}
+
+fn draw_chunk(ctx: &mut Context, vertices: &[DebugVertex], indices: &[u16]) {
+    let (vertex_buffer, index_buffer) = ctx.gfx_context.mesh_batch_buffers(
+        &mut ctx.quad_ctx,
+        std::mem::size_of_val(vertices),
+        std::mem::size_of_val(indices),
+    );
+    vertex_buffer.update(&mut ctx.quad_ctx, vertices);
+    index_buffer.update(&mut ctx.quad_ctx, indices);
+    let bindings = Bindings {
+        vertex_buffers: vec![vertex_buffer],
+        index_buffer,
+        images: vec![ctx.gfx_context.white_texture],
+    };
+
+    crate::graphics::begin_draw_pass(ctx, None);
+    let pipeline = ctx.gfx_context.pipeline(
+        &mut ctx.quad_ctx,
+        PipelineKind::Mesh,
+        Some(BlendMode::Alpha),
+    );
+    ctx.quad_ctx.apply_pipeline(&pipeline);
+    ctx.quad_ctx.apply_bindings(&bindings);
+    ctx.quad_ctx.apply_uniforms(&mesh_shader::Uniforms {
+        projection: ctx.gfx_context.projection,
+        model: Matrix4::from_scale(1.),
+        color: Vector4::new(1., 1., 1., 1.),
+        color_add: Vector4::new(0., 0., 0., 0.),
+        // in front of everything in a canvas with depth
+        depth: -1.,
+    });
+    ctx.quad_ctx.draw(0, indices.len() as i32, 1);
+    ctx.quad_ctx.end_render_pass();
+}
@@ -0,0 +1,169 @@
+//! Redrawing only the parts of the screen that changed.
+
+use crate::{
+    graphics::{self, BlendMode, Color, DrawParam, Drawable, Point2, Rect, Vector2},
+    Context, GameResult,
+};
+
+pub(crate) struct DirtyRegions {
+    /// Marked since the last frame, in screen coordinates.
+    rects: Vec<Rect>,
+    /// Everything has to be redrawn, like after a resize.
+    all: bool,
+    skip_if_clean: bool,
+    /// The scissor set by the game, while the dirty one replaces it.
+    game_scissor: Option<Rect>,
+}
+
+/// Redraws only the regions marked with [`mark_dirty()`](fn.mark_dirty.html)
+/// since the last frame, and, by default, calls
+/// [`draw()`](../event/trait.EventHandler.html#tymethod.draw) only if there
+/// was any, for mostly static screens like a turn-based game or a menu:
+/// no drawing at all happens while nothing changes, which saves a lot of
+/// battery on phones and laptops.
+///
+/// `draw()` still draws everything as usual, but the scissor is set to the
+/// bounds of the dirty regions while it runs, so only their pixels change:
+/// the game doesn't have to know what's in which region. Calling
+/// [`set_scissor()`](fn.set_scissor.html) replaces that scissor for the rest
+/// of the frame, and it applies to canvases drawn into during `draw()` too.
+/// [`clear()`](fn.clear.html) and the automatic clear only clear the dirty
+/// regions.
+///
+/// Presenting still draws a whole frame, even a clean one: the back buffer
+/// is not kept from one frame to the next, on web and with most native
+/// drivers, so frames are rendered into a kept offscreen canvas, drawn to the
+/// screen with a single full-screen quad every frame. That is one more draw
+/// call per frame, and twice the memory of the screen.
+///
+/// Everything is dirty when enabled and after the window is resized.
+/// Fails if the offscreen canvas can't be created.
+pub fn set_dirty_tracking(ctx: &mut Context, enabled: bool) -> GameResult {
+    if !enabled {
+        if ctx.gfx_context.dirty.take().is_some()
+            && ctx
+                .gfx_context
+                .frame
+                .as_ref()
+                .map_or(false, |frame| frame.shader.is_none())
+        {
+            ctx.gfx_context.frame = None;
+        }
+        return Ok(());
+    }
+
+    if ctx.gfx_context.dirty.is_none() {
+        if ctx.gfx_context.frame.is_none() {
+            graphics::frame::create_frame(ctx, false)?;
+        }
+        ctx.gfx_context.dirty = Some(DirtyRegions {
+            rects: vec![],
+            all: true,
+            skip_if_clean: true,
+            game_scissor: None,
+        });
+    }
+    Ok(())
+}
+
+/// Returns true if dirty tracking is enabled, see
+/// [`set_dirty_tracking()`](fn.set_dirty_tracking.html).
+pub fn is_dirty_tracking(ctx: &Context) -> bool {
+    ctx.gfx_context.dirty.is_some()
+}
+
+/// Marks `rect`, in screen coordinates, to be redrawn on the next frame.
+/// Does nothing unless dirty tracking is enabled.
+///
+/// Regions are merged into their bounds, so two small changes in opposite
+/// corners redraw the whole screen; that's still much less than redrawing
+/// every frame.
+pub fn mark_dirty(ctx: &mut Context, rect: Rect) {
+    if let Some(dirty) = &mut ctx.gfx_context.dirty {
+        dirty.rects.push(rect);
+    }
+}
+
+/// Marks the whole screen to be redrawn on the next frame, like after
+/// switching to another screen of the game.
+pub fn mark_all_dirty(ctx: &mut Context) {
+    if let Some(dirty) = &mut ctx.gfx_context.dirty {
+        dirty.all = true;
+    }
+}
+
+/// Sets whether `draw()` is skipped on frames with no dirty region, `true`
+/// by default. When not skipped, it runs with a scissor of no size, and
+/// draws nothing.
+pub fn set_skip_if_clean(ctx: &mut Context, skip: bool) {
+    if let Some(dirty) = &mut ctx.gfx_context.dirty {
+        dirty.skip_if_clean = skip;
+    }
+}
+
+/// Sets the dirty scissor for the frame. Returns false if `draw()` should
+/// be skipped.
+pub(crate) fn begin_frame(ctx: &mut Context) -> bool {
+    let screen = graphics::screen_coordinates(ctx);
+    let scissor = ctx.gfx_context.scissor;
+    let dirty = match &mut ctx.gfx_context.dirty {
+        Some(dirty) => dirty,
+        None => return true,
+    };
+
+    let bounds = if dirty.all {
+        Some(screen)
+    } else {
+        dirty.rects.iter().fold(None, |bounds: Option<Rect>, rect| {
+            Some(bounds.map_or(*rect, |bounds| bounds.combine_with(*rect)))
+        })
+    };
+    if bounds.is_none() && dirty.skip_if_clean {
+        return false;
+    }
+
+    dirty.game_scissor = scissor;
+    let nothing = Rect::new(screen.x, screen.y, 0., 0.);
+    ctx.gfx_context.scissor = Some(bounds.unwrap_or(nothing));
+    true
+}
+
+/// Gives the game its scissor back, and forgets the dirty regions.
+pub(crate) fn end_frame(ctx: &mut Context, drawn: bool) {
+    if let Some(dirty) = &mut ctx.gfx_context.dirty {
+        if drawn {
+            ctx.gfx_context.scissor = dirty.game_scissor.take();
+        }
+        dirty.rects.clear();
+        dirty.all = false;
+    }
+}
+
+/// Clears what the scissor leaves of the screen, instead of all of it, if
+/// dirty tracking is enabled and the screen is the render target.
+/// Returns false if the screen should be cleared as usual.
+pub(crate) fn clear(ctx: &mut Context, color: Color) -> bool {
+    let scissor = match (&ctx.gfx_context.dirty, &ctx.gfx_context.canvas) {
+        (Some(_), None) => ctx.gfx_context.scissor,
+        _ => return false,
+    };
+    let rect = scissor.unwrap_or_else(|| graphics::screen_coordinates(ctx));
+
+    let mut white_image = graphics::white_image(ctx);
+    white_image.set_blend_mode(Some(BlendMode::Replace));
+    let shader = ctx.gfx_context.shader.take();
+    ctx.gfx_context.push_transform(&cgmath::One::one());
+
+    // failing to draw a solid quad is not worth reporting from `clear()`
+    let _ = white_image.draw(
+        ctx,
+        DrawParam::new()
+            .dest(Point2::new(rect.x, rect.y))
+            .scale(Vector2::new(rect.w, rect.h))
+            .color(color),
+    );
+
+    ctx.gfx_context.pop_transform();
+    ctx.gfx_context.shader = shader;
+    true
+}
@@ -0,0 +1,188 @@
+use std::any::Any;
+
+use cgmath::Matrix3;
+
+use crate::{
+    error::GameResult,
+    graphics::{spritebatch::SpriteBatch, DrawParam, Drawable, Image, Shader, Text},
+    Context,
+};
+
+/// A drawable waiting in the queue filled by `graphics::queue`.
+pub(crate) struct QueuedDraw {
+    layer: i32,
+    group: Group,
+    drawable: Queued,
+    param: DrawParam,
+    /// The transform and shader when queued, put back to draw it.
+    transform: Matrix3<f32>,
+    shader: Option<Shader>,
+}
+
+enum Queued {
+    /// Kept apart, consecutive images of the same texture are batched.
+    Image(Image),
+    Other(Box<dyn Drawable>),
+}
+
+/// What the queue sorts by within a layer, so that draws sharing a texture
+/// end up next to each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Group {
+    Texture(u32),
+    Font(usize),
+    Ungrouped,
+}
+
+/// Queues `drawable` to be drawn by [`flush_queue()`](fn.flush_queue.html),
+/// on `layer`: lower layers are drawn first, below higher ones.
+///
+/// The drawable is cloned, which is cheap for images, texts and canvases,
+/// they share their GPU resources with the original. The current transform
+/// and [shader](fn.set_shader.html) are saved with it, and used again to
+/// draw it, whatever they are when the queue is flushed.
+///
+/// Within a layer, draws are grouped to switch textures as little as
+/// possible: images by texture, texts by font, then everything else.
+/// Each group keeps its queue order, but groups are drawn one after another,
+/// so draws of different groups that must overlap in a given order should
+/// go on different layers. Consecutive images of the same texture that only
+/// differ in `src`, `dest`, `rotation`, `scale`, `offset` and `color` are
+/// drawn in a single draw call, as a sprite batch, unless they were queued
+/// with a shader, have an [alpha threshold](struct.Image.html#method.set_alpha_threshold)
+/// or are drawn with `pixel_snap`.
+///
+/// ```rust,ignore
+/// graphics::queue(ctx, &self.player, 1, (player_pos,));
+/// for tree in &self.trees {
+///     graphics::queue(ctx, &self.tree_image, 0, (tree.pos,));
+/// }
+/// graphics::flush_queue(ctx)?;
+/// ```
+pub fn queue<D, P>(ctx: &mut Context, drawable: &D, layer: i32, param: P)
+where
+    D: Drawable + Clone + 'static,
+    P: Into<DrawParam>,
+{
+    let any = drawable as &dyn Any;
+    let (group, drawable) = if let Some(image) = any.downcast_ref::<Image>() {
+        (
+            Group::Texture(image.texture.texture),
+            Queued::Image(image.clone()),
+        )
+    } else if let Some(text) = any.downcast_ref::<Text>() {
+        (
+            Group::Font(text.font_id().0),
+            Queued::Other(Box::new(text.clone())),
+        )
+    } else {
+        (Group::Ungrouped, Queued::Other(Box::new(drawable.clone())))
+    };
+
+    ctx.gfx_context.draw_queue.push(QueuedDraw {
+        layer,
+        group,
+        drawable,
+        param: param.into(),
+        transform: ctx.gfx_context.transform(),
+        shader: ctx.gfx_context.shader,
+    });
+}
+
+/// Draws everything queued with [`queue()`](fn.queue.html) on the current
+/// target, sorted by layer, and empties the queue.
+///
+/// [`present()`](fn.present.html) calls it, drawing what is left on the
+/// screen on top of everything; call it to draw the queue on a canvas,
+/// or under something.
+pub fn flush_queue(ctx: &mut Context) -> GameResult {
+    let mut queue = std::mem::replace(&mut ctx.gfx_context.draw_queue, vec![]);
+    if queue.is_empty() {
+        ctx.gfx_context.draw_queue = queue;
+        return Ok(());
+    }
+    // a stable sort, to keep the queue order within a group
+    queue.sort_by_key(|draw| (draw.layer, draw.group));
+
+    let shader = ctx.gfx_context.shader;
+    let mut result = Ok(());
+    let mut start = 0;
+    while start < queue.len() && result.is_ok() {
+        let mut end = start + 1;
+        while end < queue.len() && batchable(&queue[start], &queue[end]) {
+            end += 1;
+        }
+
+        ctx.gfx_context.push_transform(&queue[start].transform);
+        ctx.gfx_context.shader = queue[start].shader;
+        result = match (&queue[start].drawable, end - start) {
+            (Queued::Image(image), len) if len > 1 => draw_batch(ctx, image, &queue[start..end]),
+            (Queued::Image(image), _) => image.draw(ctx, queue[start].param),
+            (Queued::Other(drawable), _) => drawable.draw(ctx, queue[start].param),
+        };
+        ctx.gfx_context.pop_transform();
+        start = end;
+    }
+    ctx.gfx_context.shader = shader;
+
+    // hand the allocation back for the next frame
+    queue.clear();
+    ctx.gfx_context.draw_queue = queue;
+    result
+}
+
+/// Whether `next` can be drawn in the same sprite batch as `first`.
+fn batchable(first: &QueuedDraw, next: &QueuedDraw) -> bool {
+    let (a, b) = match (&first.drawable, &next.drawable) {
+        (Queued::Image(a), Queued::Image(b)) => (a, b),
+        _ => return false,
+    };
+    let plain = |param: &DrawParam| param.color_matrix.is_none() && !param.pixel_snap;
+
+    // sprite batches have no custom shaders, alpha test or pixel snapping
+    first.shader.is_none()
+        && next.shader.is_none()
+        && a.alpha_threshold().is_none()
+        && b.alpha_threshold().is_none()
+        && first.transform == next.transform
+        && first.layer == next.layer
+        && first.group == next.group
+        && a.blend_mode() == b.blend_mode()
+        && a.filter() == b.filter()
+        && plain(&first.param)
+        && plain(&next.param)
+        && first.param.clip == next.param.clip
+        && first.param.color_add == next.param.color_add
+        && first.param.depth == next.param.depth
+}
+
+fn draw_batch(ctx: &mut Context, image: &Image, draws: &[QueuedDraw]) -> GameResult {
+    let mut batch = match ctx.gfx_context.queue_batch.take() {
+        Some(mut batch) => {
+            batch.set_image(image.clone());
+            batch.clear();
+            batch
+        }
+        None => SpriteBatch::new(image.clone()),
+    };
+    batch.set_blend_mode(image.blend_mode());
+    for draw in draws {
+        batch.add(draw.param);
+    }
+
+    let param = draws[0].param;
+    let result = batch.draw(
+        ctx,
+        DrawParam {
+            clip: param.clip,
+            color_add: param.color_add,
+            depth: param.depth,
+            ..DrawParam::default()
+        },
+    );
+
+    // don't keep the image alive until the next flush
+    batch.set_image(ctx.gfx_context.white_image.clone());
+    ctx.gfx_context.queue_batch = Some(batch);
+    result
+}
@@ -1,8 +1,18 @@
+use cgmath::Matrix4;
 use mint::{Point2, Vector2};
 
-use crate::graphics::{Color, Rect, WHITE};
+use crate::graphics::{Color, ColorMatrix, Rect, WHITE};
 
+/// How to draw a `Drawable`: where, how big, rotated and tinted how.
+///
+/// With the `serde` feature, missing fields take their default value
+/// when deserializing.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct DrawParam {
     /// A portion of the drawable to clip, as a fraction of the whole image.
     /// Defaults to the whole image `(0,0 to 1,1)` if omitted.
@@ -21,6 +31,30 @@ pub struct DrawParam {
     /// A color to draw the target with.
     /// Default: white.
     pub color: Color,
+    /// A color added to every pixel after it was multiplied by `color`,
+    /// so a drawable can be brightened, e.g. flashed white when hit.
+    /// Default: transparent black, which changes nothing.
+    pub color_add: Color,
+    /// A rectangle, in the same coordinates as `dest`, outside of which
+    /// nothing of this draw call is drawn.
+    /// Default: `None`, no clipping.
+    pub clip: Option<Rect>,
+    /// A color transform applied after `color` and before `color_add`.
+    /// Default: `None`, which is the same as the identity but cheaper.
+    pub color_matrix: Option<ColorMatrix>,
+    /// Mirrors the drawable horizontally within its bounds.
+    /// Default: `false`.
+    pub flip_x: bool,
+    /// Mirrors the drawable vertically within its bounds.
+    /// Default: `false`.
+    pub flip_y: bool,
+    /// Snaps the edges of the drawable to whole pixels of the render target.
+    /// Default: `false`.
+    pub pixel_snap: bool,
+    /// The depth the drawable is drawn at, from `-1.0`, the nearest, to
+    /// `1.0`, the farthest, for canvases with a depth buffer.
+    /// Default: `0.0`.
+    pub depth: f32,
 }
 
 impl Default for DrawParam {
@@ -32,6 +66,13 @@ impl Default for DrawParam {
             scale: [1.0, 1.0].into(),
             offset: [0.0, 0.0].into(),
             color: WHITE,
+            color_add: Color::new(0.0, 0.0, 0.0, 0.0),
+            clip: None,
+            color_matrix: None,
+            flip_x: false,
+            flip_y: false,
+            pixel_snap: false,
+            depth: 0.0,
         }
     }
 }
@@ -42,6 +83,33 @@ impl DrawParam {
         Self::default()
     }
 
+    /// Creates a DrawParam with the `dest`, `rotation` and `scale` of a 2D
+    /// transform, the other fields keep their default value. This is the
+    /// inverse of the transform a `DrawParam` with no offset is drawn with.
+    ///
+    /// Only the translation and the XY part of `matrix` are used. Shear can't
+    /// be represented and is lost; a mirrored transform comes back with a
+    /// negative `scale.y`.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        let (x, y) = (matrix.x, matrix.y);
+        let rotation = x.y.atan2(x.x);
+        let (sinr, cosr) = rotation.sin_cos();
+        let scale_x = (x.x * x.x + x.y * x.y).sqrt();
+        // the Y axis projected on the rotated one, signed
+        let scale_y = cosr * y.y - sinr * y.x;
+
+        DrawParam::new()
+            .dest(Point2 {
+                x: matrix.w.x,
+                y: matrix.w.y,
+            })
+            .rotation(rotation)
+            .scale(Vector2 {
+                x: scale_x,
+                y: scale_y,
+            })
+    }
+
     /// Set the source rect
     pub fn src(mut self, src: Rect) -> Self {
         self.src = src;
@@ -64,6 +132,83 @@ impl DrawParam {
         self
     }
 
+    /// Set the color added on top of the drawable, after `color` was applied.
+    ///
+    /// The alpha component is added too, so keep it at `0.0` to leave
+    /// transparent pixels transparent.
+    pub fn color_add(mut self, color_add: Color) -> Self {
+        self.color_add = color_add;
+        self
+    }
+
+    /// Clips this draw call to the given rectangle, in the same coordinates
+    /// as `dest` and affected by the current transform.
+    ///
+    /// It is intersected with the scissor set with
+    /// [`graphics::set_scissor()`](fn.set_scissor.html), if any.
+    /// A rotated transform clips to the bounds of the rotated rectangle.
+    /// `Text` ignores it.
+    pub fn clip(mut self, clip: Rect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Transforms the colors of the drawable with `matrix`, for effects like
+    /// hue rotation, saturation and brightness, or color-blind modes.
+    ///
+    /// Only `Image` and `Canvas` support it, and not while a shader is set
+    /// with [`graphics::set_shader()`](fn.set_shader.html); other drawables
+    /// ignore it. Matrices can be chained with
+    /// [`ColorMatrix::then()`](struct.ColorMatrix.html#method.then).
+    pub fn color_matrix(mut self, matrix: ColorMatrix) -> Self {
+        self.color_matrix = Some(matrix);
+        self
+    }
+
+    /// Mirrors the drawable horizontally, in place: `dest`, `offset`,
+    /// `rotation` and `scale` work the same as without flipping, and so do
+    /// the bounds. Unlike a negative `scale.x`, the drawable doesn't move.
+    ///
+    /// Supported by `Image`, `Canvas`, `PalettedImage` and sprite batches;
+    /// other drawables ignore it.
+    pub fn flip_x(mut self, flip: bool) -> Self {
+        self.flip_x = flip;
+        self
+    }
+
+    /// Mirrors the drawable vertically, in place, like
+    /// [`flip_x()`](#method.flip_x) does horizontally.
+    pub fn flip_y(mut self, flip: bool) -> Self {
+        self.flip_y = flip;
+        self
+    }
+
+    /// Snaps the edges of the drawable to whole pixels of the render target,
+    /// after the transform and screen coordinates, for crisp solid rects like
+    /// UI dividers: a rect one pixel wide always covers exactly one pixel,
+    /// at any zoom, and one that's thinner still covers one.
+    ///
+    /// Supported by `Image` and `Canvas` when they aren't rotated, which
+    /// includes rects drawn with [`graphics::white_image()`](fn.white_image.html);
+    /// other drawables and rotated images ignore it.
+    pub fn pixel_snap(mut self, snap: bool) -> Self {
+        self.pixel_snap = snap;
+        self
+    }
+
+    /// Sets the depth the drawable is drawn at, only meaningful while a
+    /// canvas made with [`Canvas::new_with_depth()`](struct.Canvas.html#method.new_with_depth)
+    /// is the render target: what's drawn deeper than what is already there
+    /// is hidden, whatever the drawing order. Depths are clamped to
+    /// `[-1, 1]`, `-1.0` being the nearest.
+    ///
+    /// Sprite batches are drawn at the depth of the `DrawParam` given to
+    /// their `draw()`, not those of their sprites; `Text` ignores it.
+    pub fn depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
     /// Set the rotation of the drawable.
     pub fn rotation(mut self, rotation: f32) -> Self {
         self.rotation = rotation;
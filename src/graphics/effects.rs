@@ -0,0 +1,77 @@
+//! Ready-made [`Shader`](../struct.Shader.html)s for whole-screen effects.
+//!
+//! Render the scene to a `Canvas`, then draw the canvas with one of these:
+//!
+//! ```rust,ignore
+//! let desaturate = graphics::effects::grayscale(ctx)?;
+//! desaturate.set_params(ctx, [0.8, 0., 0., 0.]);
+//!
+//! graphics::set_shader(ctx, Some(&desaturate));
+//! graphics::draw(ctx, &self.scene, DrawParam::new())?;
+//! graphics::set_shader(ctx, None);
+//! ```
+
+use crate::{graphics::Shader, Context, GameResult};
+
+const GRAYSCALE: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform lowp vec4 ColorAdd;
+uniform lowp vec4 Params;
+
+void main() {
+    lowp vec4 texel = texture2D(Texture, uv) * color;
+    lowp float gray = dot(texel.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(mix(texel.rgb, vec3(gray), Params.x), texel.a) + ColorAdd;
+}"#;
+
+const SEPIA: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform lowp vec4 ColorAdd;
+
+void main() {
+    lowp vec4 texel = texture2D(Texture, uv) * color;
+    lowp vec3 sepia = vec3(
+        dot(texel.rgb, vec3(0.393, 0.769, 0.189)),
+        dot(texel.rgb, vec3(0.349, 0.686, 0.168)),
+        dot(texel.rgb, vec3(0.272, 0.534, 0.131))
+    );
+    gl_FragColor = vec4(min(sepia, vec3(1.0)), texel.a) + ColorAdd;
+}"#;
+
+const INVERT: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform lowp vec4 ColorAdd;
+
+void main() {
+    lowp vec4 texel = texture2D(Texture, uv) * color;
+    gl_FragColor = vec4(vec3(1.0) - texel.rgb, texel.a) + ColorAdd;
+}"#;
+
+/// Desaturates the image.
+///
+/// `Params.x` is the intensity: `0.0` keeps the original colors,
+/// `1.0` (the default) is fully gray.
+pub fn grayscale(ctx: &mut Context) -> GameResult<Shader> {
+    let shader = Shader::from_fragment(ctx, GRAYSCALE)?;
+    shader.set_params(ctx, [1., 0., 0., 0.]);
+    Ok(shader)
+}
+
+/// Gives the image an old-photo brown tone.
+pub fn sepia(ctx: &mut Context) -> GameResult<Shader> {
+    Shader::from_fragment(ctx, SEPIA)
+}
+
+/// Inverts the colors of the image, alpha is kept.
+pub fn invert(ctx: &mut Context) -> GameResult<Shader> {
+    Shader::from_fragment(ctx, INVERT)
+}
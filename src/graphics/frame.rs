@@ -0,0 +1,72 @@
+//! Rendering the whole frame offscreen, for effects applied when presenting
+//! and for keeping it between frames.
+
+use crate::{
+    graphics::{self, BlendMode, Canvas, DrawParam, Drawable, Point2, Shader, Vector2},
+    Context, GameResult,
+};
+
+/// Linear to sRGB, approximated with a 2.2 gamma.
+const SRGB: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    mediump vec4 texel = texture2D(Texture, uv);
+    gl_FragColor = vec4(pow(texel.rgb, vec3(1.0 / 2.2)), texel.a);
+}"#;
+
+pub(crate) struct FrameCanvas {
+    pub canvas: Canvas,
+    /// The sRGB conversion, if asked for in `WindowMode::srgb`.
+    pub shader: Option<Shader>,
+}
+
+/// Starts rendering frames offscreen, or recreates the offscreen target
+/// with the current window size. An existing target keeps its shader,
+/// a new one gets the sRGB one if `srgb` is set.
+pub(crate) fn create_frame(ctx: &mut Context, srgb: bool) -> GameResult {
+    let shader = match ctx.gfx_context.frame.take() {
+        Some(frame) => frame.shader,
+        None if srgb => Some(Shader::from_fragment(ctx, SRGB)?),
+        None => None,
+    };
+
+    let mut canvas = Canvas::with_window_size(ctx)?;
+    canvas.set_blend_mode(Some(BlendMode::Replace));
+
+    ctx.gfx_context.frame = Some(FrameCanvas { canvas, shader });
+    Ok(())
+}
+
+/// Draws the offscreen frame, if any, to the screen.
+pub(crate) fn draw_frame(ctx: &mut Context) -> GameResult {
+    let frame = match ctx.gfx_context.frame.take() {
+        Some(frame) => frame,
+        None => return Ok(()),
+    };
+
+    // the current state is the user's, the frame covers the whole screen as is
+    let shader = std::mem::replace(&mut ctx.gfx_context.shader, frame.shader);
+    let scissor = ctx.gfx_context.scissor.take();
+    ctx.gfx_context.push_transform(&cgmath::One::one());
+
+    let screen = graphics::screen_coordinates(ctx);
+    let image = frame.canvas.image();
+    let param = DrawParam::new()
+        .dest(Point2::new(screen.x, screen.y))
+        .scale(Vector2::new(
+            screen.w / f32::from(image.width()),
+            screen.h / f32::from(image.height()),
+        ));
+    let result = frame.canvas.draw(ctx, param);
+
+    ctx.gfx_context.pop_transform();
+    ctx.gfx_context.scissor = scissor;
+    ctx.gfx_context.shader = shader;
+    ctx.gfx_context.frame = Some(frame);
+
+    result
+}
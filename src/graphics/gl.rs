@@ -0,0 +1,412 @@
+//! The few GL queries miniquad does not wrap.
+//!
+//! Everything here talks to the GL context miniquad created, so it must only
+//! be called once the `Context` exists.
+
+use miniquad::sapp::{
+    glActiveTexture, glBindTexture, glCompileShader, glCreateShader, glDeleteShader, glGetIntegerv,
+    glGetShaderInfoLog, glGetShaderiv, glShaderSource, glTexParameteri, GLenum, GL_COMPILE_STATUS,
+    GL_FRAGMENT_SHADER, GL_INFO_LOG_LENGTH, GL_LINEAR_MIPMAP_LINEAR, GL_MAX_TEXTURE_SIZE,
+    GL_NEAREST_MIPMAP_NEAREST, GL_TEXTURE0, GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER,
+};
+// not in the JS GL bindings of miniquad, only called on other platforms
+#[cfg(not(target_arch = "wasm32"))]
+use miniquad::sapp::{
+    glBeginQuery, glCopyTexSubImage2D, glDeleteQueries, glDrawBuffers, glEndQuery,
+    glFramebufferTexture2D, glGenQueries, glGenerateMipmap, glGetError, glGetFloatv,
+    glGetQueryObjectuiv, GL_COLOR_ATTACHMENT0, GL_FRAMEBUFFER, GL_NO_ERROR,
+};
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+use miniquad::sapp::{glGetString, GL_VERSION};
+use miniquad::{graphics::FilterMode, Texture};
+
+/// The texture unit textures are bound to here. miniquad binds the images of
+/// a draw to the first units, and sets the active unit before each binding,
+/// so its cache of bindings never sees this one.
+const SCRATCH_UNIT: GLenum = GL_TEXTURE0 + 7;
+
+pub(crate) fn max_texture_size() -> u32 {
+    let mut size = 0;
+    unsafe {
+        glGetIntegerv(GL_MAX_TEXTURE_SIZE, &mut size);
+    }
+    size as u32
+}
+
+/// The `GL_VERSION` string, like `"OpenGL ES 3.0 Mesa 20.0"` or, on
+/// desktop, `"4.6.0 NVIDIA 450.80"`, empty if it can't be queried.
+///
+/// The JS GL bindings of miniquad have no `glGetString`, and always create a
+/// WebGL 1 context, so on web this is what WebGL 1 reports. The native GL
+/// bindings differ between platforms, `glGetString` is only called on the
+/// ones checked to have it; on the others, like macOS, this is empty.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+pub(crate) fn version() -> String {
+    unsafe {
+        let version = glGetString(GL_VERSION);
+        if version.is_null() {
+            return String::new();
+        }
+        std::ffi::CStr::from_ptr(version as *const _)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn version() -> String {
+    "WebGL 1.0".to_string()
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "windows",
+    target_os = "android",
+    target_arch = "wasm32"
+)))]
+pub(crate) fn version() -> String {
+    String::new()
+}
+
+/// Compiles `source` as a fragment shader and throws it away, returning the
+/// info log if it doesn't compile. miniquad panics on shader errors, this
+/// finds them first.
+pub(crate) fn fragment_shader_error(source: &str) -> Option<String> {
+    let source = match std::ffi::CString::new(source) {
+        Ok(source) => source,
+        Err(_) => return Some("the shader source contains a NUL byte".to_string()),
+    };
+    unsafe {
+        let shader = glCreateShader(GL_FRAGMENT_SHADER);
+        glShaderSource(shader, 1, &(source.as_ptr() as *const _), std::ptr::null());
+        glCompileShader(shader);
+
+        let mut compiled = 0;
+        glGetShaderiv(shader, GL_COMPILE_STATUS, &mut compiled);
+        let error = if compiled == 0 {
+            let mut length = 0;
+            glGetShaderiv(shader, GL_INFO_LOG_LENGTH, &mut length);
+            let mut log = vec![0u8; length.max(1) as usize];
+            glGetShaderInfoLog(
+                shader,
+                length,
+                std::ptr::null_mut(),
+                log.as_mut_ptr() as *mut _,
+            );
+            let end = log.iter().position(|&b| b == 0).unwrap_or(log.len());
+            Some(String::from_utf8_lossy(&log[..end]).into_owned())
+        } else {
+            None
+        };
+        glDeleteShader(shader);
+        error
+    }
+}
+
+/// Runs `f` with `texture` bound to the scratch unit, leaving the units
+/// miniquad uses, and its cache of their bindings, as they were.
+///
+/// This doesn't read the current binding back, `GL_TEXTURE_BINDING_2D` is a
+/// texture object in WebGL that the JS GL bindings of miniquad can't return.
+fn with_texture_bound(texture: &Texture, f: impl FnOnce()) {
+    unsafe {
+        glActiveTexture(SCRATCH_UNIT);
+        glBindTexture(GL_TEXTURE_2D, texture.texture);
+        f();
+        glBindTexture(GL_TEXTURE_2D, 0);
+        glActiveTexture(GL_TEXTURE0);
+    }
+}
+
+/// Builds the mip chain of `texture` from its level 0. Returns false if
+/// mipmaps can't be built.
+///
+/// The JS GL bindings of miniquad have no `glGenerateMipmap`, so on web it
+/// always returns false.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn generate_mipmaps(texture: &Texture) -> bool {
+    with_texture_bound(texture, || unsafe {
+        glGenerateMipmap(GL_TEXTURE_2D);
+    });
+    true
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn generate_mipmaps(_texture: &Texture) -> bool {
+    false
+}
+
+/// Sets the minification filter of a mipmapped `texture`, trilinear for `Linear`.
+/// miniquad only knows the filters without mips, and sets both at once.
+pub(crate) fn set_mipmap_filter(texture: &Texture, filter: FilterMode) {
+    let min_filter = match filter {
+        FilterMode::Linear => GL_LINEAR_MIPMAP_LINEAR,
+        FilterMode::Nearest => GL_NEAREST_MIPMAP_NEAREST,
+    };
+    with_texture_bound(texture, || unsafe {
+        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, min_filter as i32);
+    });
+}
+
+const GL_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FE;
+const GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FF;
+
+/// Highest anisotropic filtering level, 1 without EXT_texture_filter_anisotropic.
+///
+/// The JS GL bindings of miniquad do not enable the WebGL extension,
+/// so on web it is always 1, without calling into GL at all.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn max_anisotropy() -> u8 {
+    let mut level = 1.0;
+    unsafe {
+        // clear older errors, so an unknown enum is the only possible one
+        while glGetError() != GL_NO_ERROR {}
+        glGetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut level);
+        if glGetError() != GL_NO_ERROR {
+            return 1;
+        }
+    }
+    level.max(1.0).min(255.0) as u8
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn max_anisotropy() -> u8 {
+    1
+}
+
+/// Sets the anisotropic filtering level of `texture`, which must be
+/// supported, see `max_anisotropy`.
+pub(crate) fn set_anisotropy(texture: &Texture, level: u8) {
+    with_texture_bound(texture, || unsafe {
+        glTexParameteri(
+            GL_TEXTURE_2D,
+            GL_TEXTURE_MAX_ANISOTROPY_EXT,
+            i32::from(level),
+        );
+    });
+}
+
+/// Copies the bottom-left `width` x `height` pixels of the bound framebuffer
+/// into level 0 of `texture`, bottom row first. Returns false if it can't.
+///
+/// The JS GL bindings of miniquad have no `glCopyTexSubImage2D`, so on web
+/// it always returns false.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn copy_framebuffer_to_texture(texture: &Texture, width: i32, height: i32) -> bool {
+    with_texture_bound(texture, || unsafe {
+        glCopyTexSubImage2D(GL_TEXTURE_2D, 0, 0, 0, 0, 0, width, height);
+    });
+    true
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn copy_framebuffer_to_texture(_texture: &Texture, _width: i32, _height: i32) -> bool {
+    false
+}
+
+const GL_MAX_DRAW_BUFFERS: GLenum = 0x8824;
+
+/// How many color targets a framebuffer can be drawn to at once.
+///
+/// WebGL1 needs the WEBGL_draw_buffers extension for more than one, which
+/// the JS GL bindings of miniquad do not enable, so on web it is always 1.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn max_draw_buffers() -> usize {
+    let mut count = 1;
+    unsafe {
+        glGetIntegerv(GL_MAX_DRAW_BUFFERS, &mut count);
+    }
+    count.max(1) as usize
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn max_draw_buffers() -> usize {
+    1
+}
+
+/// Attaches `textures` as the color targets 1 and up of the bound framebuffer,
+/// and draws to all of them from then on. Target 0 is left as is.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn attach_color_targets(textures: &[Texture]) {
+    let buffers: Vec<GLenum> = (0..=textures.len() as GLenum)
+        .map(|n| GL_COLOR_ATTACHMENT0 + n)
+        .collect();
+    unsafe {
+        for (attachment, texture) in buffers[1..].iter().zip(textures) {
+            glFramebufferTexture2D(
+                GL_FRAMEBUFFER,
+                *attachment,
+                GL_TEXTURE_2D,
+                texture.texture,
+                0,
+            );
+        }
+        glDrawBuffers(buffers.len() as i32, buffers.as_ptr());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn attach_color_targets(_textures: &[Texture]) {
+    // `max_draw_buffers` is 1 on web, there is never anything to attach
+}
+
+const GL_TIME_ELAPSED: GLenum = 0x88BF;
+const GL_QUERY_RESULT: GLenum = 0x8866;
+const GL_QUERY_RESULT_AVAILABLE: GLenum = 0x8867;
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+const GL_EXTENSIONS: GLenum = 0x1F03;
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+const GL_GPU_DISJOINT_EXT: GLenum = 0x8FBB;
+
+/// How timer queries can be used, found out from the version and the
+/// extensions of the context before calling any query function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TimerQueries {
+    Unsupported,
+    /// Desktop GL 3.3 or ARB_timer_query, where measures are always valid.
+    Supported,
+    /// GLES 3 with EXT_disjoint_timer_query, where measures are invalid
+    /// whenever `timer_disjoint` is true.
+    Disjoint,
+}
+
+/// Checks for timer queries without calling any query function, as GLES 2
+/// drivers don't have them, only their `EXT` suffixed versions the GL
+/// bindings of miniquad don't load: on GLES this needs version 3 and
+/// EXT_disjoint_timer_query.
+///
+/// Only platforms `version` can be queried on are checked, timer queries are
+/// unsupported on the others, like macOS. On web, the JS GL bindings of
+/// miniquad have no query functions, and WebGL 1 only has them through
+/// EXT_disjoint_timer_query, which needs a JS plugin and is turned off in
+/// most browsers, so they are unsupported there too.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+pub(crate) fn timer_queries() -> TimerQueries {
+    let version = version();
+    let (major, minor) = version_number(&version);
+    let extensions = unsafe {
+        // null in core profiles, where the version is enough
+        let extensions = glGetString(GL_EXTENSIONS);
+        if extensions.is_null() {
+            String::new()
+        } else {
+            std::ffi::CStr::from_ptr(extensions as *const _)
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    let has = |name| extensions.split_whitespace().any(|ext| ext == name);
+
+    if version.starts_with("OpenGL ES") {
+        if major >= 3 && has("GL_EXT_disjoint_timer_query") {
+            TimerQueries::Disjoint
+        } else {
+            TimerQueries::Unsupported
+        }
+    } else if (major, minor) >= (3, 3) || has("GL_ARB_timer_query") {
+        TimerQueries::Supported
+    } else {
+        TimerQueries::Unsupported
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "android")))]
+pub(crate) fn timer_queries() -> TimerQueries {
+    TimerQueries::Unsupported
+}
+
+/// The major and minor numbers of a `version` string, `(0, 0)` if there
+/// are none.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+fn version_number(version: &str) -> (u32, u32) {
+    let mut numbers = version
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|number| number.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+/// Checks and resets `GL_GPU_DISJOINT_EXT`, true if something like a
+/// change of GPU frequency happened since the last check, which makes the
+/// measures of the queries running or ended meanwhile invalid.
+/// Only for `TimerQueries::Disjoint`, the flag is unknown to desktop GL.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "android"))]
+pub(crate) fn timer_disjoint() -> bool {
+    let mut disjoint = 0;
+    unsafe {
+        glGetIntegerv(GL_GPU_DISJOINT_EXT, &mut disjoint);
+    }
+    disjoint != 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "android")))]
+pub(crate) fn timer_disjoint() -> bool {
+    false
+}
+
+/// Creates a query measuring the GPU time of what's drawn between
+/// `begin_timer_query` and `end_timer_query`, `None` if the driver still
+/// refuses it. Only call this if `timer_queries` found them supported.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn create_timer_query() -> Option<u32> {
+    let mut query = 0;
+    unsafe {
+        // clear older errors, so an unknown target is the only possible one
+        while glGetError() != GL_NO_ERROR {}
+        glGenQueries(1, &mut query);
+        // a query only gets its type once begun, which fails if unsupported
+        glBeginQuery(GL_TIME_ELAPSED, query);
+        glEndQuery(GL_TIME_ELAPSED);
+        if glGetError() != GL_NO_ERROR {
+            glDeleteQueries(1, &query);
+            return None;
+        }
+    }
+    Some(query)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn create_timer_query() -> Option<u32> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn begin_timer_query(query: u32) {
+    unsafe {
+        glBeginQuery(GL_TIME_ELAPSED, query);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn begin_timer_query(_query: u32) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn end_timer_query() {
+    unsafe {
+        glEndQuery(GL_TIME_ELAPSED);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn end_timer_query() {}
+
+/// The GPU time measured by an ended query, in nanoseconds, or `None` if
+/// the GPU isn't done with it yet. Never blocks.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn timer_query_result(query: u32) -> Option<u32> {
+    let mut available = 0;
+    let mut nanos = 0;
+    unsafe {
+        glGetQueryObjectuiv(query, GL_QUERY_RESULT_AVAILABLE, &mut available);
+        if available == 0 {
+            return None;
+        }
+        glGetQueryObjectuiv(query, GL_QUERY_RESULT, &mut nanos);
+    }
+    Some(nanos)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timer_query_result(_query: u32) -> Option<u32> {
+    None
+}
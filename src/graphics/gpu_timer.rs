@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::gl::{self, TimerQueries};
+use crate::Context;
+
+#[derive(Debug, Default)]
+pub(crate) struct GpuTimer {
+    /// `None` until the first `begin_gpu_timer`, found out then.
+    queries: Option<TimerQueries>,
+    /// The query between `begin_gpu_timer` and `end_gpu_timer`.
+    running: Option<u32>,
+    /// Ended queries the GPU may not be done with, oldest first.
+    pending: VecDeque<u32>,
+    /// Queries done with, for reuse.
+    free: Vec<u32>,
+    /// The last result read back.
+    last: Option<Duration>,
+}
+
+/// Starts measuring the time the GPU takes to draw what's drawn until
+/// [`end_gpu_timer()`](fn.end_gpu_timer.html), to find the expensive parts
+/// of a frame.
+///
+/// Uses GL timer queries, which need GL 3.3 or ARB_timer_query on desktop,
+/// and GLES 3 with EXT_disjoint_timer_query on mobile; they are checked for
+/// on Linux, Windows and Android, and unsupported elsewhere. They aren't
+/// available on web: the GL bindings of miniquad don't reach them, and
+/// EXT_disjoint_timer_query is turned off in most browsers. Timers can't be
+/// nested, a timer begun while another is running is ignored. Draws
+/// [queued](fn.queue.html) in between are only measured if they're flushed
+/// before the timer ends.
+pub fn begin_gpu_timer(ctx: &mut Context) {
+    let timer = &mut ctx.gfx_context.gpu_timer;
+    if timer.running.is_some() {
+        return;
+    }
+    let queries = *timer.queries.get_or_insert_with(gl::timer_queries);
+    if queries == TimerQueries::Unsupported {
+        return;
+    }
+
+    let query = timer.free.pop().or_else(gl::create_timer_query);
+    match query {
+        Some(query) => {
+            gl::begin_timer_query(query);
+            timer.running = Some(query);
+        }
+        None => timer.queries = Some(TimerQueries::Unsupported),
+    }
+}
+
+/// Stops the timer started with [`begin_gpu_timer()`](fn.begin_gpu_timer.html),
+/// and returns the latest measure the GPU has finished, `None` if timer
+/// queries are unsupported or no measure finished yet.
+///
+/// The GPU runs behind the CPU, so the measure of a frame is usually
+/// ready one to three frames later, and the one returned is that of an
+/// earlier frame: for a value that is always there, begin and end the timer
+/// around the same draws every frame. Nothing waits for the GPU.
+///
+/// On mobile, measures overlapping something like a change of GPU frequency
+/// are thrown away, and the previous one is returned again.
+pub fn end_gpu_timer(ctx: &mut Context) -> Option<Duration> {
+    let timer = &mut ctx.gfx_context.gpu_timer;
+    if let Some(query) = timer.running.take() {
+        gl::end_timer_query();
+        timer.pending.push_back(query);
+    }
+
+    if timer.queries == Some(TimerQueries::Disjoint) && gl::timer_disjoint() {
+        timer.free.extend(timer.pending.drain(..));
+    }
+
+    // queries finish in order
+    while let Some(&query) = timer.pending.front() {
+        match gl::timer_query_result(query) {
+            Some(nanos) => {
+                timer.last = Some(Duration::from_nanos(u64::from(nanos)));
+                timer.pending.pop_front();
+                timer.free.push(query);
+            }
+            None => break,
+        }
+    }
+    timer.last
+}
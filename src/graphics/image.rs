@@ -1,27 +1,55 @@
 use cgmath::{Matrix4, Transform, Vector2, Vector4};
 use std::path;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crate::{
-    error::GameResult,
+    conf::NumSamples,
+    error::{GameError, GameResult},
     filesystem,
-    graphics::{context::image_shader, BlendMode, DrawParam, Drawable, Rect},
+    graphics::{
+        canvas::CanvasPass,
+        context::{
+            alpha_test_shader, color_matrix_shader, custom_shader, image_shader, PipelineKind,
+        },
+        BlendMode, Canvas, Color, DrawParam, Drawable, Rect,
+    },
     Context,
 };
 
-use miniquad::{Bindings, Buffer, BufferType, PassAction, Texture};
+use miniquad::{Bindings, Buffer, BufferType, Texture};
 
 pub use miniquad::graphics::FilterMode;
 
+/// Per-instance data of an instanced draw, see
+/// [`graphics::draw_instanced()`](fn.draw_instanced.html).
+///
+/// The layout matches the sprite pipeline attributes and is uploaded as is.
 #[derive(Debug, Clone)]
 #[repr(C)]
-pub(crate) struct InstanceAttributes {
+pub struct InstanceAttributes {
+    /// The part of the image to draw, as `(x, y, w, h)` fractions
+    /// of the whole image, like `DrawParam::src`.
     pub source: Vector4<f32>,
+    /// The color to multiply the image with, as `(r, g, b, a)`.
     pub color: Vector4<f32>,
+    /// Maps the unit square to where the instance is drawn, so it
+    /// includes the size of the image (and `source`) in pixels.
     pub model: Matrix4<f32>,
 }
 
+impl InstanceAttributes {
+    /// The attributes drawing `image` like `graphics::draw` would with `param`.
+    pub fn from_param(image: &Image, param: &DrawParam) -> InstanceAttributes {
+        InstanceAttributes {
+            source: source_rect(param),
+            color: param.color.into(),
+            model: quad_transform(param, image.width, image.height),
+        }
+    }
+}
+
 impl Default for InstanceAttributes {
     fn default() -> InstanceAttributes {
         InstanceAttributes {
@@ -38,46 +66,232 @@ pub struct Image {
     pub(crate) width: u16,
     pub(crate) height: u16,
     filter: FilterMode,
+    blend_mode: Option<BlendMode>,
     pub(crate) bindings: Bindings,
     dirty_filter: Arc<AtomicBool>,
+    content_hash: Option<u64>,
+    /// RGBA pixels as uploaded, only kept if asked for at construction.
+    cpu_pixels: Option<Arc<Vec<u8>>>,
+    /// Set by `generate_mipmaps`.
+    mipmaps: bool,
+    /// Set by `set_anisotropy`.
+    anisotropy: u8,
+    /// Set by `set_alpha_threshold`.
+    alpha_threshold: Option<f32>,
+    /// The render pass of the canvas this is the image of, which owns the
+    /// texture.
+    pub(crate) canvas_pass: Option<Rc<CanvasPass>>,
 
     clones_hack: Arc<()>,
 }
 
 impl Image {
     pub fn new<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<Self> {
-        use std::io::Read;
+        let bytes = read_file(ctx, path)?;
+        Self::from_png_bytes(ctx, &bytes)
+    }
 
-        let mut file = filesystem::open(ctx, path)?;
+    /// Like [`new()`](#method.new), but also keeps the decoded pixels in memory,
+    /// see [`pixels()`](#method.pixels).
+    pub fn new_with_cpu_copy<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<Self> {
+        let bytes = read_file(ctx, path)?;
+        let (width, height, pixels) = decode_png(&bytes)?;
+        Self::from_rgba8_with_cpu_copy(ctx, width, height, pixels)
+    }
 
-        let mut bytes = vec![];
-        file.bytes.read_to_end(&mut bytes)?;
+    /// Reads `path` again and replaces the texture of this image with it,
+    /// for hot-reloading assets during development.
+    ///
+    /// The filter and blend mode are kept, the size is updated, and so is
+    /// the CPU copy if the image had one. Clones made before the reload
+    /// keep showing the old texture.
+    ///
+    /// The file is read like in [`new()`](#method.new): on desktop from
+    /// `physical_root_dir` first, so edits on disk are picked up, on web
+    /// only from the preloaded cache, which does not change.
+    ///
+    /// On error the image is left untouched.
+    pub fn reload<P: AsRef<path::Path>>(&mut self, ctx: &mut Context, path: P) -> GameResult {
+        let bytes = read_file(ctx, path)?;
+        let (width, height, pixels) = decode_png(&bytes)?;
+        let mut image = if self.cpu_pixels.is_some() {
+            Self::from_rgba8_with_cpu_copy(ctx, width, height, pixels)?
+        } else {
+            Self::from_rgba8(ctx, width, height, &pixels)?
+        };
 
+        if image.filter != self.filter {
+            image.set_filter(self.filter);
+        }
+        image.blend_mode = self.blend_mode;
+
+        // the old texture is freed when `image` is dropped, unless it is
+        // still used by a clone
+        std::mem::swap(self, &mut image);
+        Ok(())
+    }
+
+    /// Creates an `Image` from base64 encoded PNG data (or JPEG, with the `jpeg` feature),
+    /// optionally prefixed as a data URL, like `data:image/png;base64,iVBORw0...`.
+    ///
+    /// Handy to embed tiny sprites in the page or in JS instead of fetching them.
+    pub fn from_base64(ctx: &mut Context, data: &str) -> GameResult<Self> {
+        let data = if data.starts_with("data:") {
+            let start = data.find(";base64,").ok_or_else(|| {
+                GameError::ResourceLoadError("Data URL is not base64 encoded".to_string())
+            })?;
+            &data[start + ";base64,".len()..]
+        } else {
+            data
+        };
+
+        let bytes = decode_base64(data)?;
         Self::from_png_bytes(ctx, &bytes)
     }
 
     pub fn from_png_bytes(ctx: &mut Context, bytes: &[u8]) -> GameResult<Self> {
-        let img = image::load_from_memory(&bytes)
-            .unwrap_or_else(|e| panic!(e))
-            .to_rgba();
-        let width = img.width() as u16;
-        let height = img.height() as u16;
-        let bytes = img.into_raw();
+        let (width, height, bytes) = decode_png(bytes)?;
+
+        Image::from_rgba8(ctx, width, height, &bytes)
+    }
+
+    /// Rasterizes an SVG document to an image of `width` x `height` pixels,
+    /// scaled to fit while keeping its aspect ratio, for icons that stay crisp
+    /// at any size. Only with the `svg` feature.
+    ///
+    /// Fails if the document can't be parsed, or if it has elements that
+    /// would be silently left out: text, which needs fonts that aren't
+    /// loaded, so convert it to paths first, `foreignObject` and SVG fonts.
+    /// Unsupported attributes are still skipped rather than failing.
+    #[cfg(feature = "svg")]
+    pub fn from_svg(ctx: &mut Context, bytes: &[u8], width: u16, height: u16) -> GameResult<Self> {
+        if let Some(element) = unsupported_svg_element(&String::from_utf8_lossy(bytes)) {
+            return Err(GameError::ResourceLoadError(format!(
+                "Can't load SVG: <{}> elements aren't supported",
+                element
+            )));
+        }
+
+        let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())
+            .map_err(|e| GameError::ResourceLoadError(format!("Can't load SVG: {}", e)))?;
+
+        let mut pixmap =
+            tiny_skia::Pixmap::new(u32::from(width), u32::from(height)).ok_or_else(|| {
+                GameError::ResourceLoadError(format!(
+                    "Can't rasterize an SVG to {}x{} pixels",
+                    width, height
+                ))
+            })?;
+        resvg::render(
+            &tree,
+            usvg::FitTo::Size(u32::from(width), u32::from(height)),
+            pixmap.as_mut(),
+        )
+        .ok_or_else(|| GameError::ResourceLoadError("Can't rasterize the SVG".to_string()))?;
+
+        // tiny-skia pixels are premultiplied, images are not
+        let mut pixels = pixmap.take();
+        for pixel in pixels.chunks_exact_mut(4) {
+            let alpha = u16::from(pixel[3]);
+            if alpha != 0 && alpha != 255 {
+                for channel in &mut pixel[..3] {
+                    *channel = (u16::from(*channel) * 255 / alpha).min(255) as u8;
+                }
+            }
+        }
+
+        Image::from_rgba8(ctx, width, height, &pixels)
+    }
+
+    /// Like [`from_png_bytes()`](#method.from_png_bytes), but pixels of the `key`
+    /// color become fully transparent, for old assets that use a color key,
+    /// often magenta, instead of an alpha channel.
+    ///
+    /// A pixel matches if each of its RGB channels is within 4/255 of the
+    /// key, so slightly off keys from lossy conversions are caught too; its
+    /// alpha is ignored. Transparent pixels are set to transparent black,
+    /// linear filtering may darken the edges a little, nearest does not.
+    pub fn from_png_bytes_color_key(
+        ctx: &mut Context,
+        bytes: &[u8],
+        key: Color,
+    ) -> GameResult<Self> {
+        const TOLERANCE: i16 = 4;
+
+        let (width, height, mut bytes) = decode_png(bytes)?;
+        let (kr, kg, kb) = key.to_rgb();
+        let matches = |c: u8, k: u8| (i16::from(c) - i16::from(k)).abs() <= TOLERANCE;
+        for pixel in bytes.chunks_exact_mut(4) {
+            if matches(pixel[0], kr) && matches(pixel[1], kg) && matches(pixel[2], kb) {
+                pixel.copy_from_slice(&[0, 0, 0, 0]);
+            }
+        }
 
         Image::from_rgba8(ctx, width, height, &bytes)
     }
 
+    /// Creates an `Image` from raw RGBA pixels, row by row from the top-left corner.
+    ///
+    /// Fails if `bytes` is not `width * height * 4` long, or if the image is
+    /// larger than [`graphics::max_texture_size()`](fn.max_texture_size.html).
     pub fn from_rgba8(
         ctx: &mut Context,
         width: u16,
         height: u16,
         bytes: &[u8],
     ) -> GameResult<Image> {
+        let max_size = ctx.gfx_context.max_texture_size;
+        if u32::from(width) > max_size || u32::from(height) > max_size {
+            return Err(GameError::ResourceLoadError(format!(
+                "Image of {}x{} pixels is larger than the maximum texture size of this GPU, {}x{}",
+                width, height, max_size, max_size
+            )));
+        }
+        if bytes.len() != width as usize * height as usize * 4 {
+            return Err(GameError::ResourceLoadError(format!(
+                "Image of {}x{} pixels needs {} bytes of RGBA data, got {}",
+                width,
+                height,
+                width as usize * height as usize * 4,
+                bytes.len()
+            )));
+        }
+
         let texture = Texture::from_rgba8(&mut ctx.quad_ctx, width, height, bytes);
 
         Self::from_texture(&mut ctx.quad_ctx, texture)
     }
 
+    /// Creates an `Image` for each `(width, height, bytes)` of `images`, as
+    /// [`from_rgba8()`](#method.from_rgba8) does, for example on a loading screen.
+    ///
+    /// Every item gets its own result, in order, so one bad image doesn't
+    /// fail the others.
+    pub fn from_rgba8_batch(
+        ctx: &mut Context,
+        images: &[(u16, u16, &[u8])],
+    ) -> Vec<GameResult<Image>> {
+        images
+            .iter()
+            .map(|&(width, height, bytes)| Image::from_rgba8(ctx, width, height, bytes))
+            .collect()
+    }
+
+    /// Like [`from_rgba8()`](#method.from_rgba8), but also keeps `bytes` in memory,
+    /// see [`pixels()`](#method.pixels), and hashes them, see
+    /// [`content_hash()`](#method.content_hash).
+    pub fn from_rgba8_with_cpu_copy(
+        ctx: &mut Context,
+        width: u16,
+        height: u16,
+        bytes: Vec<u8>,
+    ) -> GameResult<Image> {
+        let mut image = Self::from_rgba8(ctx, width, height, &bytes)?;
+        image.content_hash = Some(hash_rgba8(width, height, &bytes));
+        image.cpu_pixels = Some(Arc::new(bytes));
+        Ok(image)
+    }
+
     pub fn from_texture(ctx: &mut miniquad::Context, texture: Texture) -> GameResult<Image> {
         #[rustfmt::skip]
         let vertices: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
@@ -99,6 +313,13 @@ impl Image {
             bindings,
             dirty_filter: Arc::new(AtomicBool::new(false)),
             filter: FilterMode::Linear,
+            blend_mode: None,
+            content_hash: None,
+            cpu_pixels: None,
+            mipmaps: false,
+            anisotropy: 1,
+            alpha_threshold: None,
+            canvas_pass: None,
             clones_hack: Arc::new(()),
         })
     }
@@ -124,6 +345,353 @@ impl Image {
     pub fn filter(&self) -> FilterMode {
         self.filter
     }
+
+    /// Sends a filter changed with `set_filter` to the texture.
+    pub(crate) fn apply_filter(&self, ctx: &mut Context) {
+        if self.dirty_filter.load(Ordering::Acquire) {
+            self.dirty_filter.store(false, Ordering::Release);
+            self.texture.set_filter(&mut ctx.quad_ctx, self.filter);
+            if self.mipmaps {
+                super::gl::set_mipmap_filter(&self.texture, self.filter);
+            }
+        }
+    }
+
+    /// Builds the mip chain of the texture, the image downscaled by halves,
+    /// so it stays smooth and cheap to sample when drawn much smaller than
+    /// its size, like a world map zoomed far out.
+    ///
+    /// From then on, images drawn smaller than their size use trilinear
+    /// filtering with `FilterMode::Linear`, and the nearest mip level with
+    /// `FilterMode::Nearest`. Drawing larger is not affected.
+    ///
+    /// Call it after the final pixels are uploaded: the mips are not
+    /// rebuilt when the texture changes, like in a `TextureAtlas` or a
+    /// `Canvas`, or replaced, as by [`reload()`](#method.reload).
+    ///
+    /// Fails on web: the JS GL bindings of miniquad can't build mipmaps.
+    pub fn generate_mipmaps(&mut self, ctx: &mut Context) -> GameResult {
+        self.apply_filter(ctx);
+        if !super::gl::generate_mipmaps(&self.texture) {
+            return Err(GameError::ResourceLoadError(
+                "Mipmaps can't be built on this platform".to_string(),
+            ));
+        }
+        super::gl::set_mipmap_filter(&self.texture, self.filter);
+        self.mipmaps = true;
+        Ok(())
+    }
+
+    /// Returns true once [`generate_mipmaps()`](#method.generate_mipmaps) was called.
+    pub fn has_mipmaps(&self) -> bool {
+        self.mipmaps
+    }
+
+    /// Sets the anisotropic filtering level, keeping the image sharp when
+    /// drawn squashed, like a ground plane seen at a grazing angle in a
+    /// pseudo-3D game. `1` turns it off, which is the default.
+    ///
+    /// Returns the level applied: `level` clamped to what the GPU supports,
+    /// `1` without the `EXT_texture_filter_anisotropic` extension. WebGL
+    /// has the extension too, but the JS bindings of miniquad do not enable
+    /// it, so it is always `1` on web.
+    ///
+    /// Works best with [mipmaps](#method.generate_mipmaps) and `FilterMode::Linear`.
+    pub fn set_anisotropy(&mut self, ctx: &mut Context, level: u8) -> u8 {
+        let level = level.max(1).min(ctx.gfx_context.max_anisotropy);
+        if level != self.anisotropy {
+            super::gl::set_anisotropy(&self.texture, level);
+            self.anisotropy = level;
+        }
+        level
+    }
+
+    /// The level applied by [`set_anisotropy()`](#method.set_anisotropy), `1` by default.
+    pub fn anisotropy(&self) -> u8 {
+        self.anisotropy
+    }
+
+    /// Discards the pixels whose alpha, after `DrawParam::color` is applied,
+    /// is below `threshold`, instead of blending them, for cutout sprites
+    /// like foliage: discarded pixels write no depth either, so they don't
+    /// hide what's drawn behind them later into a canvas with a depth buffer.
+    ///
+    /// Edges become hard, with no partial transparency. `None`, the default,
+    /// draws every pixel. Ignored when drawn with a custom shader or a color
+    /// matrix, and by sprite batches.
+    pub fn set_alpha_threshold(&mut self, threshold: Option<f32>) {
+        self.alpha_threshold = threshold;
+    }
+
+    pub fn alpha_threshold(&self) -> Option<f32> {
+        self.alpha_threshold
+    }
+
+    /// A hash of the image size and RGBA pixels, computed once at load time.
+    ///
+    /// Images with equal hashes almost certainly have the same content,
+    /// which is enough to find duplicate textures and share one of them.
+    ///
+    /// Hashing reads every pixel, 64 MB for a 4096x4096 image, so it is only
+    /// done by the `*_with_cpu_copy` constructors, that have the pixels at
+    /// hand anyway: `None` for other images.
+    pub fn content_hash(&self) -> Option<u64> {
+        self.content_hash
+    }
+
+    /// The RGBA pixels of the image, row by row from the top-left corner.
+    ///
+    /// Only available for images created with one of the `*_with_cpu_copy`
+    /// constructors, that keep `width * height * 4` bytes in memory for the
+    /// whole life of the image. Handy for per-pixel queries, like hit testing
+    /// a sprite, without reading anything back from the GPU.
+    pub fn pixels(&self) -> Option<&[u8]> {
+        self.cpu_pixels.as_ref().map(|pixels| &pixels[..])
+    }
+
+    /// The color of the pixel at the given coordinates, in pixels
+    /// from the top-left corner.
+    ///
+    /// `None` if the coordinates are out of bounds or the image has no
+    /// CPU copy, see [`pixels()`](#method.pixels).
+    pub fn pixel(&self, x: u16, y: u16) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels()
+            .map(|p| Color::from_rgba(p[i], p[i + 1], p[i + 2], p[i + 3]))
+    }
+
+    /// The alpha of the pixel at the given coordinates, in `[0.0, 1.0]`.
+    ///
+    /// `None` if the coordinates are out of bounds or the image has no
+    /// CPU copy, see [`pixels()`](#method.pixels).
+    pub fn alpha_at(&self, x: u16, y: u16) -> Option<f32> {
+        self.pixel(x, y).map(|color| color.a)
+    }
+
+    /// Copies `rect`, in pixels from the top-left corner, into a new image
+    /// with its own texture, like one frame of a sprite sheet for code that
+    /// takes a whole `Image`. The rect is clamped to the image bounds and
+    /// rounded out to whole pixels.
+    ///
+    /// Images with a CPU copy (see [`pixels()`](#method.pixels)) are cropped
+    /// on the CPU, and the new image gets a CPU copy too. Others are drawn
+    /// into a `Canvas` of the size of the rect, whose image is then the new
+    /// image, and keeps its texture alive without the canvas.
+    /// The filter and blend mode are kept.
+    ///
+    /// Fails if nothing of the image is left in `rect`.
+    pub fn crop(&self, ctx: &mut Context, rect: Rect) -> GameResult<Image> {
+        let (width, height) = (f32::from(self.width), f32::from(self.height));
+        let left = rect.left().floor().max(0.).min(width) as u16;
+        let top = rect.top().floor().max(0.).min(height) as u16;
+        let right = rect.right().ceil().max(0.).min(width) as u16;
+        let bottom = rect.bottom().ceil().max(0.).min(height) as u16;
+        if right <= left || bottom <= top {
+            return Err(GameError::RenderError(format!(
+                "Can't crop {:?} out of a {}x{} image",
+                rect, self.width, self.height
+            )));
+        }
+        let (w, h) = (right - left, bottom - top);
+
+        let mut image = match self.pixels() {
+            Some(pixels) => {
+                let row = |y: u16| {
+                    let start = (usize::from(y) * usize::from(self.width) + usize::from(left)) * 4;
+                    &pixels[start..start + usize::from(w) * 4]
+                };
+                let bytes = (top..bottom).flat_map(row).cloned().collect();
+                Self::from_rgba8_with_cpu_copy(ctx, w, h, bytes)?
+            }
+            None => {
+                self.crop_on_gpu(ctx, Rect::new(left.into(), top.into(), w.into(), h.into()))?
+            }
+        };
+
+        if image.filter != self.filter {
+            image.set_filter(self.filter);
+        }
+        image.blend_mode = self.blend_mode;
+        Ok(image)
+    }
+
+    fn crop_on_gpu(&self, ctx: &mut Context, rect: Rect) -> GameResult<Image> {
+        let canvas = Canvas::new(ctx, rect.w as u16, rect.h as u16, NumSamples::One)?;
+        let mut source = self.clone();
+        source.set_blend_mode(Some(BlendMode::Replace));
+        // canvases are drawn flipped back, drawing flipped into one gives
+        // the texture the orientation of images
+        let param = DrawParam::new()
+            .src(Rect::new(
+                rect.x / f32::from(self.width),
+                rect.y / f32::from(self.height),
+                rect.w / f32::from(self.width),
+                rect.h / f32::from(self.height),
+            ))
+            .flip_y(true);
+
+        let gfx = &mut ctx.gfx_context;
+        let previous = gfx.canvas.replace(canvas.clone());
+        let screen_rect = gfx.screen_rect;
+        let scissor = gfx.scissor.take();
+        let shader = gfx.shader.take();
+        gfx.set_screen_coordinates(Rect::new(0., 0., rect.w, rect.h));
+        gfx.push_transform(&cgmath::One::one());
+
+        let result = source.draw(ctx, param);
+
+        let gfx = &mut ctx.gfx_context;
+        gfx.pop_transform();
+        gfx.set_screen_coordinates(screen_rect);
+        gfx.shader = shader;
+        gfx.scissor = scissor;
+        gfx.canvas = previous;
+
+        // the clone owns the pass of the canvas, which is only deleted,
+        // along with the texture, once the last clone of the image is dropped
+        result.map(|()| canvas.image().clone())
+    }
+}
+
+/// Returns true if `point` hits a non-transparent pixel of `image`
+/// drawn with `param`.
+///
+/// `point` is in the same coordinates as `param.dest`; the inverse of the
+/// draw transform, including rotation, offset, scale, `src` and flips, is
+/// applied to find the texel under it. For pixel-perfect results the image needs
+/// a CPU copy (see [`Image::pixels()`](struct.Image.html#method.pixels)),
+/// otherwise it is treated as fully opaque and only its bounds are tested.
+///
+/// ```rust,ignore
+/// let clicked = self.sprites.iter().rev()
+///     .find(|sprite| graphics::point_in_image(&sprite.image, sprite.param, mouse_pos));
+/// ```
+pub fn point_in_image<P>(image: &Image, param: DrawParam, point: P) -> bool
+where
+    P: Into<mint::Point2<f32>>,
+{
+    use cgmath::SquareMatrix;
+
+    let point: mint::Point2<f32> = point.into();
+
+    let inverse = match quad_transform(&param, image.width, image.height).invert() {
+        Some(inverse) => inverse,
+        // zero scale, nothing is drawn
+        None => return false,
+    };
+
+    // position on the drawn quad, from 0 to 1 on both axes
+    let local = inverse * Vector4::new(point.x, point.y, 0., 1.);
+    if local.x < 0. || local.x >= 1. || local.y < 0. || local.y >= 1. {
+        return false;
+    }
+
+    if image.pixels().is_none() {
+        return true;
+    }
+
+    // flipped like the drawn texture
+    let src = source_rect(&param);
+    let u = src.x + local.x * src.z;
+    let v = src.y + local.y * src.w;
+    let x = (u * f32::from(image.width)).floor();
+    let y = (v * f32::from(image.height)).floor();
+    if x < 0. || y < 0. {
+        return false;
+    }
+
+    image
+        .alpha_at(x as u16, y as u16)
+        .map_or(false, |alpha| alpha > 0.)
+}
+
+fn read_file<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<Vec<u8>> {
+    use std::io::Read;
+
+    let mut file = filesystem::open(ctx, path)?;
+
+    let mut bytes = vec![];
+    file.bytes.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn decode_png(bytes: &[u8]) -> GameResult<(u16, u16, Vec<u8>)> {
+    let img = image::load_from_memory(&bytes)
+        .map_err(|e| GameError::ResourceLoadError(format!("Can't decode image: {}", e)))?
+        .to_rgba();
+    if img.width() > u32::from(std::u16::MAX) || img.height() > u32::from(std::u16::MAX) {
+        return Err(GameError::ResourceLoadError(format!(
+            "Image of {}x{} pixels is too large",
+            img.width(),
+            img.height()
+        )));
+    }
+    let width = img.width() as u16;
+    let height = img.height() as u16;
+
+    Ok((width, height, img.into_raw()))
+}
+
+/// Decodes standard base64, with or without padding, ignoring whitespace.
+fn decode_base64(data: &str) -> GameResult<Vec<u8>> {
+    fn sextet(c: u8) -> Option<u8> {
+        match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let data = data.trim_end_matches(|c: char| c == '=' || c.is_whitespace());
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for (n, c) in data.bytes().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let value = sextet(c).ok_or_else(|| {
+            GameError::ResourceLoadError(format!(
+                "Invalid base64 character {:?} at {}",
+                c as char, n
+            ))
+        })?;
+
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    // a single leftover character can't encode a whole byte
+    if bits >= 6 {
+        return Err(GameError::ResourceLoadError(
+            "Invalid base64 length".to_string(),
+        ));
+    }
+
+    Ok(bytes)
+}
+
+fn hash_rgba8(width: u16, height: u16, bytes: &[u8]) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u16(width);
+    hasher.write_u16(height);
+    hasher.write(bytes);
+    hasher.finish()
 }
 
 pub(crate) fn param_to_instance_transform(param: &DrawParam) -> Matrix4<f32> {
@@ -152,41 +720,141 @@ pub(crate) fn param_to_instance_transform(param: &DrawParam) -> Matrix4<f32> {
     )
 }
 
-impl Drawable for Image {
-    fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
-        let src_width = param.src.w;
-        let src_height = param.src.h;
+/// The model transform of the unit quad an image of the given size is drawn with.
+pub(crate) fn quad_transform(param: &DrawParam, width: u16, height: u16) -> Matrix4<f32> {
+    // We have to mess with the scale to make everything
+    // be its-unit-size-in-pixels.
+    let real_scale = Vector2::new(
+        param.scale.x * param.src.w * f32::from(width),
+        param.scale.y * param.src.h * f32::from(height),
+    );
 
-        // We have to mess with the scale to make everything
-        // be its-unit-size-in-pixels.
-        let real_scale = Vector2::new(
-            param.scale.x * src_width * f32::from(self.width),
-            param.scale.y * src_height * f32::from(self.height),
-        );
+    let mut new_param = *param;
+    new_param.scale = real_scale.into();
 
-        let mut new_param = param;
-        new_param.scale = real_scale.into();
+    param_to_instance_transform(&new_param)
+}
 
-        let transform = param_to_instance_transform(&new_param);
+/// The `Source` uniform an image is drawn with: `src` as `(x, y, w, h)`,
+/// with the flips of `param` applied by running it backwards.
+pub(crate) fn source_rect(param: &DrawParam) -> Vector4<f32> {
+    let src = param.src;
+    let (x, w) = if param.flip_x {
+        (src.x + src.w, -src.w)
+    } else {
+        (src.x, src.w)
+    };
+    let (y, h) = if param.flip_y {
+        (src.y + src.h, -src.h)
+    } else {
+        (src.y, src.h)
+    };
+    Vector4::new(x, y, w, h)
+}
 
-        if self.dirty_filter.load(Ordering::Acquire) {
-            self.dirty_filter.store(false, Ordering::Release);
-            self.texture.set_filter(&mut ctx.quad_ctx, self.filter);
+/// Moves the edges of the unit quad drawn with `mvp` to whole pixels of a
+/// `width` x `height` target, keeping them at least one pixel apart.
+/// `None` if the quad isn't axis aligned.
+fn snap_quad(mvp: Matrix4<f32>, width: f32, height: f32) -> Option<Matrix4<f32>> {
+    if mvp.x.y != 0. || mvp.y.x != 0. {
+        return None;
+    }
+
+    // the NDC `start` and `start + extent` of a quad side, snapped
+    let snap = |start: f32, extent: f32, size: f32| {
+        let to_pixels = |ndc: f32| (ndc + 1.) / 2. * size;
+        let (a, b) = (to_pixels(start), to_pixels(start + extent));
+        let (ra, mut rb) = (a.round(), b.round());
+        if ra == rb && a != b {
+            rb = ra + (b - a).signum();
         }
+        let to_ndc = |pixels: f32| pixels / size * 2. - 1.;
+        (to_ndc(ra), to_ndc(rb) - to_ndc(ra))
+    };
+
+    let mut snapped = mvp;
+    let (x, w) = snap(mvp.w.x, mvp.x.x, width);
+    let (y, h) = snap(mvp.w.y, mvp.y.y, height);
+    snapped.w.x = x;
+    snapped.x.x = w;
+    snapped.w.y = y;
+    snapped.y.y = h;
+    Some(snapped)
+}
 
-        let pass = ctx.framebuffer();
-        ctx.quad_ctx.begin_pass(pass, PassAction::Nothing);
-        ctx.quad_ctx.apply_pipeline(&ctx.gfx_context.image_pipeline);
-        ctx.quad_ctx.apply_bindings(&self.bindings);
+impl Drawable for Image {
+    fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
+        let mut transform = quad_transform(&param, self.width, self.height);
 
-        let uniforms = image_shader::Uniforms {
-            projection: ctx.gfx_context.projection,
-            model: transform,
-            source: Vector4::new(param.src.x, param.src.y, param.src.w, param.src.h),
-            color: Vector4::new(param.color.r, param.color.g, param.color.b, param.color.a),
+        self.apply_filter(ctx);
+
+        crate::graphics::begin_draw_pass(ctx, param.clip);
+        let shader = ctx.gfx_context.shader;
+        let kind = match (shader, param.color_matrix, self.alpha_threshold) {
+            (Some(shader), _, _) => PipelineKind::Custom(shader.id),
+            (None, Some(_), _) => PipelineKind::ColorMatrix,
+            (None, None, Some(_)) => PipelineKind::AlphaTest,
+            (None, None, None) => PipelineKind::Image,
         };
+        let pipeline = ctx
+            .gfx_context
+            .pipeline(&mut ctx.quad_ctx, kind, self.blend_mode);
+        ctx.quad_ctx.apply_pipeline(&pipeline);
+        ctx.quad_ctx.apply_bindings(&self.bindings);
 
-        ctx.quad_ctx.apply_uniforms(&uniforms);
+        let mut projection = ctx.gfx_context.transformed_projection();
+        if param.pixel_snap {
+            let (w, h) = crate::graphics::target_size(ctx);
+            if let Some(snapped) = snap_quad(projection * transform, w, h) {
+                projection = snapped;
+                transform = Matrix4::one();
+            }
+        }
+        let source = source_rect(&param);
+        let color = Vector4::new(param.color.r, param.color.g, param.color.b, param.color.a);
+
+        match (shader, param.color_matrix, self.alpha_threshold) {
+            (None, Some(matrix), _) => {
+                ctx.quad_ctx.apply_uniforms(&color_matrix_shader::Uniforms {
+                    projection,
+                    model: transform,
+                    source,
+                    color,
+                    color_add: param.color_add.into(),
+                    color_matrix: matrix.to_matrix4(),
+                    color_offset: matrix.offset.into(),
+                    depth: param.depth,
+                })
+            }
+            (None, None, Some(alpha_threshold)) => {
+                ctx.quad_ctx.apply_uniforms(&alpha_test_shader::Uniforms {
+                    projection,
+                    model: transform,
+                    source,
+                    color,
+                    color_add: param.color_add.into(),
+                    alpha_threshold,
+                    depth: param.depth,
+                })
+            }
+            (None, None, None) => ctx.quad_ctx.apply_uniforms(&image_shader::Uniforms {
+                projection,
+                model: transform,
+                source,
+                color,
+                color_add: param.color_add.into(),
+                depth: param.depth,
+            }),
+            (Some(shader), _, _) => ctx.quad_ctx.apply_uniforms(&custom_shader::Uniforms {
+                projection,
+                model: transform,
+                source,
+                color,
+                color_add: param.color_add.into(),
+                params: ctx.gfx_context.shader_params(shader.id),
+                depth: param.depth,
+            }),
+        }
         ctx.quad_ctx.draw(0, 6, 1);
 
         ctx.quad_ctx.end_render_pass();
@@ -194,22 +862,53 @@ impl Drawable for Image {
         Ok(())
     }
 
-    fn set_blend_mode(&mut self, _: Option<BlendMode>) {}
+    fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
+        self.blend_mode = mode;
+    }
 
     /// Gets the blend mode to be used when drawing this drawable.
     fn blend_mode(&self) -> Option<BlendMode> {
-        unimplemented!()
+        self.blend_mode
     }
 
-    fn dimensions(&self, _ctx: &mut Context) -> Option<Rect> {
+    fn aabb(&self) -> Option<Rect> {
         Some(self.dimensions())
     }
 }
 
+/// The first element of `svg` that `usvg`, built without text support,
+/// would drop without an error.
+#[cfg(feature = "svg")]
+fn unsupported_svg_element(svg: &str) -> Option<&'static str> {
+    const UNSUPPORTED: [&str; 3] = ["text", "foreignObject", "font"];
+
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if rest.starts_with("!--") {
+            // comments may mention anything
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        // without the namespace prefix, like `svg:text`
+        let name = rest[..name_end].rsplit(':').next().unwrap_or("");
+        if let Some(element) = UNSUPPORTED.iter().find(|&&element| element == name) {
+            return Some(element);
+        }
+    }
+    None
+}
+
 impl Drop for Image {
     fn drop(&mut self) {
         if Arc::strong_count(&self.clones_hack) == 1 {
-            self.texture.delete();
+            // a canvas texture goes with the pass, once `canvas_pass` is dropped
+            if self.canvas_pass.is_none() {
+                self.texture.delete();
+            }
             self.bindings.index_buffer.delete();
             self.bindings.vertex_buffers[0].delete();
         }
@@ -0,0 +1,292 @@
+use crate::{
+    graphics::{self, BlendMode, Canvas, Color, DrawParam, Drawable, Point2, Shader, Vector2},
+    Context, GameError, GameResult,
+};
+
+/// Identifies a layer of a [`LayerStack`](struct.LayerStack.html).
+/// Stays valid when other layers are added, removed or reordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
+#[derive(Clone, Debug)]
+struct Layer {
+    id: LayerId,
+    canvas: Canvas,
+    opacity: f32,
+    shader: Option<Shader>,
+    visible: bool,
+}
+
+/// An ordered stack of render layers, each a `Canvas` composited on the
+/// screen with its own blend mode, opacity and shader.
+///
+/// Layers are composited bottom to top, in the order they were added
+/// unless moved. The blend mode is the one of the layer canvas,
+/// `BlendMode::Premultiplied` unless changed with
+/// [`canvas_mut()`](#method.canvas_mut).
+///
+/// Once given to the context with
+/// [`set_layer_stack()`](fn.set_layer_stack.html), the stack is composited
+/// by [`present()`](fn.present.html), under what is
+/// [queued](fn.queue.html) and the debug shapes:
+///
+/// ```rust,ignore
+/// let mut layers = LayerStack::new();
+/// let world = layers.add(ctx)?;
+/// let lights = layers.add(ctx)?;
+/// layers.canvas_mut(lights).unwrap().set_blend_mode(Some(BlendMode::Add));
+/// graphics::set_layer_stack(ctx, Some(layers));
+///
+/// // every frame
+/// graphics::draw_to_layer(ctx, world, |ctx| {
+///     graphics::clear(ctx, Color::new(0., 0., 0., 0.));
+///     graphics::draw(ctx, &self.player, (self.position,))
+/// })?;
+/// graphics::present(ctx)?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LayerStack {
+    layers: Vec<Layer>,
+    next_id: usize,
+}
+
+impl LayerStack {
+    pub fn new() -> LayerStack {
+        LayerStack::default()
+    }
+
+    /// Adds a layer with a canvas of the window size on top of the others.
+    pub fn add(&mut self, ctx: &mut Context) -> GameResult<LayerId> {
+        let canvas = Canvas::with_window_size(ctx)?;
+        Ok(self.add_canvas(canvas))
+    }
+
+    /// Adds a layer drawing into `canvas` on top of the others.
+    pub fn add_canvas(&mut self, canvas: Canvas) -> LayerId {
+        let id = LayerId(self.next_id);
+        self.next_id += 1;
+        self.layers.push(Layer {
+            id,
+            canvas,
+            opacity: 1.0,
+            shader: None,
+            visible: true,
+        });
+        id
+    }
+
+    /// Removes a layer, returning its canvas.
+    pub fn remove(&mut self, id: LayerId) -> Option<Canvas> {
+        let index = self.index(id)?;
+        Some(self.layers.remove(index).canvas)
+    }
+
+    /// Moves a layer to `index` in the stack, `0` being the bottom.
+    /// Indices past the top move it to the top.
+    pub fn move_to(&mut self, id: LayerId, index: usize) {
+        if let Some(from) = self.index(id) {
+            let layer = self.layers.remove(from);
+            let index = index.min(self.layers.len());
+            self.layers.insert(index, layer);
+        }
+    }
+
+    /// Moves a layer to the top of the stack.
+    pub fn raise_to_top(&mut self, id: LayerId) {
+        self.move_to(id, std::usize::MAX);
+    }
+
+    /// Moves a layer to the bottom of the stack.
+    pub fn lower_to_bottom(&mut self, id: LayerId) {
+        self.move_to(id, 0);
+    }
+
+    /// The position of a layer in the stack, `0` being the bottom.
+    pub fn index(&self, id: LayerId) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.id == id)
+    }
+
+    /// All the layers, bottom to top.
+    pub fn ids(&self) -> Vec<LayerId> {
+        self.layers.iter().map(|layer| layer.id).collect()
+    }
+
+    /// Number of layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns true if the stack has no layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn canvas(&self, id: LayerId) -> Option<&Canvas> {
+        self.layer(id).map(|layer| &layer.canvas)
+    }
+
+    /// The canvas of a layer, to change its blend mode, filter or load action.
+    pub fn canvas_mut(&mut self, id: LayerId) -> Option<&mut Canvas> {
+        self.layer_mut(id).map(|layer| &mut layer.canvas)
+    }
+
+    /// Sets the opacity of a layer, from `0.0` to `1.0`, `1.0` by default.
+    pub fn set_opacity(&mut self, id: LayerId, opacity: f32) {
+        if let Some(layer) = self.layer_mut(id) {
+            layer.opacity = opacity.max(0.0).min(1.0);
+        }
+    }
+
+    pub fn opacity(&self, id: LayerId) -> Option<f32> {
+        self.layer(id).map(|layer| layer.opacity)
+    }
+
+    /// Sets the shader a layer is composited with,
+    /// `None`, the built-in one, by default.
+    pub fn set_shader(&mut self, id: LayerId, shader: Option<Shader>) {
+        if let Some(layer) = self.layer_mut(id) {
+            layer.shader = shader;
+        }
+    }
+
+    pub fn shader(&self, id: LayerId) -> Option<Shader> {
+        self.layer(id).and_then(|layer| layer.shader)
+    }
+
+    /// Hides or shows a layer when compositing, layers are visible by default.
+    pub fn set_visible(&mut self, id: LayerId, visible: bool) {
+        if let Some(layer) = self.layer_mut(id) {
+            layer.visible = visible;
+        }
+    }
+
+    pub fn is_visible(&self, id: LayerId) -> bool {
+        self.layer(id).map_or(false, |layer| layer.visible)
+    }
+
+    /// Calls `draw` with the canvas of a layer as the render target, then
+    /// restores the previous target.
+    ///
+    /// The canvas is not cleared first unless its load action says so, see
+    /// [`Canvas::set_load_action()`](struct.Canvas.html#method.set_load_action).
+    /// Fails if the layer doesn't exist.
+    pub fn draw_to<F, R>(&self, ctx: &mut Context, id: LayerId, draw: F) -> GameResult<R>
+    where
+        F: FnOnce(&mut Context) -> GameResult<R>,
+    {
+        let canvas = self.layer_canvas(id)?;
+        draw_to_canvas(ctx, &canvas, draw)
+    }
+
+    /// Draws the visible layers, bottom to top, over the current target,
+    /// each covering the screen coordinates whatever the current transform.
+    /// [`present()`](fn.present.html) calls it for the stack set with
+    /// [`set_layer_stack()`](fn.set_layer_stack.html); call it to composite
+    /// another stack, or on a canvas.
+    pub fn composite(&self, ctx: &mut Context) -> GameResult {
+        let shader = ctx.gfx_context.shader;
+        ctx.gfx_context.push_transform(&cgmath::One::one());
+
+        let screen = graphics::screen_coordinates(ctx);
+        let result = self
+            .layers
+            .iter()
+            .filter(|layer| layer.visible && layer.opacity > 0.0)
+            .try_for_each(|layer| {
+                let image = layer.canvas.image();
+                // premultiplied colors fade with all their channels
+                let color = match layer.canvas.blend_mode() {
+                    Some(BlendMode::Premultiplied) => {
+                        Color::new(layer.opacity, layer.opacity, layer.opacity, layer.opacity)
+                    }
+                    _ => Color::new(1., 1., 1., layer.opacity),
+                };
+                let param = DrawParam::new()
+                    .dest(Point2::new(screen.x, screen.y))
+                    .scale(Vector2::new(
+                        screen.w / f32::from(image.width()),
+                        screen.h / f32::from(image.height()),
+                    ))
+                    .color(color);
+
+                ctx.gfx_context.shader = layer.shader;
+                layer.canvas.draw(ctx, param)
+            });
+
+        ctx.gfx_context.pop_transform();
+        ctx.gfx_context.shader = shader;
+
+        result
+    }
+
+    /// A clone of the canvas of a layer, failing if the layer doesn't exist.
+    fn layer_canvas(&self, id: LayerId) -> GameResult<Canvas> {
+        self.canvas(id)
+            .cloned()
+            .ok_or_else(|| GameError::RenderError(format!("No layer {:?} in the stack", id)))
+    }
+
+    fn layer(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id == id)
+    }
+
+    fn layer_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id == id)
+    }
+}
+
+fn draw_to_canvas<F, R>(ctx: &mut Context, canvas: &Canvas, draw: F) -> GameResult<R>
+where
+    F: FnOnce(&mut Context) -> GameResult<R>,
+{
+    let previous = ctx.gfx_context.canvas.take();
+    graphics::set_canvas(ctx, Some(canvas));
+    let result = draw(ctx);
+    ctx.gfx_context.canvas = previous;
+
+    result
+}
+
+/// Gives `layers` to the context, to be composited on the screen by every
+/// [`present()`](fn.present.html), or takes the current stack back with
+/// `None`. There is none by default.
+pub fn set_layer_stack(ctx: &mut Context, layers: Option<LayerStack>) -> Option<LayerStack> {
+    std::mem::replace(&mut ctx.gfx_context.layer_stack, layers)
+}
+
+/// The stack set with [`set_layer_stack()`](fn.set_layer_stack.html).
+pub fn layer_stack(ctx: &Context) -> Option<&LayerStack> {
+    ctx.gfx_context.layer_stack.as_ref()
+}
+
+/// The stack set with [`set_layer_stack()`](fn.set_layer_stack.html), to
+/// add, reorder or change layers while the game runs.
+pub fn layer_stack_mut(ctx: &mut Context) -> Option<&mut LayerStack> {
+    ctx.gfx_context.layer_stack.as_mut()
+}
+
+/// [`LayerStack::draw_to()`](struct.LayerStack.html#method.draw_to) on the
+/// stack set with [`set_layer_stack()`](fn.set_layer_stack.html). Fails if
+/// there is no stack or the layer doesn't exist.
+pub fn draw_to_layer<F, R>(ctx: &mut Context, id: LayerId, draw: F) -> GameResult<R>
+where
+    F: FnOnce(&mut Context) -> GameResult<R>,
+{
+    let canvas = match &ctx.gfx_context.layer_stack {
+        Some(layers) => layers.layer_canvas(id)?,
+        None => return Err(GameError::RenderError("No layer stack set".to_string())),
+    };
+    draw_to_canvas(ctx, &canvas, draw)
+}
+
+/// Composites the stack set with `set_layer_stack`, for `present`.
+pub(crate) fn composite_layer_stack(ctx: &mut Context) -> GameResult {
+    // the stack can't be borrowed from the context while drawing with it
+    let layers = match ctx.gfx_context.layer_stack.take() {
+        Some(layers) => layers,
+        None => return Ok(()),
+    };
+    let result = layers.composite(ctx);
+    ctx.gfx_context.layer_stack = Some(layers);
+    result
+}
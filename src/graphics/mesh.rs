@@ -1,6 +1,9 @@
 #![allow(warnings)]
 use crate::{
-    graphics::{context::mesh_shader, *},
+    graphics::{
+        context::{mesh_shader, PipelineKind},
+        *,
+    },
     GameError,
 };
 use lyon;
@@ -74,8 +77,9 @@ pub struct Vertex {
 /// ```
 #[derive(Debug, Clone)]
 pub struct MeshBuilder {
-    buffer: t::geometry_builder::VertexBuffers<Vertex, u16>,
-    texture: Option<miniquad::Texture>,
+    /// `u32` indices, meshes over `u16::MAX` vertices are split when built.
+    buffer: t::geometry_builder::VertexBuffers<Vertex, u32>,
+    texture: Option<Image>,
 }
 
 impl Default for MeshBuilder {
@@ -319,7 +323,7 @@ impl MeshBuilder {
 
     /// Takes an `Image` to apply to the mesh.
     pub fn texture(&mut self, texture: Image) -> &mut Self {
-        self.texture = Some(texture.texture);
+        self.texture = Some(texture);
         self
     }
 
@@ -335,53 +339,108 @@ impl MeshBuilder {
     ///
     ///  * `indices` contains a value out of bounds of `verts`
     ///  * Adding the `indices` or `verts` would create a buffer too long
-    ///    to be indexed by a `u16`.
+    ///    to be indexed by a `u32`.
     pub fn from_raw<V>(&mut self, verts: &[V], indices: &[u16], texture: Option<Image>) -> &mut Self
     where
         V: Into<Vertex> + Clone,
     {
-        assert!(self.buffer.vertices.len() + verts.len() < (std::u16::MAX as usize));
-        assert!(self.buffer.indices.len() + indices.len() < (std::u16::MAX as usize));
-        let next_idx = self.buffer.vertices.len() as u16;
+        assert!(self.buffer.vertices.len() + verts.len() < (std::u32::MAX as usize));
+        assert!(self.buffer.indices.len() + indices.len() < (std::u32::MAX as usize));
+        let next_idx = self.buffer.vertices.len() as u32;
         // Can we remove the clone here?
         // I can't find a way to, because `into()` consumes its source and
         // `Borrow` or `AsRef` aren't really right.
         let vertices = verts.iter().cloned().map(|v: V| -> Vertex { v.into() });
-        let indices = indices.iter().map(|i| (*i) + next_idx);
+        let indices = indices.iter().map(|i| u32::from(*i) + next_idx);
+        self.buffer.vertices.extend(vertices);
+        self.buffer.indices.extend(indices);
+        self.texture = texture;
+
+        self
+    }
+
+    /// Adds a textured polygon from raw vertices and indices, sampling `image`
+    /// at each vertex `uv`, where `[0, 0]` is the top-left corner of the image
+    /// and `[1, 1]` the bottom-right one. The vertex colors tint the texture.
+    ///
+    /// Same as [`from_raw()`](#method.from_raw) with `Some(image)`. A mesh has
+    /// a single texture, so it also applies to every other shape of the builder.
+    ///
+    /// ```rust,ignore
+    /// let quad = [
+    ///     Vertex { pos: [0., 0.], uv: [0., 0.], color: [1.; 4] },
+    ///     Vertex { pos: [64., 0.], uv: [1., 0.], color: [1.; 4] },
+    ///     Vertex { pos: [64., 64.], uv: [1., 1.], color: [1.; 4] },
+    ///     Vertex { pos: [0., 64.], uv: [0., 1.], color: [1.; 4] },
+    /// ];
+    /// let mesh = MeshBuilder::new()
+    ///     .raw_textured(&quad, &[0, 1, 2, 0, 2, 3], image)
+    ///     .build(ctx)?;
+    /// ```
+    pub fn raw_textured<V>(&mut self, verts: &[V], indices: &[u16], image: Image) -> &mut Self
+    where
+        V: Into<Vertex> + Clone,
+    {
+        self.from_raw(verts, indices, Some(image))
+    }
+
+    /// Adds a polygon from raw vertices and indices, each vertex with its own
+    /// RGBA color, `[r, g, b, a]`, blended across the triangles.
+    ///
+    /// The colors are multiplied by `DrawParam::color` when drawn and the alpha
+    /// blends with the mesh blend mode, `BlendMode::Alpha` by default, so a
+    /// vertex alpha of `0.0` fades to transparent. Unlike
+    /// [`from_raw()`](#method.from_raw), the texture of the builder is kept.
+    ///
+    /// ```rust,ignore
+    /// // a fog disc, opaque in the middle and fading out at the edge
+    /// let mut verts = vec![([0., 0.], [0., 0., 0., 0.8])];
+    /// let mut indices = vec![];
+    /// for i in 0..32u16 {
+    ///     let angle = i as f32 / 32. * std::f32::consts::PI * 2.;
+    ///     verts.push(([angle.cos() * 100., angle.sin() * 100.], [0., 0., 0., 0.]));
+    ///     indices.extend_from_slice(&[0, i + 1, (i + 1) % 32 + 1]);
+    /// }
+    /// let fog = MeshBuilder::new().raw_colored(&verts, &indices).build(ctx)?;
+    /// ```
+    pub fn raw_colored<P>(&mut self, verts: &[(P, [f32; 4])], indices: &[u16]) -> &mut Self
+    where
+        P: Into<mint::Point2<f32>> + Clone,
+    {
+        assert!(self.buffer.vertices.len() + verts.len() < (std::u32::MAX as usize));
+        assert!(self.buffer.indices.len() + indices.len() < (std::u32::MAX as usize));
+        let next_idx = self.buffer.vertices.len() as u32;
+        let vertices = verts.iter().cloned().map(|(p, color)| {
+            let p: mint::Point2<f32> = p.into();
+            Vertex {
+                pos: [p.x, p.y],
+                uv: [p.x, p.y],
+                color,
+            }
+        });
+        let indices = indices.iter().map(|i| u32::from(*i) + next_idx);
         self.buffer.vertices.extend(vertices);
         self.buffer.indices.extend(indices);
-        self.texture = texture.map(|texture| texture.texture);
 
         self
     }
 
     /// Takes the accumulated geometry and load it into GPU memory,
     /// creating a single `Mesh`.
+    ///
+    /// Geometry of more than `u16::MAX` vertices is split into several
+    /// parts, drawn one after the other, see
+    /// [`Mesh::from_raw_u32()`](struct.Mesh.html#method.from_raw_u32).
+    /// Fails if the builder holds no triangles.
     pub fn build(&self, ctx: &mut Context) -> GameResult<Mesh> {
-        let vertex_buffer = miniquad::Buffer::immutable(
-            &mut ctx.quad_ctx,
-            miniquad::BufferType::VertexBuffer,
-            &self.buffer.vertices[..],
-        );
-        let index_buffer = miniquad::Buffer::immutable(
-            &mut ctx.quad_ctx,
-            miniquad::BufferType::IndexBuffer,
-            &self.buffer.indices[..],
-        );
-        let bindings = miniquad::Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer: index_buffer,
-            images: self
-                .texture
-                .map_or(vec![ctx.gfx_context.white_texture], |texture| vec![texture]),
-        };
-        let rect = bbox_for_vertices(&self.buffer.vertices).expect("No vertices in MeshBuilder");
+        let vertices = &self.buffer.vertices[..];
+        if vertices.len() > std::u16::MAX as usize {
+            return Mesh::from_raw_u32(ctx, vertices, &self.buffer.indices, self.texture.clone());
+        }
 
-        Ok(Mesh {
-            bindings: bindings,
-            blend_mode: None,
-            rect,
-        })
+        // every index is below the number of vertices, so it fits
+        let indices: Vec<u16> = self.buffer.indices.iter().map(|&i| i as u16).collect();
+        Mesh::from_raw(ctx, vertices, &indices, self.texture.clone())
     }
 }
 
@@ -417,14 +476,23 @@ impl t::VertexConstructor<t::StrokeVertex, Vertex> for VertexBuilder {
 #[derive(Debug)]
 pub struct Mesh {
     bindings: miniquad::Bindings,
+    /// More geometry drawn after `bindings`, for meshes with more vertices
+    /// than a `u16` index buffer reaches, see `from_raw_u32`.
+    extra_parts: Vec<miniquad::Bindings>,
+    /// Keeps the texture in `bindings` alive, `None` for the white texture.
+    texture: Option<Image>,
     blend_mode: Option<BlendMode>,
+    cull_mode: CullMode,
+    front_face: FrontFace,
     rect: Rect,
 }
 
 impl Drop for Mesh {
     fn drop(&mut self) {
-        self.bindings.vertex_buffers[0].delete();
-        self.bindings.index_buffer.delete();
+        for bindings in std::iter::once(&self.bindings).chain(&self.extra_parts) {
+            bindings.vertex_buffers[0].delete();
+            bindings.index_buffer.delete();
+        }
     }
 }
 
@@ -536,26 +604,28 @@ impl Mesh {
     /// supply an `Image` to use as a texture, if you pass `None`, it will
     /// just use a pure white texture.
     ///
+    /// `Vertex` matches the mesh pipeline attributes exactly, so geometry
+    /// tesselated by another library can be uploaded as is.
+    ///
     /// This is the most primitive mesh-creation method, but allows you full
     /// control over the tesselation and texturing.
     /// As such it will panic or produce incorrect/invalid output (that may later
     /// cause drawing to panic), if:
     ///
-    ///  * `indices` contains a value out of bounds of `verts`
     ///  * `verts` is longer than `u16::MAX` elements.
     pub fn from_raw<V>(
         ctx: &mut Context,
         verts: &[V],
         indices: &[u16],
-        texture: Option<miniquad::Texture>,
+        texture: Option<Image>,
     ) -> GameResult<Mesh>
     where
         V: Into<Vertex> + Clone,
     {
         // Sanity checks to return early with helpful error messages.
-        if verts.len() > (std::u32::MAX as usize) {
+        if verts.len() > (std::u16::MAX as usize) {
             let msg = format!(
-                "Tried to build a mesh with {} vertices, max is u32::MAX",
+                "Tried to build a mesh with {} vertices, max is u16::MAX",
                 verts.len()
             );
             return Err(GameError::LyonError(msg));
@@ -567,6 +637,14 @@ impl Mesh {
             );
             return Err(GameError::LyonError(msg));
         }
+        if let Some(index) = indices.iter().find(|i| **i as usize >= verts.len()) {
+            let msg = format!(
+                "Tried to build a mesh with index {} out of bounds of {} vertices",
+                index,
+                verts.len()
+            );
+            return Err(GameError::LyonError(msg));
+        }
         if verts.len() < 3 {
             let msg = format!("Trying to build mesh with < 3 vertices, this is usually due to invalid input to a `Mesh` or MeshBuilder`.");
             return Err(GameError::LyonError(msg));
@@ -581,6 +659,7 @@ impl Mesh {
             return Err(GameError::LyonError(msg));
         }
 
+        let verts: Vec<Vertex> = verts.iter().cloned().map(Into::into).collect();
         let vertex_buffer = miniquad::Buffer::immutable(
             &mut ctx.quad_ctx,
             miniquad::BufferType::VertexBuffer,
@@ -595,19 +674,110 @@ impl Mesh {
         let bindings = miniquad::Bindings {
             vertex_buffers: vec![vertex_buffer],
             index_buffer: index_buffer,
-            images: texture.map_or(vec![ctx.gfx_context.white_texture], |texture| vec![texture]),
+            images: texture
+                .as_ref()
+                .map_or(vec![ctx.gfx_context.white_texture], |image| {
+                    vec![image.texture]
+                }),
         };
 
-        let verts: Vec<Vertex> = verts.iter().cloned().map(Into::into).collect();
         let rect = bbox_for_vertices(&verts).expect("No vertices in MeshBuilder");
 
         Ok(Mesh {
             bindings,
+            extra_parts: vec![],
+            texture,
             blend_mode: None,
+            cull_mode: CullMode::default(),
+            front_face: FrontFace::default(),
             rect,
         })
     }
 
+    /// Like [`from_raw()`](#method.from_raw), with `u32` indices, for meshes
+    /// of more than `u16::MAX` vertices like a big tessellated terrain.
+    ///
+    /// miniquad only draws with `u16` index buffers, and WebGL1 would need the
+    /// `OES_element_index_uint` extension for `u32` ones anyway, so bigger
+    /// meshes are split into parts of up to `u16::MAX` vertices, drawn one
+    /// after the other: one draw call per part. Vertices used by triangles
+    /// of several parts are duplicated in each of them.
+    ///
+    /// Fails on out of bounds indices, or if `indices` isn't a list of triangles.
+    pub fn from_raw_u32<V>(
+        ctx: &mut Context,
+        verts: &[V],
+        indices: &[u32],
+        texture: Option<Image>,
+    ) -> GameResult<Mesh>
+    where
+        V: Into<Vertex> + Clone,
+    {
+        if let Some(index) = indices.iter().find(|i| **i as usize >= verts.len()) {
+            let msg = format!(
+                "Tried to build a mesh with index {} out of bounds of {} vertices",
+                index,
+                verts.len()
+            );
+            return Err(GameError::LyonError(msg));
+        }
+        if indices.len() < 3 || indices.len() % 3 != 0 {
+            let msg = format!(
+                "Trying to build mesh with {} indices, which is not a list of triangles",
+                indices.len()
+            );
+            return Err(GameError::LyonError(msg));
+        }
+
+        let verts: Vec<Vertex> = verts.iter().cloned().map(Into::into).collect();
+        let mut parts = split_into_u16_parts(&verts, indices).into_iter();
+        let (first_verts, first_indices) = parts
+            .next()
+            .ok_or_else(|| GameError::LyonError("No triangles in the mesh".to_string()))?;
+        let mut mesh = Mesh::from_raw(ctx, &first_verts, &first_indices, texture)?;
+        for (part_verts, part_indices) in parts {
+            let vertex_buffer = miniquad::Buffer::immutable(
+                &mut ctx.quad_ctx,
+                miniquad::BufferType::VertexBuffer,
+                &part_verts[..],
+            );
+            let index_buffer = miniquad::Buffer::immutable(
+                &mut ctx.quad_ctx,
+                miniquad::BufferType::IndexBuffer,
+                &part_indices[..],
+            );
+            mesh.extra_parts.push(miniquad::Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: mesh.bindings.images.clone(),
+            });
+        }
+        mesh.rect = bbox_for_vertices(&verts).expect("No vertices in the mesh");
+
+        Ok(mesh)
+    }
+
+    /// Sets which triangles are skipped when drawing, `CullMode::None` by default.
+    ///
+    /// Only useful with geometry of consistent winding, like meshes from
+    /// external tools; see also [`set_front_face()`](#method.set_front_face).
+    pub fn set_cull_mode(&mut self, cull_mode: CullMode) {
+        self.cull_mode = cull_mode;
+    }
+
+    pub fn cull_mode(&self) -> CullMode {
+        self.cull_mode
+    }
+
+    /// Sets the winding of front-facing triangles, `FrontFace::CounterClockwise` by default.
+    pub fn set_front_face(&mut self, front_face: FrontFace) {
+        self.front_face = front_face;
+    }
+
+    pub fn front_face(&self) -> FrontFace {
+        self.front_face
+    }
+
     /// Replaces the vertices in the `Mesh` with the given ones.  This MAY be faster
     /// than re-creating a `Mesh` with [`Mesh::from_raw()`](#method.from_raw) due to
     /// reusing memory instead of allocating and deallocating it, both on the CPU and
@@ -636,32 +806,76 @@ impl Mesh {
     }
 }
 
+/// Splits triangles indexed with `u32` into parts of at most `u16::MAX`
+/// vertices each, indexed with `u16`.
+fn split_into_u16_parts(verts: &[Vertex], indices: &[u32]) -> Vec<(Vec<Vertex>, Vec<u16>)> {
+    const MAX_VERTICES: usize = std::u16::MAX as usize;
+
+    let mut parts = vec![];
+    let mut part_verts: Vec<Vertex> = vec![];
+    let mut part_indices: Vec<u16> = vec![];
+    // index in `verts` to index in `part_verts`
+    let mut remap: std::collections::HashMap<u32, u16> = std::collections::HashMap::new();
+
+    for triangle in indices.chunks(3) {
+        let new_vertices = triangle
+            .iter()
+            .filter(|index| !remap.contains_key(index))
+            .count();
+        if part_verts.len() + new_vertices > MAX_VERTICES {
+            parts.push((
+                std::mem::replace(&mut part_verts, vec![]),
+                std::mem::replace(&mut part_indices, vec![]),
+            ));
+            remap.clear();
+        }
+        for &index in triangle {
+            let local = *remap.entry(index).or_insert_with(|| {
+                part_verts.push(verts[index as usize].clone());
+                (part_verts.len() - 1) as u16
+            });
+            part_indices.push(local);
+        }
+    }
+    if !part_indices.is_empty() {
+        parts.push((part_verts, part_indices));
+    }
+    parts
+}
+
 impl Drawable for Mesh {
     fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
         let transform = param_to_instance_transform(&param);
 
-        let pass = ctx.framebuffer();
-
-        ctx.quad_ctx.begin_pass(pass, PassAction::Nothing);
-        ctx.quad_ctx.apply_pipeline(&ctx.gfx_context.mesh_pipeline);
-        ctx.quad_ctx.apply_bindings(&self.bindings);
+        begin_draw_pass(ctx, param.clip);
+        let mut key = ctx
+            .gfx_context
+            .pipeline_key(PipelineKind::Mesh, self.blend_mode);
+        key.cull_mode = self.cull_mode;
+        key.front_face = self.front_face;
+        let pipeline = ctx.gfx_context.pipeline_for_key(&mut ctx.quad_ctx, key);
+        ctx.quad_ctx.apply_pipeline(&pipeline);
 
         let uniforms = mesh_shader::Uniforms {
-            projection: ctx.gfx_context.projection,
+            projection: ctx.gfx_context.transformed_projection(),
             model: transform,
             color: Vector4::new(param.color.r, param.color.g, param.color.b, param.color.a),
+            color_add: param.color_add.into(),
+            depth: param.depth,
         };
 
-        ctx.quad_ctx.apply_uniforms(&uniforms);
-
-        ctx.quad_ctx
-            .draw(0, self.bindings.index_buffer.size() as i32 / 2, 1);
+        for bindings in std::iter::once(&self.bindings).chain(&self.extra_parts) {
+            ctx.quad_ctx.apply_bindings(bindings);
+            ctx.quad_ctx.apply_uniforms(&uniforms);
+            ctx.quad_ctx
+                .draw(0, bindings.index_buffer.size() as i32 / 2, 1);
+        }
 
         ctx.quad_ctx.end_render_pass();
 
         Ok(())
     }
-    fn dimensions(&self, _ctx: &mut Context) -> Option<Rect> {
+    fn aabb(&self) -> Option<Rect> {
         Some(self.rect)
     }
     fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
@@ -672,6 +886,127 @@ impl Drawable for Mesh {
     }
 }
 
+/// Draws several meshes in as few draw calls as possible, by concatenating
+/// their geometry into one vertex and one index buffer.
+///
+/// Meant for geometry that changes every frame, like a deforming rope or a
+/// fluid surface: build a `MeshBuilder` per piece every frame and draw them
+/// all at once, without creating a `Mesh` for each. The buffers are shared
+/// by all calls and only reallocated to grow.
+///
+/// Each mesh is placed with its own `DrawParam`, of which only `dest`,
+/// `rotation`, `scale`, `offset` and `color` are used; the vertices are
+/// transformed on the CPU. `param` then applies to the whole batch, like
+/// the `DrawParam` of a single mesh.
+///
+/// All the meshes must have the same texture, or none; meshes without
+/// indices are skipped. One draw call holds up to 65536 vertices, bigger
+/// batches take one more for every such chunk.
+pub fn draw_batched_meshes<D>(
+    ctx: &mut Context,
+    meshes: &[(&MeshBuilder, DrawParam)],
+    param: D,
+) -> GameResult
+where
+    D: Into<DrawParam>,
+{
+    let param: DrawParam = param.into();
+    let texture = meshes.first().and_then(|(mesh, _)| mesh.texture.as_ref());
+    let texture_id = |texture: Option<&Image>| texture.map(|image| image.texture.texture);
+    if meshes
+        .iter()
+        .any(|(mesh, _)| texture_id(mesh.texture.as_ref()) != texture_id(texture))
+    {
+        return Err(GameError::RenderError(
+            "draw_batched_meshes needs meshes with the same texture".to_string(),
+        ));
+    }
+    if let Some(texture) = texture {
+        texture.apply_filter(ctx);
+    }
+
+    let mut vertices: Vec<Vertex> = vec![];
+    let mut indices: Vec<u16> = vec![];
+    for (mesh, mesh_param) in meshes {
+        let buffer = &mesh.buffer;
+        if buffer.indices.is_empty() {
+            continue;
+        }
+        if buffer.vertices.len() > std::u16::MAX as usize + 1 {
+            return Err(GameError::RenderError(format!(
+                "Tried to batch a mesh with {} vertices, max is 65536",
+                buffer.vertices.len()
+            )));
+        }
+        if vertices.len() + buffer.vertices.len() > std::u16::MAX as usize + 1 {
+            draw_mesh_chunk(ctx, texture, &vertices, &indices, param);
+            vertices.clear();
+            indices.clear();
+        }
+
+        let transform = param_to_instance_transform(mesh_param);
+        let color = mesh_param.color;
+        let base = vertices.len() as u32;
+        indices.extend(buffer.indices.iter().map(|index| (base + index) as u16));
+        vertices.extend(buffer.vertices.iter().map(|vertex| {
+            let pos = transform * Vector4::new(vertex.pos[0], vertex.pos[1], 0., 1.);
+            Vertex {
+                pos: [pos.x, pos.y],
+                uv: vertex.uv,
+                color: [
+                    vertex.color[0] * color.r,
+                    vertex.color[1] * color.g,
+                    vertex.color[2] * color.b,
+                    vertex.color[3] * color.a,
+                ],
+            }
+        }));
+    }
+    if !indices.is_empty() {
+        draw_mesh_chunk(ctx, texture, &vertices, &indices, param);
+    }
+
+    Ok(())
+}
+
+fn draw_mesh_chunk(
+    ctx: &mut Context,
+    texture: Option<&Image>,
+    vertices: &[Vertex],
+    indices: &[u16],
+    param: DrawParam,
+) {
+    let (vertex_buffer, index_buffer) = ctx.gfx_context.mesh_batch_buffers(
+        &mut ctx.quad_ctx,
+        std::mem::size_of_val(vertices),
+        std::mem::size_of_val(indices),
+    );
+    vertex_buffer.update(&mut ctx.quad_ctx, vertices);
+    index_buffer.update(&mut ctx.quad_ctx, indices);
+
+    let bindings = miniquad::Bindings {
+        vertex_buffers: vec![vertex_buffer],
+        index_buffer,
+        images: vec![texture.map_or(ctx.gfx_context.white_texture, |image| image.texture)],
+    };
+
+    begin_draw_pass(ctx, param.clip);
+    let pipeline = ctx
+        .gfx_context
+        .pipeline(&mut ctx.quad_ctx, PipelineKind::Mesh, None);
+    ctx.quad_ctx.apply_pipeline(&pipeline);
+    ctx.quad_ctx.apply_bindings(&bindings);
+    ctx.quad_ctx.apply_uniforms(&mesh_shader::Uniforms {
+        projection: ctx.gfx_context.transformed_projection(),
+        model: param_to_instance_transform(&param),
+        color: Vector4::new(param.color.r, param.color.g, param.color.b, param.color.a),
+        color_add: param.color_add.into(),
+        depth: param.depth,
+    });
+    ctx.quad_ctx.draw(0, indices.len() as i32, 1);
+    ctx.quad_ctx.end_render_pass();
+}
+
 fn bbox_for_vertices(verts: &[Vertex]) -> Option<Rect> {
     if verts.is_empty() {
         return None;
@@ -0,0 +1,112 @@
+use cgmath::{Matrix4, Vector4};
+
+use crate::{
+    graphics::{draw_instanced, Color, Image, InstanceAttributes, Rect},
+    Context, GameResult,
+};
+
+/// Widths of the borders of a nine-slice image, in pixels of the image,
+/// see [`draw_nine_slice()`](fn.draw_nine_slice.html).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BorderInsets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl BorderInsets {
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> BorderInsets {
+        BorderInsets {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// The same width on all four sides.
+    pub fn uniform(width: f32) -> BorderInsets {
+        BorderInsets::new(width, width, width, width)
+    }
+}
+
+/// Shrinks two opposite borders by the same factor so they fit in `size`,
+/// and makes them non-negative.
+fn fit_borders(first: f32, second: f32, size: f32) -> (f32, f32) {
+    let (first, second) = (first.max(0.), second.max(0.));
+    let total = first + second;
+    if total > size && total > 0. {
+        let scale = size.max(0.) / total;
+        (first * scale, second * scale)
+    } else {
+        (first, second)
+    }
+}
+
+/// Draws `image` stretched over `dest` as a nine-slice, or nine-patch:
+/// the corners keep their size, the edges only stretch along their length
+/// and the center stretches both ways. Handy for resizable UI panels.
+///
+/// `borders` are in pixels of the image, and drawn at that size. Borders
+/// wider than the image are shrunk to fit it, and if `dest` is smaller
+/// than the borders they are shrunk to fit it too, leaving no center.
+///
+/// Everything is drawn in a single draw call, with the image blend mode
+/// and the current transform, like [`draw_instanced()`](fn.draw_instanced.html).
+pub fn draw_nine_slice(
+    ctx: &mut Context,
+    image: &Image,
+    borders: BorderInsets,
+    dest: Rect,
+    color: Color,
+) -> GameResult {
+    let (width, height) = (f32::from(image.width()), f32::from(image.height()));
+    let (left, right) = fit_borders(borders.left, borders.right, width);
+    let (top, bottom) = fit_borders(borders.top, borders.bottom, height);
+    let (dest_left, dest_right) = fit_borders(left, right, dest.w);
+    let (dest_top, dest_bottom) = fit_borders(top, bottom, dest.h);
+
+    // column and row edges, in the image then in `dest`
+    let src_x = [0., left, width - right, width];
+    let src_y = [0., top, height - bottom, height];
+    let dest_x = [
+        dest.x,
+        dest.x + dest_left,
+        dest.x + dest.w - dest_right,
+        dest.x + dest.w,
+    ];
+    let dest_y = [
+        dest.y,
+        dest.y + dest_top,
+        dest.y + dest.h - dest_bottom,
+        dest.y + dest.h,
+    ];
+
+    let mut instances = Vec::with_capacity(9);
+    for row in 0..3 {
+        for col in 0..3 {
+            let (w, h) = (dest_x[col + 1] - dest_x[col], dest_y[row + 1] - dest_y[row]);
+            if w <= 0. || h <= 0. {
+                continue;
+            }
+
+            instances.push(InstanceAttributes {
+                source: Vector4::new(
+                    src_x[col] / width,
+                    src_y[row] / height,
+                    (src_x[col + 1] - src_x[col]) / width,
+                    (src_y[row + 1] - src_y[row]) / height,
+                ),
+                color: color.into(),
+                model: Matrix4::from_translation(cgmath::Vector3::new(
+                    dest_x[col],
+                    dest_y[row],
+                    0.,
+                )) * Matrix4::from_nonuniform_scale(w, h, 1.),
+            });
+        }
+    }
+
+    draw_instanced(ctx, image, &instances)
+}
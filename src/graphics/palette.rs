@@ -0,0 +1,157 @@
+use crate::{
+    error::GameResult,
+    graphics::{
+        context::{image_shader, PipelineKind},
+        image::{quad_transform, source_rect},
+        BlendMode, Color, DrawParam, Drawable, FilterMode, Image, Rect,
+    },
+    Context, GameError,
+};
+
+use miniquad::Bindings;
+
+/// Number of colors in a palette.
+const PALETTE_SIZE: usize = 256;
+
+/// An image of palette indices, drawn by looking each pixel up in a
+/// 256 colors palette.
+///
+/// The palette can be swapped at any time, which recolors the whole image
+/// at once: team colors, day/night cycles, classic palette cycling effects.
+/// Both textures use nearest filtering, as blending indices makes no sense.
+///
+/// ```rust,ignore
+/// let mut sprite = PalettedImage::new(ctx, 16, 16, &indices, &day_palette)?;
+/// // at dusk
+/// sprite.set_palette(ctx, &night_palette)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct PalettedImage {
+    indices: Image,
+    palette: Image,
+    bindings: Bindings,
+    blend_mode: Option<BlendMode>,
+}
+
+impl PalettedImage {
+    /// Creates a paletted image from one palette index per pixel,
+    /// row by row from the top-left corner.
+    ///
+    /// `palette` may hold up to 256 colors; missing entries are transparent.
+    pub fn new(
+        ctx: &mut Context,
+        width: u16,
+        height: u16,
+        indices: &[u8],
+        palette: &[Color],
+    ) -> GameResult<PalettedImage> {
+        if indices.len() != width as usize * height as usize {
+            return Err(GameError::UnknownError(
+                "PalettedImage needs exactly one index per pixel",
+            ));
+        }
+
+        // the index goes to the red channel, that the shader reads
+        let mut bytes = Vec::with_capacity(indices.len() * 4);
+        for index in indices {
+            bytes.extend_from_slice(&[*index, 0, 0, 255]);
+        }
+        let mut indices = Image::from_rgba8(ctx, width, height, &bytes)?;
+        let mut palette = Image::from_rgba8(ctx, PALETTE_SIZE as u16, 1, &palette_bytes(palette)?)?;
+
+        for image in &mut [&mut indices, &mut palette] {
+            image.set_filter(FilterMode::Nearest);
+            image.apply_filter(ctx);
+        }
+
+        let bindings = Bindings {
+            vertex_buffers: indices.bindings.vertex_buffers.clone(),
+            index_buffer: indices.bindings.index_buffer,
+            images: vec![indices.texture, palette.texture],
+        };
+
+        Ok(PalettedImage {
+            indices,
+            palette,
+            bindings,
+            blend_mode: None,
+        })
+    }
+
+    /// Replaces the palette, up to 256 colors; missing entries are transparent.
+    pub fn set_palette(&mut self, ctx: &mut Context, palette: &[Color]) -> GameResult {
+        self.palette
+            .texture
+            .update(&mut ctx.quad_ctx, &palette_bytes(palette)?);
+        Ok(())
+    }
+
+    pub fn width(&self) -> u16 {
+        self.indices.width()
+    }
+
+    pub fn height(&self) -> u16 {
+        self.indices.height()
+    }
+
+    /// Returns the dimensions of the image.
+    pub fn dimensions(&self) -> Rect {
+        self.indices.dimensions()
+    }
+}
+
+fn palette_bytes(palette: &[Color]) -> GameResult<Vec<u8>> {
+    if palette.len() > PALETTE_SIZE {
+        return Err(GameError::UnknownError(
+            "A palette can't have more than 256 colors",
+        ));
+    }
+
+    let mut bytes = Vec::with_capacity(PALETTE_SIZE * 4);
+    for color in palette {
+        let (r, g, b, a) = color.to_rgba();
+        bytes.extend_from_slice(&[r, g, b, a]);
+    }
+    bytes.resize(PALETTE_SIZE * 4, 0);
+
+    Ok(bytes)
+}
+
+impl Drawable for PalettedImage {
+    fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
+        let transform = quad_transform(&param, self.width(), self.height());
+
+        crate::graphics::begin_draw_pass(ctx, param.clip);
+        let pipeline =
+            ctx.gfx_context
+                .pipeline(&mut ctx.quad_ctx, PipelineKind::Palette, self.blend_mode);
+        ctx.quad_ctx.apply_pipeline(&pipeline);
+        ctx.quad_ctx.apply_bindings(&self.bindings);
+
+        ctx.quad_ctx.apply_uniforms(&image_shader::Uniforms {
+            projection: ctx.gfx_context.transformed_projection(),
+            model: transform,
+            source: source_rect(&param),
+            color: param.color.into(),
+            color_add: param.color_add.into(),
+            depth: param.depth,
+        });
+        ctx.quad_ctx.draw(0, 6, 1);
+
+        ctx.quad_ctx.end_render_pass();
+
+        Ok(())
+    }
+
+    fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
+        self.blend_mode = mode;
+    }
+
+    fn blend_mode(&self) -> Option<BlendMode> {
+        self.blend_mode
+    }
+
+    fn aabb(&self) -> Option<Rect> {
+        Some(self.dimensions())
+    }
+}
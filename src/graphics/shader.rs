@@ -1,3 +1,6 @@
+use super::gl;
+use crate::{Context, GameError, GameResult};
+
 /// An enum for specifying default and custom blend modes
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlendMode {
@@ -11,19 +14,223 @@ pub enum BlendMode {
     /// alpha channel with the value of the destination multiplied by the inverse
     /// of the source alpha channel. Has the usual transparency effect: mixes the
     /// two colors using a fraction of each one specified by the alpha of the source.
+    ///
+    /// The alpha channel adds the source alpha to the destination one
+    /// multiplied by the inverse of the source alpha, so drawing into a
+    /// transparent `Canvas` leaves it with premultiplied colors, see
+    /// `Premultiplied`.
     Alpha,
+    /// Like `Alpha`, for a source whose color is already multiplied by
+    /// its alpha: adds the source value with the value of the destination
+    /// multiplied by the inverse of the source alpha channel.
+    ///
+    /// This is the default for canvases, which hold premultiplied colors:
+    /// layering transparent canvases with `Alpha` would multiply their colors
+    /// by alpha twice and darken their edges. A `DrawParam::color` fading such
+    /// a source needs its color scaled by its alpha too, like
+    /// `Color::new(a, a, a, a)`.
+    Premultiplied,
     /// When combining two fragments, subtract the destination color from a constant
     /// color using the source color as weight. Has an invert effect with the constant
     /// color as base and source color controlling displacement from the base color.
-    /// A white source color and a white value results in plain invert. The output
-    /// alpha is same as destination alpha.
+    /// A white source color and a white value results in plain invert.
+    ///
+    /// The alpha channel is blended the same way as the color ones.
     Invert,
     /// When combining two fragments, multiply their values together.
     Multiply,
     /// When combining two fragments, choose the source value
     Replace,
     /// When combining two fragments, choose the lighter value
+    ///
+    /// miniquad has no min/max blend equations, so this is approximated
+    /// with a "screen" blend, which never darkens the destination.
     Lighten,
     /// When combining two fragments, choose the darker value
+    ///
+    /// miniquad has no min/max blend equations, so this is approximated
+    /// with `Multiply`, which never lightens the destination.
     Darken,
 }
+
+impl BlendMode {
+    pub(crate) fn to_blend_state(
+        self,
+    ) -> (
+        miniquad::Equation,
+        miniquad::BlendFactor,
+        miniquad::BlendFactor,
+    ) {
+        use miniquad::{BlendFactor, BlendValue, Equation};
+
+        match self {
+            BlendMode::Add => (
+                Equation::Add,
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                BlendFactor::One,
+            ),
+            BlendMode::Subtract => (
+                Equation::ReverseSubtract,
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                BlendFactor::One,
+            ),
+            BlendMode::Alpha => (
+                Equation::Add,
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+            ),
+            BlendMode::Premultiplied => (
+                Equation::Add,
+                BlendFactor::One,
+                BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+            ),
+            BlendMode::Invert => (
+                Equation::Add,
+                BlendFactor::OneMinusValue(BlendValue::DestinationColor),
+                BlendFactor::Zero,
+            ),
+            BlendMode::Multiply | BlendMode::Darken => (
+                Equation::Add,
+                BlendFactor::Value(BlendValue::DestinationColor),
+                BlendFactor::Zero,
+            ),
+            BlendMode::Replace => (Equation::Add, BlendFactor::One, BlendFactor::Zero),
+            BlendMode::Lighten => (
+                Equation::Add,
+                BlendFactor::One,
+                BlendFactor::OneMinusValue(BlendValue::SourceColor),
+            ),
+        }
+    }
+
+    pub(crate) fn to_alpha_blend_state(
+        self,
+    ) -> (
+        miniquad::Equation,
+        miniquad::BlendFactor,
+        miniquad::BlendFactor,
+    ) {
+        use miniquad::{BlendFactor, BlendValue, Equation};
+
+        match self {
+            BlendMode::Alpha => (
+                Equation::Add,
+                BlendFactor::One,
+                BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+            ),
+            _ => self.to_blend_state(),
+        }
+    }
+}
+
+/// Which triangles to skip drawing, based on their winding order.
+///
+/// Everything is drawn by default, as 2D geometry often mixes windings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    /// Draw all triangles.
+    None,
+    /// Skip front-facing triangles.
+    Front,
+    /// Skip back-facing triangles.
+    Back,
+}
+
+impl Default for CullMode {
+    fn default() -> Self {
+        CullMode::None
+    }
+}
+
+/// Which winding order makes a triangle front-facing,
+/// as seen on the screen.
+///
+/// Canvases are rendered upside down and flipped back when drawn,
+/// so when drawing onto a `Canvas` the winding is reversed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrontFace {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Default for FrontFace {
+    fn default() -> Self {
+        FrontFace::CounterClockwise
+    }
+}
+
+impl CullMode {
+    pub(crate) fn to_quad(self) -> miniquad::CullFace {
+        match self {
+            CullMode::None => miniquad::CullFace::Nothing,
+            CullMode::Front => miniquad::CullFace::Front,
+            CullMode::Back => miniquad::CullFace::Back,
+        }
+    }
+}
+
+impl FrontFace {
+    pub(crate) fn to_quad(self) -> miniquad::FrontFaceOrder {
+        match self {
+            FrontFace::Clockwise => miniquad::FrontFaceOrder::Clockwise,
+            FrontFace::CounterClockwise => miniquad::FrontFaceOrder::CounterClockwise,
+        }
+    }
+}
+
+/// A custom fragment shader, used instead of the built-in one when drawing
+/// `Image`s and `Canvas`es. Other drawables always use their built-in shaders.
+///
+/// The fragment shader is GLSL 100 and gets the same inputs as the built-in one:
+///
+/// ```glsl
+/// varying lowp vec4 color;   // DrawParam::color
+/// varying lowp vec2 uv;
+/// uniform sampler2D Texture;
+/// uniform lowp vec4 ColorAdd; // DrawParam::color_add
+/// uniform lowp vec4 Params;   // set with Shader::set_params
+/// ```
+///
+/// Shaders live as long as the `Context`; a `Shader` is only a handle.
+/// See [`effects`](effects/index.html) for some ready-made ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Shader {
+    pub(crate) id: usize,
+}
+
+impl Shader {
+    /// Compiles a fragment shader, failing with the compiler's log if it
+    /// doesn't compile.
+    pub fn from_fragment(ctx: &mut Context, fragment: &str) -> GameResult<Shader> {
+        if let Some(log) = gl::fragment_shader_error(fragment) {
+            return Err(GameError::ResourceLoadError(format!(
+                "Fragment shader doesn't compile: {}",
+                log
+            )));
+        }
+        let id = ctx.gfx_context.add_shader(&mut ctx.quad_ctx, fragment);
+        Ok(Shader { id })
+    }
+
+    /// Sets the `Params` uniform of this shader, `[0.; 4]` by default.
+    pub fn set_params(&self, ctx: &mut Context, params: [f32; 4]) {
+        ctx.gfx_context.set_shader_params(self.id, params.into());
+    }
+}
+
+/// Sets the shader used to draw every `Image` and `Canvas` from now on.
+/// `None` restores the built-in one.
+///
+/// ```rust,ignore
+/// graphics::set_shader(ctx, Some(&self.grayscale));
+/// graphics::draw(ctx, &self.scene, DrawParam::new())?;
+/// graphics::set_shader(ctx, None);
+/// ```
+pub fn set_shader(ctx: &mut Context, shader: Option<&Shader>) {
+    ctx.gfx_context.shader = shader.cloned();
+}
+
+/// Returns the shader set with [`set_shader()`](fn.set_shader.html), if any.
+pub fn shader(ctx: &Context) -> Option<Shader> {
+    ctx.gfx_context.shader
+}
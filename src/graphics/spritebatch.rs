@@ -1,22 +1,133 @@
 use crate::{
-    error::GameResult,
+    error::{GameError, GameResult},
     graphics::{
-        self, context::batch_shader, image::param_to_instance_transform, transform_rect, BlendMode,
-        DrawParam, FilterMode, InstanceAttributes, Rect,
+        self,
+        context::{batch_shader, PipelineKind},
+        image::{param_to_instance_transform, source_rect},
+        transform_rect, BlendMode, Color, DrawParam, FilterMode, InstanceAttributes, Rect,
     },
     Context,
 };
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
-use cgmath::Vector4;
-use miniquad::{Buffer, BufferType, PassAction};
+use cgmath::{Matrix4, Vector4};
+use miniquad::{Buffer, BufferType};
 
+/// The per-sprite buffer of a batch, deleted along with it.
+#[derive(Debug, Default)]
+struct InstanceBuffer {
+    buffer: Cell<Option<Buffer>>,
+    /// In bytes.
+    size: Cell<usize>,
+}
+
+impl InstanceBuffer {
+    /// Returns a buffer of at least `count` instances of `T`,
+    /// the current one if large enough.
+    fn reserve<T>(
+        &self,
+        ctx: &mut miniquad::Context,
+        count: usize,
+        policy: GrowthPolicy,
+    ) -> GameResult<Buffer> {
+        if let GrowthPolicy::FixedCapacity(capacity) = policy {
+            if count > capacity {
+                return Err(GameError::RenderError(format!(
+                    "SpriteBatch of {} sprites is over its fixed capacity of {}",
+                    count, capacity
+                )));
+            }
+        }
+
+        let instance_size = std::mem::size_of::<T>();
+        let size = instance_size * count;
+        if let Some(buffer) = self.buffer.get() {
+            if self.size.get() >= size {
+                return Ok(buffer);
+            }
+        }
+
+        let size = match policy {
+            GrowthPolicy::ExactFit => size,
+            GrowthPolicy::Double => size.max(self.size.get() * 2),
+            GrowthPolicy::FixedCapacity(capacity) => {
+                // room for the largest instances, so it is never reallocated
+                capacity * std::mem::size_of::<GradientInstanceAttributes>()
+            }
+        };
+
+        let buffer = Buffer::stream(ctx, BufferType::VertexBuffer, size);
+        if let Some(old) = self.buffer.replace(Some(buffer)) {
+            old.delete();
+        }
+        self.size.set(size);
+        Ok(buffer)
+    }
+}
+
+/// How the GPU buffer of a [`SpriteBatch`](struct.SpriteBatch.html) grows
+/// when it is drawn with more sprites than it has room for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Reallocate to exactly the number of sprites, the least memory.
+    ExactFit,
+    /// Reallocate to at least twice the current size, fewer reallocations
+    /// for batches that keep growing.
+    Double,
+    /// Allocate room for this many sprites once, and never reallocate.
+    /// Drawing the batch with more sprites fails with `GameError::RenderError`.
+    FixedCapacity(usize),
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> GrowthPolicy {
+        GrowthPolicy::ExactFit
+    }
+}
+
+impl Drop for InstanceBuffer {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.delete();
+        }
+    }
+}
+
+/// Per-instance data of a sprite with a color per corner,
+/// laid out for the gradient sprite pipeline.
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct GradientInstanceAttributes {
+    source: Vector4<f32>,
+    /// Top-left and top-right, then bottom-right and bottom-left colors,
+    /// each packed in two floats.
+    corners: [Vector4<f32>; 2],
+    model: Matrix4<f32>,
+}
+
+/// Packs the 8-bit channels of `color` in two floats, `r * 256 + g` and `b * 256 + a`.
+fn pack_color(color: Color) -> (f32, f32) {
+    let (r, g, b, a) = color.to_rgba();
+    (
+        f32::from(r) * 256. + f32::from(g),
+        f32::from(b) * 256. + f32::from(a),
+    )
+}
+
+/// Many sprites sharing one image, drawn with a single draw call.
+///
+/// The batch owns a GPU buffer of its sprites, freed when it is dropped.
 #[derive(Debug)]
 pub struct SpriteBatch {
     image: RefCell<graphics::Image>,
     sprites: Vec<DrawParam>,
+    /// Corner colors of the sprites added with `add_gradient`, one per sprite.
+    gradients: Vec<Option<[Color; 4]>>,
     gpu_sprites: RefCell<Vec<InstanceAttributes>>,
+    gpu_gradient_sprites: RefCell<Vec<GradientInstanceAttributes>>,
+    instance_buffer: InstanceBuffer,
+    growth_policy: GrowthPolicy,
     blend_mode: Option<BlendMode>,
 }
 
@@ -34,11 +145,26 @@ impl SpriteBatch {
         Self {
             image: RefCell::new(image),
             sprites: vec![],
+            gradients: vec![],
             blend_mode: None,
             gpu_sprites: RefCell::new(vec![]),
+            gpu_gradient_sprites: RefCell::new(vec![]),
+            instance_buffer: InstanceBuffer::default(),
+            growth_policy: GrowthPolicy::default(),
         }
     }
 
+    /// Sets how the GPU buffer of the sprites grows, builder-style.
+    /// Defaults to `GrowthPolicy::ExactFit`.
+    pub fn with_growth_policy(mut self, policy: GrowthPolicy) -> SpriteBatch {
+        self.growth_policy = policy;
+        self
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth_policy
+    }
+
     /// Adds a new sprite to the sprite batch.
     ///
     /// Returns a handle with which type to modify the sprite using
@@ -49,17 +175,36 @@ impl SpriteBatch {
     {
         let param = param.into();
         self.sprites.push(param);
+        self.gradients.push(None);
         SpriteIdx(self.sprites.len() - 1)
     }
 
+    /// Adds a new sprite with a color per corner, blended across it:
+    /// top-left, top-right, bottom-right and bottom-left, before any rotation.
+    ///
+    /// The colors are multiplied with `param.color` and stored with 8 bits
+    /// per channel. While the batch holds such a sprite, all its sprites are
+    /// uploaded in a slightly larger format, otherwise nothing changes.
+    pub fn add_gradient<P>(&mut self, param: P, colors: [Color; 4]) -> SpriteIdx
+    where
+        P: Into<graphics::DrawParam>,
+    {
+        let idx = self.add(param);
+        self.gradients[idx.0] = Some(colors);
+        idx
+    }
+
     /// Removes all data from the sprite batch.
     pub fn clear(&mut self) {
         self.sprites.clear();
+        self.gradients.clear();
     }
 
     /// Unwraps and returns the contained `Image`
     pub fn into_inner(self) -> graphics::Image {
-        self.image.into_inner()
+        let mut image = self.image.into_inner();
+        image.bindings.vertex_buffers.truncate(1);
+        image
     }
 
     /// Replaces the contained `Image`, returning the old one.
@@ -67,8 +212,11 @@ impl SpriteBatch {
         use std::mem;
 
         self.gpu_sprites = RefCell::new(vec![]);
+        self.gpu_gradient_sprites = RefCell::new(vec![]);
         let mut self_image = self.image.borrow_mut();
-        mem::replace(&mut *self_image, image)
+        let mut old = mem::replace(&mut *self_image, image);
+        old.bindings.vertex_buffers.truncate(1);
+        old
     }
 
     /// Set the filter mode for the SpriteBatch.
@@ -79,57 +227,93 @@ impl SpriteBatch {
 
 impl graphics::Drawable for SpriteBatch {
     fn draw(&self, ctx: &mut Context, param: DrawParam) -> GameResult {
+        if self.sprites.is_empty() {
+            return Ok(());
+        }
+
         let mut image = self.image.borrow_mut();
-        let mut gpu_sprites = self.gpu_sprites.borrow_mut();
+        image.apply_filter(ctx);
 
-        if self.sprites.len() > gpu_sprites.len() {
-            gpu_sprites.resize(self.sprites.len(), InstanceAttributes::default());
+        let (width, height) = (f32::from(image.width), f32::from(image.height));
+        let model = |param: &DrawParam| {
+            let mut new_param = *param;
+            new_param.scale = graphics::Vector2::new(
+                param.src.w * param.scale.x * width,
+                param.src.h * param.scale.y * height,
+            )
+            .into();
+            param_to_instance_transform(&new_param)
+        };
 
-            let buffer = Buffer::stream(
-                &mut ctx.quad_ctx,
-                BufferType::VertexBuffer,
-                std::mem::size_of::<InstanceAttributes>() * self.sprites.len(),
-            );
+        let (kind, buffer) = if self.gradients.iter().all(Option::is_none) {
+            let mut gpu_sprites = self.gpu_sprites.borrow_mut();
+            gpu_sprites.clear();
+            gpu_sprites.extend(self.sprites.iter().map(|param| InstanceAttributes {
+                model: model(param),
+                source: source_rect(param),
+                color: param.color.into(),
+            }));
 
-            if image.bindings.vertex_buffers.len() <= 1 {
-                image.bindings.vertex_buffers.push(buffer);
-            } else {
-                image.bindings.vertex_buffers[1].delete();
+            let buffer = self.instance_buffer.reserve::<InstanceAttributes>(
+                &mut ctx.quad_ctx,
+                gpu_sprites.len(),
+                self.growth_policy,
+            )?;
+            buffer.update(&mut ctx.quad_ctx, &gpu_sprites[..]);
+            (PipelineKind::Sprite, buffer)
+        } else {
+            let mut gpu_sprites = self.gpu_gradient_sprites.borrow_mut();
+            gpu_sprites.clear();
+            let sprites = self.sprites.iter().zip(&self.gradients);
+            gpu_sprites.extend(sprites.map(|(param, gradient)| {
+                let tint = param.color;
+                let corner = |n: usize| {
+                    let c = gradient.map_or(graphics::WHITE, |colors| colors[n]);
+                    pack_color(Color::new(
+                        c.r * tint.r,
+                        c.g * tint.g,
+                        c.b * tint.b,
+                        c.a * tint.a,
+                    ))
+                };
+                let (tl, tr, br, bl) = (corner(0), corner(1), corner(2), corner(3));
+                GradientInstanceAttributes {
+                    source: source_rect(param),
+                    corners: [
+                        Vector4::new(tl.0, tl.1, tr.0, tr.1),
+                        Vector4::new(br.0, br.1, bl.0, bl.1),
+                    ],
+                    model: model(param),
+                }
+            }));
 
-                image.bindings.vertex_buffers[1] = buffer;
-            }
-        }
+            let buffer = self.instance_buffer.reserve::<GradientInstanceAttributes>(
+                &mut ctx.quad_ctx,
+                gpu_sprites.len(),
+                self.growth_policy,
+            )?;
+            buffer.update(&mut ctx.quad_ctx, &gpu_sprites[..]);
+            (PipelineKind::GradientSprite, buffer)
+        };
 
-        for (n, param) in self.sprites.iter().enumerate() {
-            let mut new_param = param.clone();
-            let src_width = param.src.w;
-            let src_height = param.src.h;
-            let real_scale = graphics::Vector2::new(
-                src_width * param.scale.x * f32::from(image.width),
-                src_height * param.scale.y * f32::from(image.height),
-            );
-            new_param.scale = real_scale.into();
-
-            let instance = InstanceAttributes {
-                model: param_to_instance_transform(&new_param),
-                source: Vector4::new(param.src.x, param.src.y, param.src.w, param.src.h),
-                color: Vector4::new(param.color.r, param.color.g, param.color.b, param.color.a),
-            };
-            gpu_sprites[n] = instance;
+        if image.bindings.vertex_buffers.len() <= 1 {
+            image.bindings.vertex_buffers.push(buffer);
+        } else {
+            image.bindings.vertex_buffers[1] = buffer;
         }
 
-        image.bindings.vertex_buffers[1]
-            .update(&mut ctx.quad_ctx, &gpu_sprites[0..self.sprites.len()]);
-
-        let pass = ctx.framebuffer();
-        ctx.quad_ctx.begin_pass(pass, PassAction::Nothing);
-        ctx.quad_ctx
-            .apply_pipeline(&ctx.gfx_context.sprite_pipeline);
+        graphics::begin_draw_pass(ctx, param.clip);
+        let pipeline = ctx
+            .gfx_context
+            .pipeline(&mut ctx.quad_ctx, kind, self.blend_mode);
+        ctx.quad_ctx.apply_pipeline(&pipeline);
         ctx.quad_ctx.apply_bindings(&image.bindings);
 
         let uniforms = batch_shader::Uniforms {
-            projection: ctx.gfx_context.projection,
+            projection: ctx.gfx_context.transformed_projection(),
             model: param_to_instance_transform(&param),
+            color_add: param.color_add.into(),
+            depth: param.depth,
         };
         ctx.quad_ctx.apply_uniforms(&uniforms);
         ctx.quad_ctx.draw(0, 6, self.sprites.len() as i32);
@@ -139,7 +323,7 @@ impl graphics::Drawable for SpriteBatch {
         Ok(())
     }
 
-    fn dimensions(&self, _ctx: &mut Context) -> Option<Rect> {
+    fn aabb(&self) -> Option<Rect> {
         if self.sprites.is_empty() {
             return None;
         }
@@ -157,7 +341,6 @@ impl graphics::Drawable for SpriteBatch {
     }
 
     fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
-        // TODO
         self.blend_mode = mode;
     }
 
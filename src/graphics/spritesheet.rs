@@ -0,0 +1,121 @@
+use crate::graphics::{DrawParam, Image, Rect};
+
+/// An `Image` split into a regular grid of equally sized frames.
+///
+/// The source rects are precomputed as fractions of the whole image,
+/// ready to be fed into [`DrawParam::src`](struct.DrawParam.html#structfield.src).
+/// Frames are numbered row by row, starting from the top-left corner.
+///
+/// ```rust,ignore
+/// let sheet = SpriteSheet::new(image, 4, 8);
+/// graphics::draw(ctx, sheet.image(), sheet.frame(5).dest(position))?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    image: Image,
+    rows: usize,
+    cols: usize,
+    frames: Vec<Rect>,
+}
+
+impl SpriteSheet {
+    /// Slices `image` into `rows` x `cols` frames.
+    ///
+    /// Panics if `rows` or `cols` is zero.
+    pub fn new(image: Image, rows: usize, cols: usize) -> SpriteSheet {
+        assert!(
+            rows > 0 && cols > 0,
+            "SpriteSheet needs at least one row and one column"
+        );
+
+        let w = 1.0 / cols as f32;
+        let h = 1.0 / rows as f32;
+        let mut frames = Vec::with_capacity(rows * cols);
+        for row in 0..rows {
+            for col in 0..cols {
+                frames.push(Rect::new(col as f32 * w, row as f32 * h, w, h));
+            }
+        }
+
+        SpriteSheet {
+            image,
+            rows,
+            cols,
+            frames,
+        }
+    }
+
+    /// Slices `image` into frames of `frame_width` x `frame_height` pixels.
+    ///
+    /// Leftover pixels at the right and bottom edges, if any, are ignored.
+    pub fn from_grid(image: Image, frame_width: u16, frame_height: u16) -> SpriteSheet {
+        let cols = (image.width() / frame_width.max(1)).max(1) as usize;
+        let rows = (image.height() / frame_height.max(1)).max(1) as usize;
+
+        let mut sheet = SpriteSheet::new(image, rows, cols);
+        let reference = sheet.image.dimensions();
+        for (n, frame) in sheet.frames.iter_mut().enumerate() {
+            let (row, col) = (n / cols, n % cols);
+            *frame = Rect::fraction(
+                (col * frame_width as usize) as f32,
+                (row * frame_height as usize) as f32,
+                frame_width as f32,
+                frame_height as f32,
+                &reference,
+            );
+        }
+        sheet
+    }
+
+    /// The image all the frames are taken from.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Number of rows in the grid.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns in the grid.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Total number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if the sheet contains no frames.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// All the frame source rects, in order.
+    pub fn frames(&self) -> &[Rect] {
+        &self.frames
+    }
+
+    /// The source rect of the frame with the given index.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn src(&self, index: usize) -> Rect {
+        self.frames[index]
+    }
+
+    /// A `DrawParam` with `src` set to the frame with the given index.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn frame(&self, index: usize) -> DrawParam {
+        DrawParam::new().src(self.src(index))
+    }
+
+    /// A `DrawParam` with `src` set to the frame at the given row and column.
+    ///
+    /// Panics if `row` or `col` is out of bounds.
+    pub fn frame_at(&self, row: usize, col: usize) -> DrawParam {
+        assert!(row < self.rows && col < self.cols);
+        self.frame(row * self.cols + col)
+    }
+}
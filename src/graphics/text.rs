@@ -1,6 +1,6 @@
-use super::{BlendMode, Color, DrawParam, Drawable, GameResult, Rect};
+use super::{BlendMode, Color, DrawParam, Drawable, GameResult, Point2, Rect};
 
-use crate::{filesystem, graphics::param_to_instance_transform};
+use crate::{error::GameError, filesystem, graphics::param_to_instance_transform};
 
 use miniquad_text_rusttype::{FontTexture, TextDisplay};
 
@@ -9,6 +9,18 @@ use std::{cell::Ref, ops::Deref, path, rc::Rc};
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct FontId(usize);
 
+impl FontId {
+    /// The id of the font loaded in the given slot, see
+    /// [`font_count()`](fn.font_count.html). `0` is the default font.
+    pub fn from_index(index: usize) -> FontId {
+        FontId(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Font(pub FontId);
 
@@ -20,6 +32,19 @@ impl Default for Font {
 
 impl Font {
     /// Should construct font from the ttf file path.
+    ///
+    /// Every call rasterizes the font into a new atlas texture, in a new
+    /// slot, that lives as long as the `Context`: `miniquad_text_rusttype`
+    /// owns the texture and gives no way to delete it. Load each font once
+    /// and keep the `Font`, it's only a handle.
+    ///
+    /// Glyphs are packed in the atlas with no padding between them, and it
+    /// can't be configured: `miniquad_text_rusttype` takes no such setting,
+    /// it would have to change upstream. Drawn at a scale that isn't a whole
+    /// number, linear filtering may sample the edge of a neighboring glyph
+    /// and show faint lines; load the font at the size it's drawn at instead
+    /// of scaling it, see [`set_font_size()`](fn.set_font_size.html), or
+    /// snap it with [`set_text_snap()`](fn.set_text_snap.html).
     pub fn new<P: AsRef<path::Path>>(
         ctx: &mut crate::Context,
         ttf_filepath: P,
@@ -38,6 +63,33 @@ impl Font {
     }
 }
 
+/// What a loaded font was rasterized with, see [`font_info()`](fn.font_info.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontInfo {
+    /// The size of the glyphs in the atlas, in pixels.
+    pub size: u32,
+    /// Number of glyphs in the atlas.
+    pub glyph_count: usize,
+}
+
+/// Number of font slots, loaded fonts have ids from `0` to `font_count() - 1`.
+///
+/// Slot `0` is the default font, DejaVuSerif, and stays there empty
+/// if it's disabled with `Conf::load_default_font`.
+pub fn font_count(ctx: &crate::Context) -> usize {
+    ctx.gfx_context.fonts_cache.len()
+}
+
+/// Returns what the font `id` was loaded with, or `None` if there's
+/// no such font.
+pub fn font_info(ctx: &crate::Context, id: FontId) -> Option<FontInfo> {
+    ctx.gfx_context.fonts_cache.get(id.0)?.as_ref()?;
+    Some(FontInfo {
+        size: ctx.gfx_context.font_sizes[id.0],
+        glyph_count: FontTexture::ascii_character_list().len(),
+    })
+}
+
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
 pub struct Scale {
     /// Horizontal scale, in pixels.
@@ -152,10 +204,14 @@ where
     }
 }
 
+/// Cloning a `Text` is cheap, clones share the same glyph layout.
+#[derive(Clone)]
 pub struct Text {
     fragment: TextFragment,
     font_id: FontId,
-    gpu_text: std::cell::RefCell<Option<TextDisplay<std::rc::Rc<FontTexture>>>>,
+    gpu_text: Rc<std::cell::RefCell<Option<TextDisplay<std::rc::Rc<FontTexture>>>>>,
+    outline: Option<(Color, f32)>,
+    shadow: Option<(Color, Point2)>,
 }
 
 impl Text {
@@ -174,17 +230,60 @@ impl Text {
         Text {
             fragment: fragment.into(),
             font_id: FontId(0),
-            gpu_text: std::cell::RefCell::new(None),
+            gpu_text: Rc::new(std::cell::RefCell::new(None)),
+            outline: None,
+            shadow: None,
         }
     }
 
+    /// Draws an outline of `width` around the glyphs, in the same units as
+    /// [`dimensions()`](#method.dimensions), scaled and rotated with the text.
+    ///
+    /// The glyphs are drawn eight more times, offset all around, before the
+    /// text itself; widths of more than a couple of pixels show gaps.
+    pub fn set_outline(&mut self, color: Color, width: f32) -> &mut Text {
+        self.outline = Some((color, width));
+        self
+    }
+
+    pub fn clear_outline(&mut self) -> &mut Text {
+        self.outline = None;
+        self
+    }
+
+    /// Draws a drop shadow, the glyphs again at `offset`, before the text
+    /// and its outline, in the same units as the outline width.
+    pub fn set_shadow<P>(&mut self, color: Color, offset: P) -> &mut Text
+    where
+        P: Into<mint::Point2<f32>>,
+    {
+        let offset: mint::Point2<f32> = offset.into();
+        self.shadow = Some((color, offset.into()));
+        self
+    }
+
+    pub fn clear_shadow(&mut self) -> &mut Text {
+        self.shadow = None;
+        self
+    }
+
     fn lazy_init_gpu_text<'a>(
         &'a self,
         ctx: &mut crate::Context,
-    ) -> impl Deref<Target = TextDisplay<Rc<FontTexture>>> + 'a {
-        let font =
-            ctx.gfx_context.fonts_cache[self.fragment.font.map_or(self.font_id, |f| f.0).0].clone();
+    ) -> GameResult<impl Deref<Target = TextDisplay<Rc<FontTexture>>> + 'a> {
         if self.gpu_text.borrow().is_none() {
+            let font_id = self.font_id();
+            let font = ctx
+                .gfx_context
+                .fonts_cache
+                .get(font_id.0)
+                .and_then(|font| font.clone())
+                .ok_or_else(|| {
+                    GameError::FontError(format!(
+                        "Font {:?} is not loaded (the default font is disabled with `Conf::load_default_font`)",
+                        font_id
+                    ))
+                })?;
             let text = miniquad_text_rusttype::TextDisplay::new(
                 &mut ctx.quad_ctx,
                 &ctx.gfx_context.text_system,
@@ -195,19 +294,48 @@ impl Text {
             *self.gpu_text.borrow_mut() = Some(text);
         }
 
-        Ref::map(self.gpu_text.borrow(), |t| t.as_ref().unwrap())
+        Ok(Ref::map(self.gpu_text.borrow(), |t| t.as_ref().unwrap()))
+    }
+
+    /// The font the text is drawn with.
+    fn font_id(&self) -> FontId {
+        self.fragment.font.map_or(self.font_id, |f| f.0)
     }
+
+    /// Returns the width and height of the text.
+    ///
+    /// If the text's font is not loaded, nothing would be drawn, and the
+    /// width is `0.0`.
     pub fn dimensions(&self, ctx: &mut crate::Context) -> (f32, f32) {
-        let text = self.lazy_init_gpu_text(ctx);
         let scale = self.fragment.scale.unwrap_or(Scale { x: 1., y: 1. });
+        let width = self
+            .lazy_init_gpu_text(ctx)
+            .map_or(0., |text| text.get_width());
 
-        (text.get_width() * scale.x, scale.y)
+        (width * scale.x, scale.y)
+    }
+
+    /// Like [`dimensions()`](#method.dimensions), extended to what the outline
+    /// and the shadow cover, so layouts can make room for them. The rect
+    /// starts above and left of the text origin when they stick out there.
+    pub fn dimensions_with_effects(&self, ctx: &mut crate::Context) -> Rect {
+        let (w, h) = self.dimensions(ctx);
+        let mut rect = Rect::new(0., 0., w, h);
+        if let Some((_, width)) = self.outline {
+            rect = Rect::new(-width, -width, w + width * 2., h + width * 2.);
+        }
+        if let Some((_, offset)) = self.shadow {
+            let mut shadow = rect;
+            shadow.translate([offset.x, offset.y]);
+            rect = rect.combine_with(shadow);
+        }
+        rect
     }
 }
 
 impl Drawable for Text {
     fn draw(&self, ctx: &mut crate::Context, param: DrawParam) -> GameResult {
-        let text = self.lazy_init_gpu_text(ctx);
+        let text = self.lazy_init_gpu_text(ctx)?;
 
         let scale = self.fragment.scale.unwrap_or(Scale { x: 1., y: 1. });
 
@@ -219,24 +347,63 @@ impl Drawable for Text {
         new_param.dest.y += scale.y * param.scale.y * 0.7;
 
         let transform = param_to_instance_transform(&new_param);
-        let projection = ctx.gfx_context.projection;
+        let projection = ctx.gfx_context.transformed_projection();
+        let snap_size = if ctx.gfx_context.text_snap {
+            Some(crate::graphics::target_size(ctx))
+        } else {
+            None
+        };
+
+        // `offset` is in text pixels, the glyphs are already scaled by `scale`
+        let draw_glyphs = |ctx: &mut crate::Context, offset: Point2, color: Color| {
+            let shift = cgmath::Vector3::new(offset.x / scale.x, -offset.y / scale.y, 0.);
+            let mut mvp = projection * transform * cgmath::Matrix4::from_translation(shift);
+            if let Some((w, h)) = snap_size {
+                // round the origin, the translation part, to a whole pixel
+                let origin = mvp.w;
+                let snap = |ndc: f32, size: f32| ((ndc + 1.) / 2. * size).round() / size * 2. - 1.;
+                mvp.w.x = snap(origin.x / origin.w, w) * origin.w;
+                mvp.w.y = snap(origin.y / origin.w, h) * origin.w;
+            }
+
+            miniquad_text_rusttype::draw(
+                &mut ctx.quad_ctx,
+                &text,
+                &ctx.gfx_context.text_system,
+                mvp,
+                (color.r, color.g, color.b, color.a),
+            );
+        };
+        // the effects fade along with the text
+        let faded = |color: Color| Color::new(color.r, color.g, color.b, color.a * param.color.a);
 
-        let mvp = projection * transform;
+        if let Some((color, offset)) = self.shadow {
+            draw_glyphs(ctx, offset, faded(color));
+        }
+        if let Some((color, width)) = self.outline {
+            for &(x, y) in &[
+                (-1., -1.),
+                (0., -1.),
+                (1., -1.),
+                (-1., 0.),
+                (1., 0.),
+                (-1., 1.),
+                (0., 1.),
+                (1., 1.),
+            ] {
+                draw_glyphs(ctx, Point2::new(x * width, y * width), faded(color));
+            }
+        }
+        draw_glyphs(ctx, Point2::new(0., 0.), param.color);
 
-        miniquad_text_rusttype::draw(
-            &mut ctx.quad_ctx,
-            &text,
-            &ctx.gfx_context.text_system,
-            mvp,
-            (param.color.r, param.color.g, param.color.b, param.color.a),
-        );
         Ok(())
     }
 
     fn dimensions(&self, ctx: &mut crate::Context) -> Option<Rect> {
-        let (w, h) = self.dimensions(ctx);
+        let text = self.lazy_init_gpu_text(ctx).ok()?;
+        let scale = self.fragment.scale.unwrap_or(Scale { x: 1., y: 1. });
 
-        Some(Rect::new(0., 0., w as f32, h as f32))
+        Some(Rect::new(0., 0., text.get_width() * scale.x, scale.y))
     }
 
     fn set_blend_mode(&mut self, _: Option<BlendMode>) {
@@ -247,3 +414,65 @@ impl Drawable for Text {
         unimplemented!()
     }
 }
+
+/// A text waiting in the queue of [`queue_text()`](fn.queue_text.html).
+pub(crate) struct QueuedText {
+    text: Text,
+    dest: Point2,
+    color: Option<Color>,
+}
+
+/// Queues a `Text` to be drawn by [`draw_queued_text()`](fn.draw_queued_text.html),
+/// at `relative_dest` from the position given there.
+///
+/// `color` overrides the color of the `DrawParam` given to `draw_queued_text()`.
+/// The text is cloned, which is cheap and keeps its already laid out glyphs.
+pub fn queue_text<P>(ctx: &mut crate::Context, text: &Text, relative_dest: P, color: Option<Color>)
+where
+    P: Into<mint::Point2<f32>>,
+{
+    let dest: mint::Point2<f32> = relative_dest.into();
+    ctx.gfx_context.queued_text.push(QueuedText {
+        text: text.clone(),
+        dest: dest.into(),
+        color,
+    });
+}
+
+/// Draws all the texts queued with [`queue_text()`](fn.queue_text.html)
+/// and empties the queue.
+///
+/// `param` applies to every text, their relative destinations are scaled
+/// along. Texts are drawn grouped by font, each font having its own glyph
+/// atlas, so mixing fonts costs one texture switch per font instead of
+/// one per change between consecutive texts. Within a font they keep their
+/// queue order; texts of different fonts should not overlap, as they may
+/// be drawn in a different order than queued.
+///
+/// Every text is still one draw call, glyphs of several texts are not
+/// merged into one mesh.
+pub fn draw_queued_text<D>(ctx: &mut crate::Context, param: D) -> GameResult
+where
+    D: Into<DrawParam>,
+{
+    let param: DrawParam = param.into();
+
+    let mut queue = std::mem::replace(&mut ctx.gfx_context.queued_text, vec![]);
+    // a stable sort, to keep the queue order within a font
+    queue.sort_by_key(|queued| queued.text.font_id().0);
+
+    let result = queue.iter().try_for_each(|queued| {
+        let mut text_param = param;
+        text_param.dest.x += queued.dest.x * param.scale.x;
+        text_param.dest.y += queued.dest.y * param.scale.y;
+        if let Some(color) = queued.color {
+            text_param.color = color;
+        }
+        queued.text.draw(ctx, text_param)
+    });
+
+    // hand the allocation back for the next frame
+    queue.clear();
+    ctx.gfx_context.queued_text = queue;
+    result
+}
@@ -0,0 +1,163 @@
+use cgmath::{Matrix4, SquareMatrix, Vector3, Vector4};
+
+use crate::{
+    graphics::{self, draw_instanced, Color, Image, InstanceAttributes, Point2, Rect, WHITE},
+    Context, GameResult,
+};
+
+/// Fills `dest` with copies of `image` at its size in pixels, like a floor
+/// pattern, starting from the top-left corner of `dest`.
+///
+/// The tiles of the last column and row are cut to end exactly at the
+/// right and bottom edges of `dest`, showing the left and top part of the
+/// image. Nothing is drawn if `dest` is empty.
+///
+/// Everything is drawn in a single draw call, with the image blend mode
+/// and the current transform, like [`draw_instanced()`](fn.draw_instanced.html).
+pub fn draw_tiled(ctx: &mut Context, image: &Image, dest: Rect, color: Color) -> GameResult {
+    let (width, height) = (f32::from(image.width()), f32::from(image.height()));
+    if dest.w <= 0. || dest.h <= 0. || width <= 0. || height <= 0. {
+        return Ok(());
+    }
+
+    let (cols, rows) = (
+        (dest.w / width).ceil() as usize,
+        (dest.h / height).ceil() as usize,
+    );
+    let mut instances = Vec::with_capacity(cols * rows);
+    for row in 0..rows {
+        let y = row as f32 * height;
+        let h = (dest.h - y).min(height);
+        for col in 0..cols {
+            let x = col as f32 * width;
+            let w = (dest.w - x).min(width);
+
+            instances.push(InstanceAttributes {
+                source: Vector4::new(0., 0., w / width, h / height),
+                color: color.into(),
+                model: Matrix4::from_translation(cgmath::Vector3::new(dest.x + x, dest.y + y, 0.))
+                    * Matrix4::from_nonuniform_scale(w, h, 1.),
+            });
+        }
+    }
+
+    draw_instanced(ctx, image, &instances)
+}
+
+/// Draws a tile map: `tiles[row][col]` is the index of the tile of `atlas`
+/// drawn at `origin + (col, row) * tile_size`, counting the tiles of
+/// `atlas` left to right, then top to bottom, in a grid of `tile_size`
+/// pixels. Tiles are drawn at their size in pixels too.
+///
+/// Tiles equal to `empty`, and indices past the last tile of `atlas`, are
+/// skipped. So are the tiles outside of the
+/// [screen coordinates](fn.screen_coordinates.html), after the current
+/// transform: only the visible part of a big map costs anything, and
+/// scrolling it is a matter of moving `origin` or the transform.
+///
+/// Everything is drawn in a single draw call, with the atlas blend mode,
+/// like [`draw_instanced()`](fn.draw_instanced.html). Rows may have
+/// different lengths.
+///
+/// ```rust,ignore
+/// // 0 is no tile, the atlas has 16x16 tiles
+/// graphics::draw_tilemap(ctx, &self.tileset, &self.level, (16, 16), self.camera, Some(0))?;
+/// ```
+pub fn draw_tilemap<R: AsRef<[u16]>>(
+    ctx: &mut Context,
+    atlas: &Image,
+    tiles: &[R],
+    tile_size: (u16, u16),
+    origin: Point2,
+    empty: Option<u16>,
+) -> GameResult {
+    if tile_size.0 == 0 || tile_size.1 == 0 || tiles.is_empty() {
+        return Ok(());
+    }
+    let (width, height) = (f32::from(tile_size.0), f32::from(tile_size.1));
+    let (atlas_cols, atlas_rows) = (atlas.width() / tile_size.0, atlas.height() / tile_size.1);
+    let tile_count = usize::from(atlas_cols) * usize::from(atlas_rows);
+    if tile_count == 0 {
+        return Ok(());
+    }
+
+    let (first_col, last_col, first_row, last_row) = match visible_area(ctx) {
+        Some(area) => (
+            ((area.x - origin.x) / width).floor(),
+            ((area.right() - origin.x) / width).ceil(),
+            ((area.y - origin.y) / height).floor(),
+            ((area.bottom() - origin.y) / height).ceil(),
+        ),
+        // the transform squashes everything to a line, nothing shows
+        None => return Ok(()),
+    };
+    let clamp = |value: f32, len: usize| value.max(0.).min(len as f32) as usize;
+    let (first_row, last_row) = (clamp(first_row, tiles.len()), clamp(last_row, tiles.len()));
+
+    let source_size = (
+        width / f32::from(atlas.width()),
+        height / f32::from(atlas.height()),
+    );
+    let mut instances = vec![];
+    for (row, line) in tiles.iter().enumerate().take(last_row).skip(first_row) {
+        let line = line.as_ref();
+        let (first_col, last_col) = (clamp(first_col, line.len()), clamp(last_col, line.len()));
+        let y = origin.y + row as f32 * height;
+        for (col, &tile) in line.iter().enumerate().take(last_col).skip(first_col) {
+            if Some(tile) == empty || usize::from(tile) >= tile_count {
+                continue;
+            }
+            let (tile_col, tile_row) = (tile % atlas_cols, tile / atlas_cols);
+            let x = origin.x + col as f32 * width;
+
+            instances.push(InstanceAttributes {
+                source: Vector4::new(
+                    f32::from(tile_col) * source_size.0,
+                    f32::from(tile_row) * source_size.1,
+                    source_size.0,
+                    source_size.1,
+                ),
+                color: WHITE.into(),
+                model: Matrix4::from_translation(Vector3::new(x, y, 0.))
+                    * Matrix4::from_nonuniform_scale(width, height, 1.),
+            });
+        }
+    }
+
+    draw_instanced(ctx, atlas, &instances)
+}
+
+/// The bounds of the screen coordinates before the current transform,
+/// `None` if the transform can't be inverted.
+fn visible_area(ctx: &Context) -> Option<Rect> {
+    let screen = graphics::screen_coordinates(ctx);
+    let inverse = graphics::transform(ctx).invert()?;
+    let corners = [
+        (screen.x, screen.y),
+        (screen.right(), screen.y),
+        (screen.x, screen.bottom()),
+        (screen.right(), screen.bottom()),
+    ];
+    let corners: Vec<_> = corners
+        .iter()
+        .map(|&(x, y)| inverse * Vector3::new(x, y, 1.))
+        .collect();
+
+    let min_x = corners
+        .iter()
+        .map(|c| c.x)
+        .fold(std::f32::INFINITY, f32::min);
+    let max_x = corners
+        .iter()
+        .map(|c| c.x)
+        .fold(std::f32::NEG_INFINITY, f32::max);
+    let min_y = corners
+        .iter()
+        .map(|c| c.y)
+        .fold(std::f32::INFINITY, f32::min);
+    let max_y = corners
+        .iter()
+        .map(|c| c.y)
+        .fold(std::f32::NEG_INFINITY, f32::max);
+    Some(Rect::new(min_x, min_y, max_x - min_x, max_y - min_y))
+}
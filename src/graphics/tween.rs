@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use crate::graphics::{Color, Point2, Vector2};
+use crate::timer;
+
+/// Easing curves mapping linear progress in `[0, 1]` to eased progress.
+///
+/// All curves start at `0.0` and end at `1.0`; `Bounce*` ones stay within
+/// that range, there is no overshoot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+impl Easing {
+    /// Applies the curve to `t`, which is clamped to `[0, 1]` first.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984_375
+    }
+}
+
+/// Values that can be interpolated by a [`Tween`](struct.Tween.html).
+pub trait Tweenable: Copy {
+    /// Linear interpolation, `t == 0.0` gives `from` and `t == 1.0` gives `to`.
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(from: f32, to: f32, t: f32) -> f32 {
+        from + (to - from) * t
+    }
+}
+
+impl Tweenable for Point2 {
+    fn lerp(from: Point2, to: Point2, t: f32) -> Point2 {
+        Point2::new(f32::lerp(from.x, to.x, t), f32::lerp(from.y, to.y, t))
+    }
+}
+
+impl Tweenable for Vector2 {
+    fn lerp(from: Vector2, to: Vector2, t: f32) -> Vector2 {
+        Vector2::new(f32::lerp(from.x, to.x, t), f32::lerp(from.y, to.y, t))
+    }
+}
+
+impl Tweenable for mint::Point2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        mint::Point2 {
+            x: f32::lerp(from.x, to.x, t),
+            y: f32::lerp(from.y, to.y, t),
+        }
+    }
+}
+
+impl Tweenable for mint::Vector2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        mint::Vector2 {
+            x: f32::lerp(from.x, to.x, t),
+            y: f32::lerp(from.y, to.y, t),
+        }
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(from: Color, to: Color, t: f32) -> Color {
+        Color::new(
+            f32::lerp(from.r, to.r, t),
+            f32::lerp(from.g, to.g, t),
+            f32::lerp(from.b, to.b, t),
+            f32::lerp(from.a, to.a, t),
+        )
+    }
+}
+
+/// An eased transition between two values over a fixed duration.
+///
+/// A `Tween` holds no clock of its own: ask it for the value at any
+/// elapsed time, for example to animate a `DrawParam`'s scale, rotation,
+/// color or destination.
+///
+/// ```rust,ignore
+/// let fade = Tween::new(graphics::WHITE, Color::new(1., 1., 1., 0.), Duration::from_secs(1), Easing::QuadOut);
+/// let color = fade.sample(timer::time_since_start(ctx) - fade_started);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tween<T: Tweenable> {
+    pub from: T,
+    pub to: T,
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(from: T, to: T, duration: Duration, easing: Easing) -> Tween<T> {
+        Tween {
+            from,
+            to,
+            duration,
+            easing,
+        }
+    }
+
+    /// Linear progress in `[0, 1]` after `elapsed` time.
+    /// A zero-length tween is always complete.
+    pub fn progress(&self, elapsed: Duration) -> f32 {
+        let duration = timer::duration_to_f64(self.duration);
+        if duration <= 0.0 {
+            return 1.0;
+        }
+        (timer::duration_to_f64(elapsed) / duration).min(1.0) as f32
+    }
+
+    /// The value after `elapsed` time; `to` once the tween is complete.
+    pub fn sample(&self, elapsed: Duration) -> T {
+        T::lerp(
+            self.from,
+            self.to,
+            self.easing.apply(self.progress(elapsed)),
+        )
+    }
+
+    /// Returns true if `elapsed` is past the end of the tween.
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+}
@@ -6,6 +6,7 @@ pub type Vector2 = cgmath::Vector2<f32>;
 /// The origin of the rectangle is at the top-left,
 /// with x increasing to the right and y increasing down.
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     /// X coordinate of the left edge of the rect.
     pub x: f32,
@@ -165,6 +166,83 @@ impl Rect {
             y: y_min,
         }
     }
+
+    /// Returns this `Rect` resized and positioned to fit `container`,
+    /// centered in it.
+    ///
+    /// Handy to lay out a fixed aspect ratio sprite in a resizable panel:
+    /// draw it with the returned rect as its destination and size.
+    pub fn fit_into(&self, container: Rect, mode: FitMode) -> Rect {
+        let sx = container.w / self.w;
+        let sy = container.h / self.h;
+        let (w, h) = match mode {
+            FitMode::Contain => {
+                let s = sx.min(sy);
+                (self.w * s, self.h * s)
+            }
+            FitMode::Cover => {
+                let s = sx.max(sy);
+                (self.w * s, self.h * s)
+            }
+            FitMode::Stretch => (container.w, container.h),
+        };
+
+        Rect::new(0., 0., w, h).center_within(container)
+    }
+
+    /// Returns this `Rect` moved to the center of `container`, same size.
+    pub fn center_within(&self, container: Rect) -> Rect {
+        Rect::new(
+            container.x + (container.w - self.w) / 2.,
+            container.y + (container.h - self.h) / 2.,
+            self.w,
+            self.h,
+        )
+    }
+
+    /// Returns this `Rect` moved by the least amount to be inside `container`,
+    /// same size. Along an axis where it is larger than `container`,
+    /// it is centered instead.
+    pub fn clamp_within(&self, container: Rect) -> Rect {
+        fn clamp_axis(pos: f32, size: f32, min: f32, container_size: f32) -> f32 {
+            if size > container_size {
+                min + (container_size - size) / 2.
+            } else {
+                pos.max(min).min(min + container_size - size)
+            }
+        }
+
+        Rect::new(
+            clamp_axis(self.x, self.w, container.x, container.w),
+            clamp_axis(self.y, self.h, container.y, container.h),
+            self.w,
+            self.h,
+        )
+    }
+}
+
+/// How [`Rect::fit_into()`](struct.Rect.html#method.fit_into) resizes a rect.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FitMode {
+    /// As large as possible while entirely inside the container,
+    /// keeping the aspect ratio; leaves empty bars on two sides.
+    Contain,
+    /// As small as possible while covering the whole container,
+    /// keeping the aspect ratio; overflows on two sides.
+    Cover,
+    /// Exactly the container, ignoring the aspect ratio.
+    Stretch,
+}
+
+/// Where [`set_ortho()`](fn.set_ortho.html) puts the origin, and which way Y goes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Origin {
+    /// At the top-left corner, Y increasing down: the default coordinates.
+    TopLeft,
+    /// At the center of the screen, Y increasing up.
+    Center,
+    /// At the bottom-left corner, Y increasing up.
+    BottomLeft,
 }
 
 impl From<[f32; 4]> for Rect {
@@ -183,6 +261,7 @@ impl From<Rect> for [f32; 4] {
 ///
 /// For convenience, [`WHITE`](constant.WHITE.html) and [`BLACK`](constant.BLACK.html) are provided.
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     /// Red component
     pub r: f32,
@@ -269,6 +348,13 @@ impl Color {
     }
 }
 
+impl From<Color> for cgmath::Vector4<f32> {
+    /// Convert a `Color` into a `Vector4`, as uploaded to shader uniforms.
+    fn from(color: Color) -> Self {
+        cgmath::Vector4::new(color.r, color.g, color.b, color.a)
+    }
+}
+
 impl From<(u8, u8, u8, u8)> for Color {
     /// Convert a `(R, G, B, A)` tuple of `u8`'s in the range `[0-255]` into a `Color`
     fn from(val: (u8, u8, u8, u8)) -> Self {
@@ -357,6 +443,7 @@ impl Into<String> for Color {
 #[cfg(feature = "mesh")]
 mod draw_mode {
     use crate::graphics::{FillOptions, StrokeOptions};
+    use crate::Context;
 
     /// Specifies whether a shape should be drawn
     /// filled or as an outline.
@@ -374,6 +461,12 @@ mod draw_mode {
             DrawMode::Stroke(StrokeOptions::default().with_line_width(width))
         }
 
+        /// Constructs a DrawMode that draws a stroke with the default width
+        /// set with [`graphics::set_line_width()`](fn.set_line_width.html)
+        pub fn default_stroke(ctx: &Context) -> DrawMode {
+            DrawMode::stroke(crate::graphics::line_width(ctx))
+        }
+
         /// Constructs a DrawMode that fills shapes
         pub fn fill() -> DrawMode {
             DrawMode::Fill(FillOptions::default())
@@ -1,6 +1,24 @@
 pub(crate) mod input_handler;
 
+mod bindings;
+pub mod gamepad;
 pub mod keyboard;
 pub mod mouse;
+mod policy;
+mod recording;
 
-pub use self::{input_handler::MouseButton, keyboard::KeyboardContext, mouse::MouseContext};
+pub use self::{
+    bindings::{
+        action_value, bindings, bindings_mut, is_action_pressed, set_bindings, Bindings,
+        InputSource,
+    },
+    input_handler::MouseButton,
+    keyboard::KeyboardContext,
+    mouse::MouseContext,
+    policy::{event_policy, set_event_policy, EventPolicy},
+    recording::{
+        is_playing, is_recording, play, start_recording, stop_playing, stop_recording, InputEvent,
+        InputLog, RecordedEvent,
+    },
+};
+pub(crate) use self::{policy::EventQueue, recording::InputRecorder};
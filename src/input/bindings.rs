@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use super::{
+    gamepad::{Axis, Button},
+    keyboard::{self, KeyCode},
+    mouse::{self, MouseButton},
+};
+use crate::Context;
+
+/// How far an axis has to be pushed for its action to count as pressed.
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Something the player can press or push, that an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputSource {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button of any connected gamepad.
+    GamepadButton(Button),
+    /// An axis of any connected gamepad.
+    GamepadAxis(Axis),
+}
+
+impl From<KeyCode> for InputSource {
+    fn from(key: KeyCode) -> InputSource {
+        InputSource::Key(key)
+    }
+}
+
+impl From<MouseButton> for InputSource {
+    fn from(button: MouseButton) -> InputSource {
+        InputSource::Mouse(button)
+    }
+}
+
+impl From<Button> for InputSource {
+    fn from(button: Button) -> InputSource {
+        InputSource::GamepadButton(button)
+    }
+}
+
+impl From<Axis> for InputSource {
+    fn from(axis: Axis) -> InputSource {
+        InputSource::GamepadAxis(axis)
+    }
+}
+
+impl InputSource {
+    /// `1.0` for a held key or button, `0.0` otherwise, the position of an axis.
+    ///
+    /// Gamepads always read `0.0`, see the [`gamepad`](../gamepad/index.html) module.
+    fn value(&self, ctx: &Context) -> f32 {
+        let pressed = match *self {
+            InputSource::Key(key) => keyboard::is_key_pressed(ctx, keyboard::key_name(key)),
+            InputSource::Mouse(button) => mouse::button_pressed(ctx, button),
+            InputSource::GamepadButton(_) => false,
+            InputSource::GamepadAxis(_) => return 0.0,
+        };
+        if pressed {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Named actions, like `"jump"` or `"move_x"`, and the inputs they're bound
+/// to, so game code asks for actions and players can rebind them.
+///
+/// Gamepad buttons and axes can be bound already, but always read `0.0`:
+/// gamepads aren't handled yet, see the [`gamepad`](gamepad/index.html) module.
+///
+/// Each input has a scale its value is multiplied by, which makes a pair of
+/// keys into an axis:
+///
+/// ```rust,ignore
+/// let mut bindings = Bindings::new();
+/// bindings.bind("jump", KeyCode::Space);
+/// bindings.bind("jump", gamepad::Button::South);
+/// bindings.bind_scaled("move_x", KeyCode::Left, -1.0);
+/// bindings.bind_scaled("move_x", KeyCode::Right, 1.0);
+/// bindings.bind("move_x", gamepad::Axis::LeftStickX);
+/// input::set_bindings(ctx, bindings);
+///
+/// // in update
+/// if input::is_action_pressed(ctx, "jump") { .. }
+/// self.x += input::action_value(ctx, "move_x") * SPEED;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    actions: HashMap<String, Vec<(InputSource, f32)>>,
+}
+
+impl Bindings {
+    pub fn new() -> Bindings {
+        Bindings::default()
+    }
+
+    /// Binds `input` to `action`, on top of the inputs already bound to it.
+    pub fn bind<I: Into<InputSource>>(&mut self, action: &str, input: I) {
+        self.bind_scaled(action, input, 1.0);
+    }
+
+    /// Binds `input` to `action` with its value multiplied by `scale`,
+    /// replacing the scale if it's already bound.
+    pub fn bind_scaled<I: Into<InputSource>>(&mut self, action: &str, input: I, scale: f32) {
+        let input = input.into();
+        let inputs = self.actions.entry(action.to_string()).or_default();
+        match inputs.iter_mut().find(|(bound, _)| *bound == input) {
+            Some(binding) => binding.1 = scale,
+            None => inputs.push((input, scale)),
+        }
+    }
+
+    /// Unbinds `input` from `action`.
+    pub fn unbind<I: Into<InputSource>>(&mut self, action: &str, input: I) {
+        let input = input.into();
+        if let Some(inputs) = self.actions.get_mut(action) {
+            inputs.retain(|(bound, _)| *bound != input);
+        }
+    }
+
+    /// Replaces `old` with `new` in the inputs of `action`, keeping its scale,
+    /// for a rebinding menu. Binds `new` if `old` wasn't bound.
+    pub fn rebind<I: Into<InputSource>, J: Into<InputSource>>(
+        &mut self,
+        action: &str,
+        old: I,
+        new: J,
+    ) {
+        let old = old.into();
+        let scale = self.scale(action, old).unwrap_or(1.0);
+        self.unbind(action, old);
+        self.bind_scaled(action, new, scale);
+    }
+
+    /// Unbinds all the inputs of `action`.
+    pub fn clear(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    /// The inputs bound to `action` and their scales, in the order they were bound.
+    pub fn inputs(&self, action: &str) -> &[(InputSource, f32)] {
+        self.actions.get(action).map_or(&[], |inputs| &inputs[..])
+    }
+
+    /// The actions with at least one input bound, in no particular order.
+    pub fn actions(&self) -> Vec<&str> {
+        self.actions
+            .iter()
+            .filter(|(_, inputs)| !inputs.is_empty())
+            .map(|(action, _)| action.as_str())
+            .collect()
+    }
+
+    fn scale(&self, action: &str, input: InputSource) -> Option<f32> {
+        self.inputs(action)
+            .iter()
+            .find(|(bound, _)| *bound == input)
+            .map(|(_, scale)| *scale)
+    }
+
+    fn value(&self, ctx: &Context, action: &str) -> f32 {
+        let value: f32 = self
+            .inputs(action)
+            .iter()
+            .map(|(input, scale)| input.value(ctx) * scale)
+            .sum();
+        value.max(-1.0).min(1.0)
+    }
+
+    fn is_pressed(&self, ctx: &Context, action: &str) -> bool {
+        self.inputs(action)
+            .iter()
+            .any(|(input, _)| input.value(ctx).abs() >= AXIS_PRESS_THRESHOLD)
+    }
+}
+
+/// Replaces the bindings actions are looked up in, there are none by default.
+pub fn set_bindings(ctx: &mut Context, bindings: Bindings) {
+    ctx.input_bindings = bindings;
+}
+
+/// The bindings actions are looked up in, see [`Bindings`](struct.Bindings.html).
+pub fn bindings(ctx: &Context) -> &Bindings {
+    &ctx.input_bindings
+}
+
+/// The current bindings, to rebind actions while the game runs.
+pub fn bindings_mut(ctx: &mut Context) -> &mut Bindings {
+    &mut ctx.input_bindings
+}
+
+/// Checks if any input bound to `action` is held down, or, for an axis,
+/// pushed at least halfway in either direction.
+/// Actions with no inputs bound are never pressed.
+pub fn is_action_pressed(ctx: &Context, action: &str) -> bool {
+    ctx.input_bindings.is_pressed(ctx, action)
+}
+
+/// The sum of the values of the inputs bound to `action` times their scale,
+/// clamped to `[-1, 1]`: `1.0` while a key bound with a scale of `1.0` is
+/// held, the position of a bound axis. Actions with no inputs bound are `0.0`.
+pub fn action_value(ctx: &Context, action: &str) -> f32 {
+    ctx.input_bindings.value(ctx, action)
+}
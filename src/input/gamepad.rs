@@ -0,0 +1,40 @@
+//! The gamepad buttons and axes input bindings can name.
+//!
+//! Gamepads are not handled yet, neither input nor rumble: miniquad does not
+//! report gamepad events on any platform, and reaches neither the web
+//! `GamepadHapticActuator` API nor desktop force feedback.
+
+/// A gamepad button, named after its position on a standard layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    /// A on Xbox, cross on PlayStation.
+    South,
+    /// B on Xbox, circle on PlayStation.
+    East,
+    /// X on Xbox, square on PlayStation.
+    West,
+    /// Y on Xbox, triangle on PlayStation.
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftStick,
+    RightStick,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// A gamepad axis. Sticks go from `-1` to `1`, right and down being
+/// positive, triggers from `0` to `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
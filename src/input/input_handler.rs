@@ -29,6 +29,8 @@ pub struct InputHandler {
     pub keys: HashSet<String>,
     pub frame_keys: HashSet<String>,
     pub mouse_position: Point2<f64>,
+    /// The mouse position as miniquad reported it, in device pixels.
+    pub mouse_position_pixels: Point2<f64>,
     pub mouse_keys: HashSet<MouseButton>,
     pub wheel: f32,
 }
@@ -39,12 +41,13 @@ impl InputHandler {
             keys: HashSet::new(),
             frame_keys: HashSet::new(),
             mouse_position: Point2::new(0., 0.),
+            mouse_position_pixels: Point2::new(0., 0.),
             mouse_keys: HashSet::new(),
             wheel: 0.,
         }
     }
 
-    pub fn handle_mouse_move(&mut self, mouse_x: i32, mouse_y: i32) {
+    pub fn handle_mouse_move(&mut self, mouse_x: f32, mouse_y: f32) {
         let mouse = Point2::new(mouse_x as f64, mouse_y as f64);
 
         self.mouse_position = mouse;
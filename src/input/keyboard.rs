@@ -5,13 +5,28 @@ use std::rc::Rc;
 use super::input_handler::InputHandler;
 use crate::Context;
 
+pub use miniquad::KeyCode;
+
 pub struct KeyboardContext {
     pub input_handler: Rc<RefCell<InputHandler>>,
+    /// See `has_focus`.
+    pub(crate) focused: bool,
+    /// Set with `set_require_focus`.
+    pub(crate) require_focus: bool,
 }
 
 impl KeyboardContext {
     pub(crate) fn new(input_handler: Rc<RefCell<InputHandler>>) -> Self {
-        KeyboardContext { input_handler }
+        KeyboardContext {
+            input_handler,
+            focused: true,
+            require_focus: false,
+        }
+    }
+
+    /// Whether a key press should reach the event handler.
+    pub(crate) fn accepts_key_down(&self) -> bool {
+        self.focused || !self.require_focus
     }
 
     pub(crate) fn is_key_pressed(&self, key: &str) -> bool {
@@ -23,7 +38,8 @@ impl KeyboardContext {
     }
 }
 
-/// Checks if a key is currently pressed down.
+/// Checks if a key is currently pressed down, `key` being its
+/// [`key_name()`](fn.key_name.html), like `"Space"` or `"A"`.
 pub fn is_key_pressed(ctx: &Context, key: &str) -> bool {
     ctx.keyboard_context.is_key_pressed(key)
 }
@@ -33,6 +49,30 @@ pub fn is_key_down(ctx: &Context, key: &str) -> bool {
     ctx.keyboard_context.is_key_down(key)
 }
 
+/// Returns true if the game is believed to have the keyboard focus.
+///
+/// Browsers give no focus events to the game, so this is a guess from the
+/// events it does get: the game gets the focus when clicked or touched,
+/// when shown again after being hidden, and with
+/// [`graphics::request_focus()`](../../graphics/fn.request_focus.html);
+/// it loses it when hidden, like when switching tabs. Clicking a text input
+/// elsewhere on the page goes unnoticed. It is true at startup.
+pub fn has_focus(ctx: &Context) -> bool {
+    ctx.keyboard_context.focused
+}
+
+/// Drops key presses while the game does not have the focus, see
+/// [`has_focus()`](fn.has_focus.html), so typing in other parts of a web
+/// page does not move the player. Key releases are always delivered,
+/// no key stays held. Off by default.
+pub fn set_require_focus(ctx: &mut Context, require: bool) {
+    ctx.keyboard_context.require_focus = require;
+}
+
+pub fn require_focus(ctx: &Context) -> bool {
+    ctx.keyboard_context.require_focus
+}
+
 bitflags! {
     pub struct KeyMods: u8 {
         const NONE = 0;
@@ -63,3 +103,201 @@ impl KeyMods {
     //     keymods
     // }
 }
+
+/// Returns the character a key types with the given modifiers,
+/// or `None` for keys that type nothing, like `Escape` or arrows.
+///
+/// This assumes a US QWERTY layout, real layouts differ: use it to
+/// display key bindings, not to read text.
+pub fn key_to_char(keycode: KeyCode, keymods: KeyMods) -> Option<char> {
+    use KeyCode::*;
+
+    let shift = keymods.contains(KeyMods::SHIFT);
+    let (plain, shifted) = match keycode {
+        A => ('a', 'A'),
+        B => ('b', 'B'),
+        C => ('c', 'C'),
+        D => ('d', 'D'),
+        E => ('e', 'E'),
+        F => ('f', 'F'),
+        G => ('g', 'G'),
+        H => ('h', 'H'),
+        I => ('i', 'I'),
+        J => ('j', 'J'),
+        K => ('k', 'K'),
+        L => ('l', 'L'),
+        M => ('m', 'M'),
+        N => ('n', 'N'),
+        O => ('o', 'O'),
+        P => ('p', 'P'),
+        Q => ('q', 'Q'),
+        R => ('r', 'R'),
+        S => ('s', 'S'),
+        T => ('t', 'T'),
+        U => ('u', 'U'),
+        V => ('v', 'V'),
+        W => ('w', 'W'),
+        X => ('x', 'X'),
+        Y => ('y', 'Y'),
+        Z => ('z', 'Z'),
+        Key0 => ('0', ')'),
+        Key1 => ('1', '!'),
+        Key2 => ('2', '@'),
+        Key3 => ('3', '#'),
+        Key4 => ('4', '$'),
+        Key5 => ('5', '%'),
+        Key6 => ('6', '^'),
+        Key7 => ('7', '&'),
+        Key8 => ('8', '*'),
+        Key9 => ('9', '('),
+        Space => (' ', ' '),
+        Apostrophe => ('\'', '"'),
+        Comma => (',', '<'),
+        Minus => ('-', '_'),
+        Period => ('.', '>'),
+        Slash => ('/', '?'),
+        Semicolon => (';', ':'),
+        Equal => ('=', '+'),
+        LeftBracket => ('[', '{'),
+        Backslash => ('\\', '|'),
+        RightBracket => (']', '}'),
+        GraveAccent => ('`', '~'),
+        Kp0 => ('0', '0'),
+        Kp1 => ('1', '1'),
+        Kp2 => ('2', '2'),
+        Kp3 => ('3', '3'),
+        Kp4 => ('4', '4'),
+        Kp5 => ('5', '5'),
+        Kp6 => ('6', '6'),
+        Kp7 => ('7', '7'),
+        Kp8 => ('8', '8'),
+        Kp9 => ('9', '9'),
+        KpDecimal => ('.', '.'),
+        KpDivide => ('/', '/'),
+        KpMultiply => ('*', '*'),
+        KpSubtract => ('-', '-'),
+        KpAdd => ('+', '+'),
+        KpEqual => ('=', '='),
+        _ => return None,
+    };
+
+    Some(if shift { shifted } else { plain })
+}
+
+/// Returns a human readable name for the key, like `"Space"`, `"Enter"`
+/// or `"F1"`; printable keys are named by their unshifted character
+/// (see [`key_to_char()`](fn.key_to_char.html)), e.g. `"A"` or `"-"`.
+///
+/// Names assume a US QWERTY layout.
+pub fn key_name(keycode: KeyCode) -> &'static str {
+    use KeyCode::*;
+
+    match keycode {
+        Space => "Space",
+        Apostrophe => "'",
+        Comma => ",",
+        Minus => "-",
+        Period => ".",
+        Slash => "/",
+        Key0 => "0",
+        Key1 => "1",
+        Key2 => "2",
+        Key3 => "3",
+        Key4 => "4",
+        Key5 => "5",
+        Key6 => "6",
+        Key7 => "7",
+        Key8 => "8",
+        Key9 => "9",
+        Semicolon => ";",
+        Equal => "=",
+        A => "A",
+        B => "B",
+        C => "C",
+        D => "D",
+        E => "E",
+        F => "F",
+        G => "G",
+        H => "H",
+        I => "I",
+        J => "J",
+        K => "K",
+        L => "L",
+        M => "M",
+        N => "N",
+        O => "O",
+        P => "P",
+        Q => "Q",
+        R => "R",
+        S => "S",
+        T => "T",
+        U => "U",
+        V => "V",
+        W => "W",
+        X => "X",
+        Y => "Y",
+        Z => "Z",
+        LeftBracket => "[",
+        Backslash => "\\",
+        RightBracket => "]",
+        GraveAccent => "`",
+        Escape => "Escape",
+        Enter => "Enter",
+        Tab => "Tab",
+        Backspace => "Backspace",
+        Insert => "Insert",
+        Delete => "Delete",
+        Right => "Right",
+        Left => "Left",
+        Down => "Down",
+        Up => "Up",
+        PageUp => "Page Up",
+        PageDown => "Page Down",
+        Home => "Home",
+        End => "End",
+        CapsLock => "Caps Lock",
+        ScrollLock => "Scroll Lock",
+        NumLock => "Num Lock",
+        PrintScreen => "Print Screen",
+        Pause => "Pause",
+        F1 => "F1",
+        F2 => "F2",
+        F3 => "F3",
+        F4 => "F4",
+        F5 => "F5",
+        F6 => "F6",
+        F7 => "F7",
+        F8 => "F8",
+        F9 => "F9",
+        F10 => "F10",
+        F11 => "F11",
+        F12 => "F12",
+        Kp0 => "Numpad 0",
+        Kp1 => "Numpad 1",
+        Kp2 => "Numpad 2",
+        Kp3 => "Numpad 3",
+        Kp4 => "Numpad 4",
+        Kp5 => "Numpad 5",
+        Kp6 => "Numpad 6",
+        Kp7 => "Numpad 7",
+        Kp8 => "Numpad 8",
+        Kp9 => "Numpad 9",
+        KpDecimal => "Numpad .",
+        KpDivide => "Numpad /",
+        KpMultiply => "Numpad *",
+        KpSubtract => "Numpad -",
+        KpAdd => "Numpad +",
+        KpEnter => "Numpad Enter",
+        KpEqual => "Numpad =",
+        LeftShift => "Left Shift",
+        LeftControl => "Left Ctrl",
+        LeftAlt => "Left Alt",
+        LeftSuper => "Left Super",
+        RightShift => "Right Shift",
+        RightControl => "Right Ctrl",
+        RightAlt => "Right Alt",
+        RightSuper => "Right Super",
+        Menu => "Menu",
+        _ => "Unknown",
+    }
+}
@@ -36,6 +36,23 @@ pub fn position(ctx: &Context) -> cgmath::Point2<f32> {
     ctx.mouse_context.mouse_position()
 }
 
+/// The mouse position in device pixels, as reported by the window or the
+/// browser, before the conversion to the logical pixels of
+/// [`position()`](fn.position.html).
+///
+/// On high-DPI displays it is more precise than `position()`, which is
+/// `f32` and divided by the DPI scale; use it for pixel-exact hit-testing.
+/// During an input replay it is computed back from the recorded logical
+/// position.
+pub fn position_pixels(ctx: &Context) -> (f64, f64) {
+    let position = ctx
+        .mouse_context
+        .input_handler
+        .borrow()
+        .mouse_position_pixels;
+    (position.x, position.y)
+}
+
 pub fn button_pressed(ctx: &Context, button: MouseButton) -> bool {
     ctx.mouse_context.button_pressed(button)
 }
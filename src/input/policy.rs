@@ -0,0 +1,105 @@
+use super::InputEvent;
+use crate::Context;
+
+/// How the input events received between two frames are delivered to the
+/// [`EventHandler`](../event/trait.EventHandler.html), see
+/// [`set_event_policy()`](fn.set_event_policy.html).
+///
+/// The default delivers every event as soon as it is received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EventPolicy {
+    /// Only the last mouse motion of a frame is delivered.
+    pub coalesce_mouse_motion: bool,
+    /// At most that many key presses, repeats included, are delivered per
+    /// frame, the following ones are dropped. Key releases are always
+    /// delivered, so no key stays held.
+    pub max_key_downs: Option<usize>,
+}
+
+impl EventPolicy {
+    /// Delivers every event as soon as it is received, the default.
+    pub fn deliver_all() -> EventPolicy {
+        EventPolicy::default()
+    }
+
+    /// Set whether only the last mouse motion of a frame is delivered.
+    pub fn coalesce_mouse_motion(mut self, coalesce: bool) -> Self {
+        self.coalesce_mouse_motion = coalesce;
+        self
+    }
+
+    /// Set the maximum number of key presses delivered per frame.
+    pub fn max_key_downs(mut self, max: Option<usize>) -> Self {
+        self.max_key_downs = max;
+        self
+    }
+
+    fn delivers_all(&self) -> bool {
+        *self == EventPolicy::deliver_all()
+    }
+}
+
+/// Sets how input events are delivered, to tame the bursts of events a slow
+/// frame can bring, mostly on web: a whole second of lag delivers a whole
+/// second of key presses in a row, which may make the player jump twice.
+///
+/// Unless every event is delivered, the events of a frame are held until
+/// right before [`update()`](../event/trait.EventHandler.html#tymethod.update)
+/// and delivered together, after the policy filtered them. The polled state,
+/// like [`keyboard::is_key_pressed()`](keyboard/fn.is_key_pressed.html),
+/// follows the delivered events. Input recordings hold the delivered events.
+///
+/// ```rust,ignore
+/// input::set_event_policy(
+///     ctx,
+///     EventPolicy::default().coalesce_mouse_motion(true).max_key_downs(Some(4)),
+/// );
+/// ```
+pub fn set_event_policy(ctx: &mut Context, policy: EventPolicy) {
+    ctx.event_policy = policy;
+}
+
+pub fn event_policy(ctx: &Context) -> EventPolicy {
+    ctx.event_policy
+}
+
+/// The events received since the last frame, held to be filtered by the
+/// `EventPolicy`.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    events: Vec<InputEvent>,
+}
+
+impl EventQueue {
+    /// Holds `event` until the next frame, returning it back if it should
+    /// be delivered right away.
+    pub fn push(&mut self, policy: EventPolicy, event: InputEvent) -> Option<InputEvent> {
+        if policy.delivers_all() && self.events.is_empty() {
+            return Some(event);
+        }
+
+        if let InputEvent::MouseMotion { .. } = event {
+            if policy.coalesce_mouse_motion {
+                self.events
+                    .retain(|event| !matches!(event, InputEvent::MouseMotion { .. }));
+            }
+        }
+        self.events.push(event);
+        None
+    }
+
+    /// The events of the frame to deliver, in the order they were received.
+    pub fn drain(&mut self, policy: EventPolicy) -> Vec<InputEvent> {
+        let mut key_downs = 0;
+        self.events
+            .drain(..)
+            .filter(|event| match event {
+                InputEvent::KeyDown { .. } => {
+                    key_downs += 1;
+                    policy.max_key_downs.map_or(true, |max| key_downs <= max)
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
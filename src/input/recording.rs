@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use crate::{
+    event::{KeyCode, KeyMods, MouseButton, TouchPhase},
+    timer, Context,
+};
+
+/// An input event, with positions in screen coordinates,
+/// as given to the [`EventHandler`](../event/trait.EventHandler.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    KeyDown {
+        keycode: KeyCode,
+        keymods: KeyMods,
+        repeat: bool,
+    },
+    KeyUp {
+        keycode: KeyCode,
+        keymods: KeyMods,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    MouseMotion {
+        x: f32,
+        y: f32,
+    },
+    Touch {
+        phase: TouchPhase,
+        id: u64,
+        x: f32,
+        y: f32,
+    },
+}
+
+/// An input event of an [`InputLog`](struct.InputLog.html), stamped
+/// with when it happened, relative to the start of the recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedEvent {
+    /// Number of frames (`update()` calls) before the event.
+    pub frame: usize,
+    /// Time before the event, for information only: replay goes by `frame`.
+    pub time: Duration,
+    pub event: InputEvent,
+}
+
+/// Input events recorded with [`start_recording()`](fn.start_recording.html),
+/// in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputLog {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl InputLog {
+    /// Number of frames until the last event.
+    pub fn frames(&self) -> usize {
+        self.events.last().map_or(0, |event| event.frame)
+    }
+}
+
+/// Recording and playback state, one of each at most.
+#[derive(Debug, Default)]
+pub(crate) struct InputRecorder {
+    recording: Option<Recording>,
+    playback: Option<Playback>,
+}
+
+#[derive(Debug)]
+struct Recording {
+    start_frame: usize,
+    start_time: Duration,
+    log: InputLog,
+}
+
+#[derive(Debug)]
+struct Playback {
+    start_frame: usize,
+    log: InputLog,
+    next: usize,
+}
+
+impl InputRecorder {
+    /// Adds an event dispatched to the event handler to the recording, if any.
+    pub(crate) fn record(&mut self, frame: usize, time: Duration, event: InputEvent) {
+        if let Some(ref mut recording) = self.recording {
+            recording.log.events.push(RecordedEvent {
+                frame: frame - recording.start_frame,
+                time: time - recording.start_time,
+                event,
+            });
+        }
+    }
+
+    pub(crate) fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// The events to replay before the `update()` of the given frame.
+    /// Playback stops once they are all replayed.
+    pub(crate) fn due_events(&mut self, frame: usize) -> Vec<InputEvent> {
+        let mut events = vec![];
+        if let Some(ref mut playback) = self.playback {
+            let frame = frame - playback.start_frame;
+            while let Some(recorded) = playback.log.events.get(playback.next) {
+                if recorded.frame > frame {
+                    break;
+                }
+                events.push(recorded.event);
+                playback.next += 1;
+            }
+            if playback.next == playback.log.events.len() {
+                self.playback = None;
+            }
+        }
+        events
+    }
+}
+
+/// Starts recording every input event given to the event handler,
+/// dropping any recording in progress.
+///
+/// Events are stamped with the frame they happen in, so replaying them
+/// with [`play()`](fn.play.html) reproduces the session as long as the game
+/// itself is deterministic, like with a fixed timestep and a seeded RNG.
+pub fn start_recording(ctx: &mut Context) {
+    ctx.input_recorder.recording = Some(Recording {
+        start_frame: timer::ticks(ctx),
+        start_time: timer::time_since_start(ctx),
+        log: InputLog::default(),
+    });
+}
+
+/// Stops recording and returns the recorded events,
+/// empty if there was no recording.
+pub fn stop_recording(ctx: &mut Context) -> InputLog {
+    ctx.input_recorder
+        .recording
+        .take()
+        .map(|recording| recording.log)
+        .unwrap_or_default()
+}
+
+/// Returns true while recording, see [`start_recording()`](fn.start_recording.html).
+pub fn is_recording(ctx: &Context) -> bool {
+    ctx.input_recorder.recording.is_some()
+}
+
+/// Replays `log` through the event handler callbacks, starting on the next
+/// frame. Each event is sent right before the `update()` of its frame, just
+/// like live events that arrived during the previous frame.
+///
+/// Live input is ignored until the last event is replayed, so it can't
+/// interfere. Replayed events are recorded like live ones if recording.
+pub fn play(ctx: &mut Context, log: &InputLog) {
+    ctx.input_recorder.playback = Some(Playback {
+        start_frame: timer::ticks(ctx),
+        log: log.clone(),
+        next: 0,
+    });
+}
+
+/// Returns true while replaying a log, see [`play()`](fn.play.html).
+pub fn is_playing(ctx: &Context) -> bool {
+    ctx.input_recorder.is_playing()
+}
+
+/// Stops replaying, live input is handled again.
+pub fn stop_playing(ctx: &mut Context) {
+    ctx.input_recorder.playback = None;
+}
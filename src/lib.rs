@@ -16,6 +16,8 @@ pub use crate::{
 };
 pub use cgmath;
 
+use crate::input::{EventQueue, InputEvent};
+
 #[cfg(feature = "log-impl")]
 pub use miniquad::{debug, info, log, warn};
 
@@ -77,11 +79,26 @@ pub mod rand {
 struct EventHandlerWrapper {
     event_handler: Box<dyn event::EventHandler>,
     context: Context,
+    /// Live events held until the next frame, see `input::set_event_policy`.
+    pending_events: EventQueue,
 }
 
 impl miniquad::EventHandlerFree for EventHandlerWrapper {
     fn update(&mut self) {
+        for event in self.pending_events.drain(self.context.event_policy) {
+            self.dispatch(event);
+        }
+        let frame = timer::ticks(&self.context);
+        for event in self.context.input_recorder.due_events(frame) {
+            self.dispatch(event);
+        }
         self.event_handler.update(&mut self.context).unwrap();
+        self.context
+            .keyboard_context
+            .input_handler
+            .borrow_mut()
+            .handle_end_frame();
+        audio::update_fades(&mut self.context);
         if let Some(ref mut mixer) = &mut *self.context.audio_context.mixer.borrow_mut() {
             mixer.frame();
         }
@@ -89,10 +106,42 @@ impl miniquad::EventHandlerFree for EventHandlerWrapper {
     }
 
     fn draw(&mut self) {
-        self.event_handler.draw(&mut self.context).unwrap();
+        let dirty = graphics::dirty::begin_frame(&mut self.context);
+        if dirty {
+            if let Some(color) = graphics::clear_color(&self.context) {
+                graphics::clear(&mut self.context, color);
+            }
+            self.event_handler.draw(&mut self.context).unwrap();
+        }
+        graphics::dirty::end_frame(&mut self.context, dirty);
+        // the buffers are swapped once this returns, present whatever was drawn
+        graphics::present(&mut self.context).unwrap();
+        self.context.gfx_context.frame_presented = false;
+        self.event_handler.after_present(&mut self.context);
+
+        if self.context.quit_requested {
+            self.context.quit_requested = false;
+            if !self.event_handler.quit_event(&mut self.context) {
+                self.context.quad_ctx.quit();
+            }
+        }
     }
 
     fn resize_event(&mut self, width: f32, height: f32) {
+        if self.context.gfx_context.frame.is_some() {
+            match graphics::frame::create_frame(&mut self.context, false) {
+                Ok(()) => graphics::mark_all_dirty(&mut self.context),
+                Err(_error) => {
+                    // draw straight to the screen rather than stop the game,
+                    // without the kept frame dirty tracking can't work either
+                    #[cfg(feature = "log-impl")]
+                    warn!("Failed to resize the offscreen frame: {:?}", _error);
+                    self.context.gfx_context.frame = None;
+                    self.context.gfx_context.dirty = None;
+                }
+            }
+        }
+        let (width, height) = self.to_logical(width, height);
         self.event_handler
             .resize_event(&mut self.context, width, height);
     }
@@ -103,40 +152,151 @@ impl miniquad::EventHandlerFree for EventHandlerWrapper {
         _keymods: miniquad::KeyMods,
         repeat: bool,
     ) {
-        self.event_handler.key_down_event(
-            &mut self.context,
-            keycode.into(),
-            crate::input::keyboard::KeyMods::NONE,
+        if !self.context.keyboard_context.accepts_key_down() {
+            return;
+        }
+        self.live_event(InputEvent::KeyDown {
+            keycode: keycode.into(),
+            keymods: crate::input::keyboard::KeyMods::NONE,
             repeat,
-        );
+        });
     }
 
     fn key_up_event(&mut self, keycode: miniquad::KeyCode, _keymods: miniquad::KeyMods) {
-        self.event_handler.key_up_event(
-            &mut self.context,
-            keycode.into(),
-            crate::input::keyboard::KeyMods::NONE,
-        );
+        self.live_event(InputEvent::KeyUp {
+            keycode: keycode.into(),
+            keymods: crate::input::keyboard::KeyMods::NONE,
+        });
     }
 
     fn mouse_button_down_event(&mut self, button: miniquad::MouseButton, x: f32, y: f32) {
-        self.event_handler
-            .mouse_button_down_event(&mut self.context, button.into(), x, y);
+        self.context.keyboard_context.focused = true;
+        let (x, y) = self.to_logical(x, y);
+        self.live_event(InputEvent::MouseButtonDown {
+            button: button.into(),
+            x,
+            y,
+        });
     }
 
     fn mouse_button_up_event(&mut self, button: miniquad::MouseButton, x: f32, y: f32) {
-        self.event_handler
-            .mouse_button_up_event(&mut self.context, button.into(), x, y);
+        let (x, y) = self.to_logical(x, y);
+        self.live_event(InputEvent::MouseButtonUp {
+            button: button.into(),
+            x,
+            y,
+        });
     }
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
-        self.event_handler
-            .mouse_motion_event(&mut self.context, x, y, 0., 0.);
+        if !self.context.input_recorder.is_playing() {
+            let mut input_handler = self.context.mouse_context.input_handler.borrow_mut();
+            input_handler.mouse_position_pixels = cgmath::Point2::new(x as f64, y as f64);
+        }
+        let (x, y) = self.to_logical(x, y);
+        self.live_event(InputEvent::MouseMotion { x, y });
     }
 
     fn touch_event(&mut self, phase: miniquad::TouchPhase, id: u64, x: f32, y: f32) {
-        self.event_handler
-            .touch_event(&mut self.context, phase, id, x, y);
+        if let miniquad::TouchPhase::Started = phase {
+            self.context.keyboard_context.focused = true;
+        }
+        let (x, y) = self.to_logical(x, y);
+        self.live_event(InputEvent::Touch { phase, id, x, y });
+    }
+
+    fn window_minimized_event(&mut self) {
+        self.context.keyboard_context.focused = false;
+        self.event_handler.focus_event(&mut self.context, false);
+    }
+
+    fn window_restored_event(&mut self) {
+        self.context.timer_context.resume();
+        self.context.keyboard_context.focused = true;
+        self.event_handler.focus_event(&mut self.context, true);
+    }
+}
+
+impl EventHandlerWrapper {
+    /// Dispatches an event from miniquad, unless a recorded log is replayed,
+    /// right away or on the next frame, depending on the event policy.
+    fn live_event(&mut self, event: InputEvent) {
+        if !self.context.input_recorder.is_playing() {
+            let policy = self.context.event_policy;
+            if let Some(event) = self.pending_events.push(policy, event) {
+                self.dispatch(event);
+            }
+        }
+    }
+
+    /// Sends an input event to the event handler, recording it if asked to.
+    fn dispatch(&mut self, event: InputEvent) {
+        let frame = timer::ticks(&self.context);
+        let time = timer::time_since_start(&self.context);
+        self.context.input_recorder.record(frame, time, event);
+
+        let ctx = &mut self.context;
+        match event {
+            InputEvent::KeyDown {
+                keycode,
+                keymods,
+                repeat,
+            } => {
+                let key = input::keyboard::key_name(keycode).to_string();
+                ctx.keyboard_context
+                    .input_handler
+                    .borrow_mut()
+                    .handle_key_down(key);
+                self.event_handler
+                    .key_down_event(ctx, keycode, keymods, repeat)
+            }
+            InputEvent::KeyUp { keycode, keymods } => {
+                let key = input::keyboard::key_name(keycode).to_string();
+                ctx.keyboard_context
+                    .input_handler
+                    .borrow_mut()
+                    .handle_key_up(key);
+                self.event_handler.key_up_event(ctx, keycode, keymods)
+            }
+            InputEvent::MouseButtonDown { button, x, y } => {
+                ctx.mouse_context
+                    .input_handler
+                    .borrow_mut()
+                    .mouse_keys
+                    .insert(button);
+                self.event_handler
+                    .mouse_button_down_event(ctx, button, x, y)
+            }
+            InputEvent::MouseButtonUp { button, x, y } => {
+                ctx.mouse_context
+                    .input_handler
+                    .borrow_mut()
+                    .mouse_keys
+                    .remove(&button);
+                self.event_handler.mouse_button_up_event(ctx, button, x, y)
+            }
+            InputEvent::MouseMotion { x, y } => {
+                let dpi_scale = graphics::dpi_scale(ctx) as f64;
+                let mut input_handler = ctx.mouse_context.input_handler.borrow_mut();
+                input_handler.handle_mouse_move(x, y);
+                if ctx.input_recorder.is_playing() {
+                    input_handler.mouse_position_pixels =
+                        cgmath::Point2::new(x as f64 * dpi_scale, y as f64 * dpi_scale);
+                }
+                drop(input_handler);
+                self.event_handler.mouse_motion_event(ctx, x, y, 0., 0.);
+            }
+            InputEvent::Touch { phase, id, x, y } => {
+                self.event_handler.touch_event(ctx, phase, id, x, y)
+            }
+        }
+    }
+
+    /// miniquad reports positions in physical pixels, while the screen
+    /// coordinates are set up in logical ones.
+    fn to_logical(&self, x: f32, y: f32) -> (f32, f32) {
+        let dpi_scale = graphics::dpi_scale(&self.context);
+        (x / dpi_scale, y / dpi_scale)
     }
 }
 
@@ -144,19 +304,44 @@ pub fn start<F>(conf: conf::Conf, f: F) -> GameResult
 where
     F: 'static + FnOnce(&mut Context) -> Box<dyn EventHandler>,
 {
-    miniquad::start(miniquad::conf::Conf::default(), |ctx| {
+    let quad_conf = miniquad::conf::Conf {
+        high_dpi: conf.window_mode.high_dpi,
+        ..Default::default()
+    };
+
+    let srgb = conf.window_mode.srgb;
+    #[cfg(feature = "log-impl")]
+    let preferred_backend = conf.preferred_backend;
+
+    miniquad::start(quad_conf, move |ctx| {
         let mut context = Context::new(ctx, conf);
 
-        let (w, h) = context.quad_ctx.screen_size();
+        // the preferred backend is only checked, and only to log a warning
+        #[cfg(feature = "log-impl")]
+        {
+            let backend = graphics::backend_info(&context);
+            if !backend.version.is_empty() && !backend.is(preferred_backend) {
+                warn!(
+                    "{:?} is not available, running on {}",
+                    preferred_backend, backend.version
+                );
+            }
+        }
+
+        let (w, h) = graphics::size(&context);
         context
             .gfx_context
-            .set_screen_coordinates(graphics::Rect::new(0., 0., w as f32, h as f32));
+            .set_screen_coordinates(graphics::Rect::new(0., 0., w, h));
+        if srgb {
+            graphics::frame::create_frame(&mut context, true).unwrap();
+        }
 
         let event_handler = f(&mut context);
 
         miniquad::UserData::free(EventHandlerWrapper {
             event_handler,
             context,
+            pending_events: EventQueue::default(),
         })
     });
     Ok(())
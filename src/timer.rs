@@ -16,8 +16,11 @@
 
 use crate::context::Context;
 
+use std::cell::Cell;
 use std::cmp;
 use std::f64;
+use std::fmt;
+use std::rc::Rc;
 use std::time;
 use std::time::Duration;
 
@@ -29,6 +32,71 @@ pub fn time() -> f64 {
     miniquad::date::now()
 }
 
+/// A clock the timer reads the time from, see [`set_time_source()`](fn.set_time_source.html).
+pub trait TimeSource {
+    /// The current time in seconds, from any fixed starting point.
+    /// Must never go backwards.
+    fn now(&self) -> f64;
+}
+
+/// The real clock, the default time source.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> f64 {
+        time()
+    }
+}
+
+/// A clock that only moves when told to, for tests and replays that need
+/// the same frame durations on every run.
+///
+/// Clones share the same time, so keep one to drive the clock given to
+/// [`set_time_source()`](fn.set_time_source.html):
+///
+/// ```rust,ignore
+/// let clock = timer::ManualClock::new();
+/// timer::set_time_source(ctx, clock.clone());
+/// // then, before every frame
+/// clock.advance(Duration::from_millis(16));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<f64>>,
+}
+
+impl ManualClock {
+    /// A clock stopped at 0.
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration_to_f64(duration));
+    }
+
+    /// The time given so far with `advance`.
+    pub fn elapsed(&self) -> Duration {
+        f64_to_duration(self.now.get())
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+}
+
+struct BoxedTimeSource(Box<dyn TimeSource>);
+
+impl fmt::Debug for BoxedTimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimeSource")
+    }
+}
+
 /// A simple buffer that fills
 /// up to a limit and then holds the last
 /// N items that have been inserted into it,
@@ -92,31 +160,73 @@ where
     fn latest(&self) -> T {
         self.contents[self.head]
     }
+
+    /// Returns up to the `n` most recent values, newest first.
+    fn recent(&self, n: usize) -> Vec<T> {
+        let len = self.contents.len();
+        let filled = cmp::min(self.samples, len);
+        (0..cmp::min(n, filled))
+            .map(|i| self.contents[(self.head + len - i) % len])
+            .collect()
+    }
+}
+
+/// How [`delta()`](fn.delta.html) smooths the frame durations it reports,
+/// see [`set_smoothing()`](fn.set_smoothing.html).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SmoothingMode {
+    /// The duration of the last frame as is, the default.
+    None,
+    /// The median of the last `n` frame durations. Ignores single spikes
+    /// entirely, but follows a lasting change of frame rate only once it
+    /// covers half of the window.
+    Median(usize),
+    /// The average of the last `n` frame durations, each capped to `max`
+    /// first so a single long frame can't skew it for the whole window.
+    CappedAverage(usize, Duration),
+}
+
+impl Default for SmoothingMode {
+    fn default() -> Self {
+        SmoothingMode::None
+    }
 }
 
 /// A structure that contains our time-tracking state.
 #[derive(Debug)]
 pub struct TimeContext {
+    source: BoxedTimeSource,
     init_instant: Instant,
     last_instant: Instant,
     frame_durations: LogBuffer<Duration>,
     residual_update_dt: Duration,
     frame_count: usize,
+    /// Set on resume, the next frame duration is clamped to `MAX_RESUME_DELTA`.
+    clamp_next_delta: bool,
+    /// Set with `set_smoothing`.
+    smoothing: SmoothingMode,
 }
 
 // How many frames we log update times for.
 const TIME_LOG_FRAMES: usize = 200;
 
+// The longest frame reported right after the game was resumed, in seconds.
+const MAX_RESUME_DELTA: f64 = 0.1;
+
 impl TimeContext {
     /// Creates a new `TimeContext` and initializes the start to this instant.
     pub fn new() -> TimeContext {
         let initial_dt = time::Duration::from_millis(16);
+        let now = time();
         TimeContext {
-            init_instant: time(),
-            last_instant: time(),
+            source: BoxedTimeSource(Box::new(SystemClock)),
+            init_instant: now,
+            last_instant: now,
             frame_durations: LogBuffer::new(TIME_LOG_FRAMES, initial_dt),
             residual_update_dt: time::Duration::from_secs(0),
             frame_count: 0,
+            clamp_next_delta: false,
+            smoothing: SmoothingMode::None,
         }
     }
 
@@ -128,14 +238,31 @@ impl TimeContext {
     /// It's usually not necessary to call this function yourself,
     /// [`event::run()`](../event/fn.run.html) will do it for you.
     pub fn tick(&mut self) {
-        let now = time();
-        let time_since_last = now - self.last_instant;
+        let now = self.now();
+        let mut time_since_last = now - self.last_instant;
+        if self.clamp_next_delta {
+            self.clamp_next_delta = false;
+            time_since_last = time_since_last.min(MAX_RESUME_DELTA);
+        }
         self.frame_durations.push(f64_to_duration(time_since_last));
         self.last_instant = now;
         self.frame_count += 1;
 
         self.residual_update_dt += f64_to_duration(time_since_last);
     }
+
+    /// Records that the game was resumed after being paused, like when
+    /// a hidden browser tab becomes visible again: the time spent paused will
+    /// not show up as one huge frame.
+    ///
+    /// [`event::run()`](../event/fn.run.html) does this for you.
+    pub fn resume(&mut self) {
+        self.clamp_next_delta = true;
+    }
+
+    fn now(&self) -> Instant {
+        self.source.0.now()
+    }
 }
 
 impl Default for TimeContext {
@@ -146,9 +273,51 @@ impl Default for TimeContext {
 
 /// Get the time between the start of the last frame and the current one;
 /// in other words, the length of the last frame.
+///
+/// Smoothed over the last frames if a smoothing mode is set with
+/// [`set_smoothing()`](fn.set_smoothing.html), see
+/// [`raw_delta()`](fn.raw_delta.html) for the actual length.
 pub fn delta(ctx: &Context) -> Duration {
     let tc = &ctx.timer_context;
-    tc.frame_durations.latest()
+    match tc.smoothing {
+        SmoothingMode::None => tc.frame_durations.latest(),
+        SmoothingMode::Median(n) => {
+            let mut recent = tc.frame_durations.recent(n.max(1));
+            recent.sort();
+            recent[recent.len() / 2]
+        }
+        SmoothingMode::CappedAverage(n, max) => {
+            let recent = tc.frame_durations.recent(n.max(1));
+            let sum: Duration = recent.iter().map(|&d| cmp::min(d, max)).sum();
+            sum / recent.len() as u32
+        }
+    }
+}
+
+/// The length of the last frame, never smoothed.
+pub fn raw_delta(ctx: &Context) -> Duration {
+    ctx.timer_context.frame_durations.latest()
+}
+
+/// Makes [`delta()`](fn.delta.html) report a smoothed frame duration, for
+/// games with a variable timestep whose motion jitters as the frame
+/// durations vary, which they do a lot on web. The window is at most 200
+/// frames.
+///
+/// Smoothing trades accuracy for steadiness: a real change of frame rate
+/// shows up in `delta()` a few frames late, so the game runs a little too
+/// slow or too fast for a while, and the smoothed deltas don't add up to
+/// the time actually elapsed. [`time_since_start()`](fn.time_since_start.html),
+/// [`raw_delta()`](fn.raw_delta.html) and the fixed timestep of
+/// [`check_update_time()`](fn.check_update_time.html) still follow the
+/// real time.
+pub fn set_smoothing(ctx: &mut Context, mode: SmoothingMode) {
+    ctx.timer_context.smoothing = mode;
+}
+
+/// Returns the mode set with [`set_smoothing()`](fn.set_smoothing.html).
+pub fn smoothing(ctx: &Context) -> SmoothingMode {
+    ctx.timer_context.smoothing
 }
 
 /// Gets the average time of a frame, averaged
@@ -203,16 +372,37 @@ pub fn fps(ctx: &Context) -> f64 {
 }
 
 /// Returns the time since the game was initialized,
-/// as reported by the system clock.
+/// as reported by the time source, the system clock by default.
 pub fn time_since_start(ctx: &Context) -> Duration {
-    let tc = &ctx.timer_context;
-    f64_to_duration(time() - tc.init_instant)
+    f64_to_duration(time_since_start_f64(ctx))
 }
 
 /// same as time_since_start, but without f64_to_duration inside
 pub fn time_since_start_f64(ctx: &Context) -> f64 {
     let tc = &ctx.timer_context;
-    time() - tc.init_instant
+    tc.now() - tc.init_instant
+}
+
+/// Replaces the clock the timer reads, the system clock by default,
+/// for example with a [`ManualClock`](struct.ManualClock.html) to make
+/// tests and input replays run with the same frame durations every time.
+///
+/// Everything timed goes through it: [`delta()`](fn.delta.html),
+/// [`time_since_start()`](fn.time_since_start.html), the fixed timestep
+/// of [`check_update_time()`](fn.check_update_time.html), and what is
+/// built on them like audio fades. [`time()`](fn.time.html) is still the
+/// system clock.
+///
+/// [`time_since_start()`](fn.time_since_start.html) starts again from zero,
+/// and the next frame lasts from now to the next tick of the new source.
+pub fn set_time_source<T>(ctx: &mut Context, source: T)
+where
+    T: TimeSource + 'static,
+{
+    let tc = &mut ctx.timer_context;
+    tc.source = BoxedTimeSource(Box::new(source));
+    tc.init_instant = tc.now();
+    tc.last_instant = tc.init_instant;
 }
 
 /// This function will return true if the time since the
@@ -271,6 +461,26 @@ pub fn remaining_update_time(ctx: &mut Context) -> Duration {
     ctx.timer_context.residual_update_dt
 }
 
+/// Returns how far into the next fixed update step the game is,
+/// as a fraction in `[0, 1]` of the step length for `target_fps`.
+///
+/// That is [`remaining_update_time()`](fn.remaining_update_time.html)
+/// divided by the step length; pass the same `target_fps` as to
+/// [`check_update_time()`](fn.check_update_time.html). Use it in
+/// [`draw()`](../event/trait.EventHandler.html#tymethod.draw)
+/// to blend the previous and the current physics state:
+///
+/// ```rust,ignore
+/// let alpha = timer::interpolation_alpha(ctx, 60);
+/// let pos = self.prev_pos + (self.pos - self.prev_pos) * alpha;
+/// ```
+pub fn interpolation_alpha(ctx: &Context, target_fps: u32) -> f32 {
+    let target_dt = duration_to_f64(fps_as_duration(target_fps));
+    let residual = duration_to_f64(ctx.timer_context.residual_update_dt);
+
+    (residual / target_dt).max(0.0).min(1.0) as f32
+}
+
 /// Gets the number of times the game has gone through its event loop.
 ///
 /// Specifically, the number of times that [`TimeContext::tick()`](struct.TimeContext.html#method.tick)